@@ -1,6 +1,21 @@
-use clap::{Parser, Subcommand};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use clap::{Args, Parser, Subcommand};
+use futures::stream::{self, StreamExt};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use serde::Serialize;
+use std::collections::HashMap;
 use std::env;
-use trimlight::{Pixel, TrimlightClient};
+use std::io::{IsTerminal, Write};
+use trimlight::{
+    diff, export_ics, find_schedule_conflicts, import_ics, parse_time, plan_config,
+    plan_holiday_schedules, solar_times, validate, BasicResponse, CalendarSchedule, CloneMode,
+    ConfigPlan, ConflictingSchedule, DateRange, DeleteMode, Device, DeviceConfig, DeviceDetails,
+    DeviceDiff, Effect, EffectConfig, EffectLibrary, EnsureAction, Finding, Holiday, HolidayPreset,
+    ImportedSchedule, LightState, Pixel, PixelConfig, ScheduleDate, ScheduleEvaluator,
+    ScheduleTime, Severity, SkippedEvent, Snapshot, SolarTime, Transition, TransitionKind,
+    TrimlightClient, TrimlightError, DAILY_SCHEDULE_SLOTS, MAX_HOLIDAY_YEAR, MIN_HOLIDAY_YEAR,
+    SWITCH_TIMER,
+};
 
 #[derive(Parser)]
 #[command(name = "trimlight")]
@@ -10,6 +25,10 @@ struct Cli {
     #[arg(long, global = true)]
     json: bool,
 
+    /// Fail when the API returns fields this tool does not know about
+    #[arg(long, global = true)]
+    strict: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -1016,6 +1035,85 @@ const CUSTOM_EFFECTS: &[EffectMode] = &[
 ];
 
 // Add helper function to get default device
+/// Read a snapshot file, or fetch the device when `source` isn't a file
+async fn load_device_state(
+    client: &TrimlightClient,
+    source: &str,
+) -> Result<DeviceDetails, Box<dyn std::error::Error>> {
+    if std::path::Path::new(source).is_file() {
+        Ok(Snapshot::load(source)?.device)
+    } else {
+        Ok(client.get_device_details(source).await?)
+    }
+}
+
+fn print_device_diff(from: &str, to: &str, changes: &DeviceDiff) {
+    let color = std::io::stdout().is_terminal();
+    let paint = |symbol: char, line: String| {
+        let code = match symbol {
+            '+' => 32,
+            '-' => 31,
+            _ => 33,
+        };
+        if color {
+            format!("\x1b[{}m{} {}\x1b[0m", code, symbol, line)
+        } else {
+            format!("{} {}", symbol, line)
+        }
+    };
+
+    println!("--- {}", from);
+    println!("+++ {}", to);
+    for change in &changes.settings {
+        println!("{}", paint('~', change.to_string()));
+    }
+    for change in changes.effects.iter().chain(&changes.schedules) {
+        println!("{}", paint(change.kind.symbol(), change.to_string()));
+    }
+}
+
+fn print_findings(findings: &[Finding]) {
+    let color = std::io::stdout().is_terminal();
+    for finding in findings {
+        let label = match (finding.severity, color) {
+            (Severity::Error, true) => "\x1b[31merror\x1b[0m".to_string(),
+            (Severity::Warning, true) => "\x1b[33mwarning\x1b[0m".to_string(),
+            (severity, false) => severity.to_string(),
+        };
+        println!("{}: {}", label, finding);
+        println!("  fix: {}", finding.fix);
+    }
+    let errors = findings
+        .iter()
+        .filter(|f| f.severity == Severity::Error)
+        .count();
+    println!(
+        "\n{} error(s), {} warning(s)",
+        errors,
+        findings.len() - errors
+    );
+}
+
+/// Ask a yes/no question on the terminal, defaulting to no
+fn confirm(prompt: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    if !std::io::stdin().is_terminal() {
+        return Err(
+            "Confirmation needed but stdin is not a terminal. Pass --yes to continue".into(),
+        );
+    }
+    eprint!("{} [y/N] ", prompt);
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+fn print_config_plan(plan: &ConfigPlan) {
+    for change in &plan.changes {
+        println!("{} {}", change.symbol(), change);
+    }
+}
+
 async fn get_default_device(
     client: &TrimlightClient,
 ) -> Result<String, Box<dyn std::error::Error>> {
@@ -1026,6 +1124,522 @@ async fn get_default_device(
     Ok(devices.data[0].device_id.clone())
 }
 
+impl DeviceTargets {
+    /// Whether the command runs on several devices
+    fn is_bulk(&self) -> bool {
+        self.all || self.match_name.is_some()
+    }
+
+    /// The single device to use when neither --all nor --match is given
+    async fn device_id(
+        &self,
+        client: &TrimlightClient,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        match &self.device {
+            Some(id) => Ok(id.clone()),
+            None => get_default_device(client).await,
+        }
+    }
+}
+
+/// Outcome of a command on one device of a bulk run
+#[derive(Serialize)]
+struct DeviceResult {
+    device_id: String,
+    name: String,
+    ok: bool,
+    result: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response: Option<serde_json::Value>,
+}
+
+/// Summary and JSON response of a successful command on one device
+type DeviceOutcome = Result<(String, serde_json::Value), TrimlightError>;
+
+/// Turn an API response into a device outcome, failing on a non-zero code
+fn outcome(response: BasicResponse, message: &str) -> DeviceOutcome {
+    if response.code != 0 {
+        return Err(TrimlightError::ApiError {
+            code: response.code,
+            message: response.desc,
+        });
+    }
+    Ok((message.to_string(), serde_json::to_value(response)?))
+}
+
+/// Names of the saved effects with these IDs on one device
+///
+/// Effect IDs are assigned by each device, so an ID given to a bulk command
+/// may be missing on some devices or name a different effect there.
+fn saved_effect_names(
+    details: &DeviceDetails,
+    effect_ids: &[i32],
+) -> Result<String, TrimlightError> {
+    let mut names = Vec::new();
+    for id in effect_ids {
+        match details.effect_name(*id) {
+            Some(name) => names.push(name.to_string()),
+            None => {
+                return Err(TrimlightError::ApiError {
+                    code: 404,
+                    message: format!("Effect {} not found on this device", id),
+                })
+            }
+        }
+    }
+    Ok(names.join(", "))
+}
+
+/// Fetch the device and look up the saved effects a bulk command refers to
+async fn check_saved_effects(
+    client: &TrimlightClient,
+    device_id: &str,
+    effect_ids: &[i32],
+) -> Result<String, TrimlightError> {
+    let details = client.get_device_details(device_id).await?;
+    saved_effect_names(&details, effect_ids)
+}
+
+/// Devices selected by --all or --match, in device list order
+fn select_devices(
+    devices: Vec<Device>,
+    targets: &DeviceTargets,
+) -> Result<Vec<Device>, Box<dyn std::error::Error>> {
+    let pattern = glob_set(targets.match_name.as_slice())?;
+    let devices: Vec<Device> = devices
+        .into_iter()
+        .filter(|d| targets.all || pattern.is_match(&d.name))
+        .collect();
+    if devices.is_empty() {
+        return Err(match &targets.match_name {
+            Some(pattern) => format!("No devices named like '{}'", pattern).into(),
+            None => "No devices found".into(),
+        });
+    }
+    Ok(devices)
+}
+
+/// Exit status of a bulk run: 1 when the command failed on any device
+fn bulk_exit_status(results: &[DeviceResult]) -> i32 {
+    if results.iter().all(|r| r.ok) {
+        0
+    } else {
+        1
+    }
+}
+
+/// Run `operation` on every device selected by --all or --match
+///
+/// Up to `--parallel` devices are worked on at once. Results are printed as a
+/// table in device list order, and the process exits with status 1 when the
+/// command failed on any device.
+async fn run_on_devices<F, Fut>(
+    client: &TrimlightClient,
+    targets: &DeviceTargets,
+    json: bool,
+    operation: F,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    F: Fn(String) -> Fut,
+    Fut: std::future::Future<Output = DeviceOutcome>,
+{
+    let devices = select_devices(client.get_all_devices().await?, targets)?;
+
+    let results: Vec<DeviceResult> = stream::iter(devices)
+        .map(|device| {
+            let run = operation(device.device_id.clone());
+            async move {
+                let (ok, result, response) = match run.await {
+                    Ok((message, response)) => (true, message, Some(response)),
+                    Err(e) => (false, e.to_string(), None),
+                };
+                DeviceResult {
+                    device_id: device.device_id,
+                    name: device.name,
+                    ok,
+                    result,
+                    response,
+                }
+            }
+        })
+        .buffered(targets.parallel as usize)
+        .collect()
+        .await;
+
+    let failed = results.iter().filter(|r| !r.ok).count();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+    } else {
+        let rows: Vec<Vec<String>> = results
+            .iter()
+            .map(|r| {
+                vec![
+                    r.device_id.clone(),
+                    r.name.clone(),
+                    if r.ok { "ok" } else { "failed" }.to_string(),
+                    r.result.clone(),
+                ]
+            })
+            .collect();
+        print_table(&["Device", "Name", "Status", "Result"], &rows);
+        println!(
+            "\n{} of {} device(s) succeeded",
+            results.len() - failed,
+            results.len()
+        );
+    }
+    let status = bulk_exit_status(&results);
+    if status != 0 {
+        std::process::exit(status);
+    }
+    Ok(())
+}
+
+// Add helper function to describe an effect by name, falling back to its ID
+fn effect_label(details: &DeviceDetails, effect_id: i32) -> String {
+    match details.effect_name(effect_id) {
+        Some(name) => format!("{} (ID: {})", name, effect_id),
+        None => format!("Effect {} (not saved)", effect_id),
+    }
+}
+
+// Add helper function to print rows as a table with aligned columns
+fn print_table(headers: &[&str], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let format_row = |cells: Vec<&str>| {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    println!("{}", format_row(headers.to_vec()));
+    println!(
+        "{}",
+        format_row(
+            widths
+                .iter()
+                .map(|w| "-".repeat(*w))
+                .collect::<Vec<_>>()
+                .iter()
+                .map(String::as_str)
+                .collect()
+        )
+    );
+    for row in rows {
+        println!("{}", format_row(row.iter().map(String::as_str).collect()));
+    }
+}
+
+/// Describe a light state using effect names from the device
+fn describe_state(details: &DeviceDetails, state: &LightState) -> String {
+    match state {
+        LightState::Off => "Off".to_string(),
+        LightState::Manual => "Manual mode, schedules are ignored".to_string(),
+        LightState::Scheduled {
+            schedule_type,
+            schedule_id,
+            effect_id,
+        } => format!(
+            "{} from {} schedule {}",
+            effect_label(details, *effect_id),
+            schedule_type,
+            schedule_id
+        ),
+    }
+}
+
+const TIMELINE_SLOT_MINUTES: i64 = 30;
+
+#[derive(Serialize)]
+struct TimelineDay {
+    date: NaiveDate,
+    /// Effect shown at the start of each slot
+    effects: Vec<Option<i32>>,
+    /// Whether a disabled daily schedule would run in each slot
+    disabled: Vec<bool>,
+}
+
+#[derive(Serialize)]
+struct Timeline {
+    week_start: NaiveDate,
+    slot_minutes: i64,
+    days: Vec<TimelineDay>,
+}
+
+/// Sample a week of schedules in half hour slots, as if the device were in timer mode
+fn build_timeline(details: &DeviceDetails, week_start: NaiveDate) -> Timeline {
+    let schedules = details.schedules();
+    let evaluator = ScheduleEvaluator::new(&schedules, SWITCH_TIMER, details.current_date());
+
+    let mut disabled_schedules = details.schedules();
+    disabled_schedules.daily.retain(|s| !s.enable);
+    disabled_schedules
+        .daily
+        .iter_mut()
+        .for_each(|s| s.enable = true);
+    disabled_schedules.calendar.clear();
+    let disabled =
+        ScheduleEvaluator::new(&disabled_schedules, SWITCH_TIMER, details.current_date());
+
+    let slots = 24 * 60 / TIMELINE_SLOT_MINUTES;
+    let days = (0..7)
+        .map(|offset| {
+            let date = week_start + Duration::days(offset);
+            let times: Vec<NaiveDateTime> = (0..slots)
+                .map(|slot| {
+                    date.and_time(NaiveTime::MIN) + Duration::minutes(slot * TIMELINE_SLOT_MINUTES)
+                })
+                .collect();
+            TimelineDay {
+                date,
+                effects: times
+                    .iter()
+                    .map(|at| evaluator.state_at(*at).effect_id())
+                    .collect(),
+                disabled: times
+                    .iter()
+                    .map(|at| disabled.state_at(*at).effect_id().is_some())
+                    .collect(),
+            }
+        })
+        .collect();
+
+    Timeline {
+        week_start,
+        slot_minutes: TIMELINE_SLOT_MINUTES,
+        days,
+    }
+}
+
+fn print_timeline(details: &DeviceDetails, timeline: &Timeline) {
+    // One character per effect, in order of effect ID
+    let mut effect_ids: Vec<i32> = timeline
+        .days
+        .iter()
+        .flat_map(|day| day.effects.iter().flatten().copied())
+        .collect();
+    effect_ids.sort();
+    effect_ids.dedup();
+    const SYMBOLS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+    let color = std::io::stdout().is_terminal();
+    let symbol = |effect_id: i32| {
+        let index = effect_ids
+            .iter()
+            .position(|id| *id == effect_id)
+            .unwrap_or(0);
+        let c = SYMBOLS[index % SYMBOLS.len()] as char;
+        if color {
+            format!("\x1b[{}m{}\x1b[0m", 31 + index % 6, c)
+        } else {
+            c.to_string()
+        }
+    };
+
+    let week_end = timeline.week_start + Duration::days(6);
+    println!(
+        "Week of {} to {}\n",
+        timeline.week_start.format("%Y-%m-%d"),
+        week_end.format("%Y-%m-%d")
+    );
+
+    let slots_per_hour = (60 / timeline.slot_minutes) as usize;
+    let mut header = String::new();
+    for hour in (0..24).step_by(3) {
+        header.push_str(&format!(
+            "{:<width$}",
+            format!("{:02}", hour),
+            width = 3 * slots_per_hour
+        ));
+    }
+    println!("{:9} {}", "", header.trim_end());
+
+    for day in &timeline.days {
+        let cells: String = day
+            .effects
+            .iter()
+            .zip(&day.disabled)
+            .map(|(effect, disabled)| match (effect, disabled) {
+                (Some(effect_id), _) => symbol(*effect_id),
+                (None, true) => "-".to_string(),
+                (None, false) => ".".to_string(),
+            })
+            .collect();
+        println!("{} {}", day.date.format("%a %m-%d"), cells);
+    }
+
+    println!();
+    for effect_id in &effect_ids {
+        println!(
+            "  {}  {}",
+            symbol(*effect_id),
+            effect_label(details, *effect_id)
+        );
+    }
+    println!("  -  Disabled daily schedule");
+    println!("  .  Off");
+
+    let dates: Vec<ScheduleDate> = timeline
+        .days
+        .iter()
+        .map(|day| ScheduleDate::from(day.date))
+        .collect();
+    let calendar: Vec<String> = details
+        .calendar
+        .iter()
+        .filter(|s| {
+            DateRange::new(&s.start_date, &s.end_date)
+                .is_some_and(|range| dates.iter().any(|d| range.contains(d)))
+        })
+        .map(|s| {
+            format!(
+                "  calendar {}: {}, {:02}-{:02} to {:02}-{:02}",
+                s.id,
+                effect_label(details, s.effect_id),
+                s.start_date.month,
+                s.start_date.day,
+                s.end_date.month,
+                s.end_date.day
+            )
+        })
+        .collect();
+    if !calendar.is_empty() {
+        println!("\nCalendar schedules this week:");
+        for line in calendar {
+            println!("{}", line);
+        }
+    }
+
+    if details.switch_state != SWITCH_TIMER {
+        println!("\nNote: the device is not in timer mode, so schedules are not running");
+    }
+}
+
+fn format_time(time: &ScheduleTime) -> String {
+    format!("{:02}:{:02}", time.hours, time.minutes)
+}
+
+fn format_date(date: &ScheduleDate) -> String {
+    format!("{:02}-{:02}", date.month, date.day)
+}
+
+fn repetition_label(repetition: i32) -> &'static str {
+    match repetition {
+        0 => "Today Only",
+        1 => "Everyday",
+        2 => "Week Days",
+        3 => "Weekend",
+        _ => "Unknown",
+    }
+}
+
+fn describe_imported(details: &DeviceDetails, schedule: &ImportedSchedule) -> String {
+    match schedule {
+        ImportedSchedule::Daily {
+            start_time,
+            end_time,
+            repetition,
+            ..
+        } => format!(
+            "daily {} {}-{}: {}",
+            repetition_label(*repetition),
+            format_time(start_time),
+            format_time(end_time),
+            effect_label(details, schedule.effect_id())
+        ),
+        ImportedSchedule::Calendar {
+            start_date,
+            end_date,
+            start_time,
+            end_time,
+            ..
+        } => format!(
+            "calendar {} to {} {}-{}: {}",
+            format_date(start_date),
+            format_date(end_date),
+            format_time(start_time),
+            format_time(end_time),
+            effect_label(details, schedule.effect_id())
+        ),
+    }
+}
+
+/// Result of `schedule solar` for one daily schedule
+#[derive(Serialize)]
+struct SolarUpdate {
+    id: i32,
+    from: String,
+    to: String,
+    changed: bool,
+}
+
+/// Parse a UTC offset such as `-05:00`, `+5:30` or `-5` into minutes
+fn parse_utc_offset(offset: &str) -> Result<i32, Box<dyn std::error::Error>> {
+    let invalid = || format!("Invalid UTC offset '{}'. Use a format like -05:00", offset);
+    let (sign, rest) = match offset.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, offset.strip_prefix('+').unwrap_or(offset)),
+    };
+    let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+    let hours: i32 = hours.parse().map_err(|_| invalid())?;
+    let minutes: i32 = minutes.parse().map_err(|_| invalid())?;
+    if hours > 14 || minutes >= 60 {
+        return Err(invalid().into());
+    }
+    Ok(sign * (hours * 60 + minutes))
+}
+
+/// UTC offset of the device clock in minutes, rounded to the nearest quarter hour
+///
+/// Falls back to the offset of this computer when the device did not report
+/// its time.
+fn device_utc_offset(details: &DeviceDetails) -> i32 {
+    match NaiveDateTime::try_from(details.current_datetime.clone()) {
+        Ok(device_time) => {
+            let minutes = (device_time - chrono::Utc::now().naive_utc()).num_minutes() as f64;
+            ((minutes / 15.0).round() * 15.0) as i32
+        }
+        Err(_) => chrono::Local::now().offset().local_minus_utc() / 60,
+    }
+}
+
+fn parse_datetime(datetime: &str) -> Result<NaiveDateTime, Box<dyn std::error::Error>> {
+    NaiveDateTime::parse_from_str(datetime, "%Y-%m-%d %H:%M")
+        .or_else(|_| NaiveDateTime::parse_from_str(datetime, "%Y-%m-%dT%H:%M"))
+        .map_err(|_| {
+            format!(
+                "Invalid date and time '{}'. Use YYYY-MM-DD HH:MM format",
+                datetime
+            )
+            .into()
+        })
+}
+
+/// Match names against shell-style patterns, ignoring case
+fn glob_set(patterns: &[String]) -> Result<GlobSet, Box<dyn std::error::Error>> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(
+            GlobBuilder::new(pattern)
+                .case_insensitive(true)
+                .literal_separator(false)
+                .build()
+                .map_err(|e| format!("Invalid pattern '{}': {}", pattern, e))?,
+        );
+    }
+    Ok(builder.build()?)
+}
+
 // Add helper function to parse pixel string
 fn parse_pixels(pixels_str: &str) -> Result<Vec<Pixel>, Box<dyn std::error::Error>> {
     pixels_str
@@ -1068,11 +1682,29 @@ fn parse_pixels(pixels_str: &str) -> Result<Vec<Pixel>, Box<dyn std::error::Erro
                 count,
                 color: ((rgb[0] as i32) << 16) | ((rgb[1] as i32) << 8) | (rgb[2] as i32),
                 disable,
+                ..Default::default()
             })
         })
         .collect()
 }
 
+/// Devices a command runs on
+#[derive(Args)]
+struct DeviceTargets {
+    /// Device ID (optional, uses first device if not specified)
+    #[arg(short, long)]
+    device: Option<String>,
+    /// Run on every device
+    #[arg(long, conflicts_with_all = ["device", "match_name"])]
+    all: bool,
+    /// Run on devices whose name matches this pattern (* and ? wildcards, case-insensitive)
+    #[arg(long = "match", value_name = "PATTERN", conflicts_with = "device")]
+    match_name: Option<String>,
+    /// Number of devices to work on at once with --all or --match
+    #[arg(long, default_value = "4", value_parser = clap::value_parser!(u16).range(1..))]
+    parallel: u16,
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// List all devices
@@ -1083,9 +1715,8 @@ enum Commands {
     },
     /// Get detailed information about a device
     Details {
-        /// Device ID (optional, uses first device if not specified)
-        #[arg(short, long)]
-        device: Option<String>,
+        #[command(flatten)]
+        devices: DeviceTargets,
     },
     /// Turn a device on or off
     #[command(after_help = "Examples:\n\
@@ -1101,9 +1732,8 @@ enum Commands {
     # Specify a particular device\n\
     trimlight-cli switch --device abc123 --off")]
     Switch {
-        /// Device ID (optional, uses first device if not specified)
-        #[arg(short, long)]
-        device: Option<String>,
+        #[command(flatten)]
+        devices: DeviceTargets,
         /// Turn device off
         #[arg(long)]
         off: bool,
@@ -1123,6 +1753,101 @@ enum Commands {
         #[arg(short, long)]
         name: String,
     },
+    /// Show the changes needed to match a configuration file
+    #[command(after_help = "Examples:\n\
+    # Compare a TOML configuration with the device\n\
+    trimlight-cli plan house.toml\n\
+    \n\
+    # Also list saved effects that are not in the file\n\
+    trimlight-cli plan house.yaml --prune")]
+    Plan {
+        /// Configuration file (.toml, .yaml or .yml)
+        file: String,
+        /// Device ID (optional, uses first device if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+        /// Delete saved effects that are not in the file
+        #[arg(long)]
+        prune: bool,
+    },
+    /// Change a device to match a configuration file
+    #[command(after_help = "Examples:\n\
+    # Apply a configuration to the first device\n\
+    trimlight-cli apply house.toml\n\
+    \n\
+    # Apply to a particular device and delete effects not in the file\n\
+    trimlight-cli apply house.toml --device abc123 --prune")]
+    Apply {
+        /// Configuration file (.toml, .yaml or .yml)
+        file: String,
+        /// Device ID (optional, uses first device if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+        /// Delete saved effects that are not in the file
+        #[arg(long)]
+        prune: bool,
+    },
+    /// Copy effects, schedules, the combined effect and overlays to another device
+    #[command(
+        after_help = "Effect IDs are remapped to the IDs the target assigns. The target's \
+    name, color order and ports are not changed.\n\
+    \n\
+    Examples:\n\
+    # Add everything from abc123 to def456, updating effects with the same name\n\
+    trimlight-cli clone --from abc123 --to def456\n\
+    \n\
+    # Make def456 an exact copy, deleting what it has now\n\
+    trimlight-cli clone --from abc123 --to def456 --replace"
+    )]
+    Clone {
+        /// Device ID to copy from
+        #[arg(long)]
+        from: String,
+        /// Device ID to copy to
+        #[arg(long)]
+        to: String,
+        /// Keep what is on the target and add to it (default)
+        #[arg(long, conflicts_with = "replace")]
+        merge: bool,
+        /// Delete everything on the target first
+        #[arg(long, conflicts_with = "merge")]
+        replace: bool,
+        /// Don't ask for confirmation before replacing
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Compare two devices or snapshots
+    #[command(
+        after_help = "Each side is a device ID or a snapshot file. Exits with status 1 \
+    when they differ.\n\
+    \n\
+    Examples:\n\
+    # Check that the front and back controllers match\n\
+    trimlight-cli diff abc123 def456\n\
+    \n\
+    # What changed since last week's snapshot?\n\
+    trimlight-cli diff last-week.json abc123"
+    )]
+    Diff {
+        /// Device ID or snapshot file to compare from
+        from: String,
+        /// Device ID or snapshot file to compare to
+        to: String,
+    },
+    /// Keep effects in a local library and push them to devices by name
+    Library {
+        /// Library file (defaults to $TRIMLIGHT_LIBRARY or ~/.trimlight/effects.toml)
+        #[arg(long, global = true)]
+        library: Option<String>,
+        #[command(subcommand)]
+        command: LibraryCommands,
+    },
+    /// Save and restore complete device snapshots
+    #[command(subcommand)]
+    Snapshot(SnapshotCommands),
+    /// Check devices for problems and suggest fixes
+    #[command(subcommand)]
+    Doctor(DoctorCommands),
     /// Manage schedules
     #[command(subcommand)]
     Schedule(ScheduleCommands),
@@ -1141,9 +1866,8 @@ enum ScheduleCommands {
     },
     /// Add a daily schedule
     Daily {
-        /// Device ID (optional, uses first device if not specified)
-        #[arg(short, long)]
-        device: Option<String>,
+        #[command(flatten)]
+        devices: DeviceTargets,
         /// Effect ID to display
         #[arg(short, long)]
         effect: i32,
@@ -1159,9 +1883,8 @@ enum ScheduleCommands {
     },
     /// Add a calendar schedule
     Calendar {
-        /// Device ID (optional, uses first device if not specified)
-        #[arg(short, long)]
-        device: Option<String>,
+        #[command(flatten)]
+        devices: DeviceTargets,
         /// Effect ID to display
         #[arg(short, long)]
         effect: i32,
@@ -1190,7 +1913,7 @@ enum ScheduleCommands {
         #[arg(short, long)]
         schedule_type: String,
     },
-    /// Enable or disable a daily schedule
+    /// Enable or disable a schedule
     Toggle {
         /// Device ID (optional, uses first device if not specified)
         #[arg(short, long)]
@@ -1198,11 +1921,17 @@ enum ScheduleCommands {
         /// Schedule ID to toggle
         #[arg(short, long)]
         id: i32,
+        /// Schedule type (only daily schedules can be enabled or disabled)
+        #[arg(short, long, default_value = "daily")]
+        schedule_type: String,
         /// Enable the schedule (if not specified, will disable)
         #[arg(long)]
         enable: bool,
     },
-    /// Modify an existing daily schedule
+    /// Modify an existing schedule
+    #[command(after_help = "Only the given fields are changed. Examples:\n\
+    trimlight-cli schedule modify --id 0 --schedule-type daily --start 17:30\n\
+    trimlight-cli schedule modify --id 1 --schedule-type calendar --start-date 11-27 --end-date 01-06")]
     Modify {
         /// Device ID (optional, uses first device if not specified)
         #[arg(short, long)]
@@ -1216,13 +1945,19 @@ enum ScheduleCommands {
         /// Effect ID to display (optional)
         #[arg(short, long)]
         effect: Option<i32>,
+        /// Start date (MM-DD format, calendar schedules only, optional)
+        #[arg(long)]
+        start_date: Option<String>,
+        /// End date (MM-DD format, calendar schedules only, optional)
+        #[arg(long)]
+        end_date: Option<String>,
         /// Start time (HH:MM format, optional)
         #[arg(long)]
         start: Option<String>,
         /// End time (HH:MM format, optional)
         #[arg(long)]
-        end: String,
-        /// Repetition type (0=today, 1=everyday, 2=weekdays, 3=weekend, optional)
+        end: Option<String>,
+        /// Repetition type (0=today, 1=everyday, 2=weekdays, 3=weekend, daily schedules only, optional)
         #[arg(short, long)]
         repeat: Option<i32>,
     },
@@ -1232,91 +1967,345 @@ enum ScheduleCommands {
         #[arg(short, long)]
         device: Option<String>,
     },
-}
-
-#[derive(Subcommand)]
-enum EffectCommands {
-    /// List saved effects
-    List {
+    /// Show what the lights will be doing at a given time
+    #[command(after_help = "Example:\n\
+    trimlight-cli schedule at \"2026-12-24 21:00\"")]
+    At {
+        /// Date and time in the device's local time (YYYY-MM-DD HH:MM format)
+        datetime: String,
         /// Device ID (optional, uses first device if not specified)
         #[arg(short, long)]
         device: Option<String>,
-        /// Show detailed information for each effect
-        #[arg(long)]
-        details: bool,
     },
-    /// List and search available effect modes
-    #[command(after_help = "Examples:\n\
-    # List all modes\n\
-    trimlight-cli effects modes\n\
-    \n\
-    # Search for modes containing 'rainbow'\n\
-    trimlight-cli effects modes --search rainbow\n\
-    \n\
-    # List only built-in modes\n\
-    trimlight-cli effects modes --built-in\n\
-    \n\
-    # List only patterns\n\
-    trimlight-cli effects modes --pattern\n\
-    \n\
-    Categories:\n\
-    - Built-in effects (modes 0-179)\n\
-    - Custom patterns for pixel-by-pixel control (patterns 0-16)")]
-    Modes {
-        /// Search for modes containing this text (case-insensitive)
+    /// List upcoming times at which the lights turn on, off or change effect
+    Next {
+        /// Device ID (optional, uses first device if not specified)
         #[arg(short, long)]
-        search: Option<String>,
-        /// Show only built-in effects
-        #[arg(long)]
-        built_in: bool,
-        /// Show only custom patterns
-        #[arg(long)]
-        pattern: bool,
+        device: Option<String>,
+        /// Number of changes to show
+        #[arg(short, long, default_value_t = 10)]
+        count: usize,
+    },
+    /// Show a week of schedules as a grid
+    #[command(after_help = "Each column is half an hour. Example:\n\
+    trimlight-cli schedule timeline --week 2026-12-21")]
+    Timeline {
+        /// Device ID (optional, uses first device if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+        /// Any date in the week to show (YYYY-MM-DD format, defaults to the current week)
+        #[arg(short, long)]
+        week: Option<String>,
     },
-    /// Preview a built-in or custom effect
-    #[command(after_help = "Examples:\n\
-    # Preview a built-in effect\n\
-    trimlight-cli effects preview --built-in 1 --speed 150 --brightness 200\n\
-    \n\
-    # Preview a built-in effect with pixel length and reverse options\n\
-    trimlight-cli effects preview --built-in 1 --pixel-len 45 --reverse\n\
-    \n\
-    # Preview a custom pattern\n\
-    trimlight-cli effects preview --pattern 1 --speed 150 --brightness 200\n\
-    \n\
-    # Preview a custom pattern with pixel colors\n\
-    trimlight-cli effects preview --pattern 1 --pixels '255,0,0;0,255,0;0,0,255'\n\
-    \n\
-    # Preview a custom pattern with pixel counts and disabled flags\n\
-    trimlight-cli effects preview --pattern 1 --pixels '255,0,0:5;0,255,0;0,0,0:2:1'\n\
-    \n\
-    # Specify a particular device\n\
-    trimlight-cli effects preview --device abc123 --built-in 1\n\
-    \n\
-    Effect Types:\n\
-    - Built-in effects (modes 0-179): Support pixel_len and reverse options\n\
-    - Custom patterns (patterns 0-16): Support pixels option for custom colors\n\
-    \n\
-    Pixel Format:\n\
-    - Basic format: 'R,G,B' where each value is 0-255\n\
-    - Extended format: 'R,G,B[:count][:disabled]'\n\
-      - count: Optional number of consecutive pixels (default: 1)\n\
-      - disabled: Optional flag (0=enabled, 1=disabled, default: 0)\n\
-    - Multiple pixels are separated by semicolons\n\
-    - Examples:\n\
-      - '255,0,0' - Single red pixel\n\
-      - '255,0,0:5' - 5 consecutive red pixels\n\
-      - '255,0,0:5:0' - 5 consecutive red pixels (enabled)\n\
-      - '0,0,0:2:1' - 2 consecutive black pixels (disabled)")]
-    Preview {
+    /// Export schedules to a calendar file
+    #[command(after_help = "Example:\n\
+    trimlight-cli schedule export --format ics --output lights.ics")]
+    Export {
         /// Device ID (optional, uses first device if not specified)
         #[arg(short, long)]
         device: Option<String>,
-        /// Built-in effect mode number (0-179)
-        #[arg(long, conflicts_with = "pattern")]
-        built_in: Option<i32>,
-        /// Custom pattern number (0-16)
-        #[arg(long, conflicts_with = "built_in")]
+        /// Output format (ics)
+        #[arg(short, long, default_value = "ics")]
+        format: String,
+        /// File to write to (prints to stdout if not specified)
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Move daily schedules to follow sunrise and sunset
+    #[command(
+        after_help = "Times are HH:MM, sunrise or sunset with an optional offset in minutes.\n\
+Schedules are only updated when their times have changed, so this is safe to run\n\
+daily from cron. Example:\n\
+    trimlight-cli schedule solar --id 0 --lat 40.71 --lon -74.01 --start sunset-15 --end 23:00"
+    )]
+    Solar {
+        /// Device ID (optional, uses first device if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+        /// Daily schedule to update (repeatable)
+        #[arg(long = "id", required = true)]
+        ids: Vec<i32>,
+        /// Latitude in degrees, positive to the north
+        #[arg(long, allow_hyphen_values = true)]
+        lat: f64,
+        /// Longitude in degrees, positive to the east
+        #[arg(long, allow_hyphen_values = true)]
+        lon: f64,
+        /// Start time (e.g. sunset-15)
+        #[arg(short, long, allow_hyphen_values = true)]
+        start: String,
+        /// End time (e.g. 23:00 or sunrise+30, keeps the current end time if not specified)
+        #[arg(short, long, allow_hyphen_values = true)]
+        end: Option<String>,
+        /// Date to compute sun times for (YYYY-MM-DD format, defaults to today on the device)
+        #[arg(long)]
+        date: Option<String>,
+        /// UTC offset of the device's time zone (e.g. -05:00, defaults to the offset of the device clock)
+        #[arg(long, allow_hyphen_values = true)]
+        utc_offset: Option<String>,
+        /// Show the changes without applying them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Create calendar schedules for holidays that move from year to year
+    #[command(
+        after_help = "Presets: us (Easter, Mother's Day, Memorial Day, Father's Day, Labor Day,\n\
+Thanksgiving), christian (Easter), jewish (Hanukkah), hindu (Diwali), chinese (Lunar\n\
+New Year) and all. Each holiday runs the saved effect of the same name unless mapped\n\
+with --effect. Last year's schedules for these holidays are replaced. Example:\n\
+    trimlight-cli schedule holidays --year 2027 --preset us --effect thanksgiving=\"Autumn Glow\""
+    )]
+    Holidays {
+        /// Device ID (optional, uses first device if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+        /// Year to schedule (defaults to the current year on the device)
+        #[arg(
+            short,
+            long,
+            value_parser = clap::value_parser!(i32)
+                .range(MIN_HOLIDAY_YEAR as i64..=MAX_HOLIDAY_YEAR as i64)
+        )]
+        year: Option<i32>,
+        /// Holiday preset (repeatable)
+        #[arg(short, long, default_value = "us")]
+        preset: Vec<String>,
+        /// Effect to run for a holiday, as HOLIDAY=EFFECT_NAME (repeatable)
+        #[arg(short, long)]
+        effect: Vec<String>,
+        /// Start time (HH:MM format)
+        #[arg(long, default_value = "18:00")]
+        start: String,
+        /// End time (HH:MM format)
+        #[arg(long, default_value = "23:00")]
+        end: String,
+        /// Show the changes without applying them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Import schedules from a calendar file
+    #[command(
+        after_help = "Event summaries must match the names of saved effects. Example:\n\
+    trimlight-cli schedule import plan.ics --dry-run"
+    )]
+    Import {
+        /// iCalendar (.ics) file to import
+        file: String,
+        /// Device ID (optional, uses first device if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+        /// Show the changes without applying them
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum LibraryCommands {
+    /// Add an effect to the library, replacing one with the same name
+    #[command(after_help = "Examples:\n\
+    trimlight-cli library add --name \"Warm White\" --built-in 0 --brightness 200\n\
+    trimlight-cli library add --name \"Candy Cane\" --pattern 1 --pixels \"255,0,0:5;255,255,255:5\"")]
+    Add {
+        /// Effect name
+        #[arg(short, long)]
+        name: String,
+        /// Built-in effect mode number (0-179)
+        #[arg(long, conflicts_with = "pattern")]
+        built_in: Option<i32>,
+        /// Custom pattern number (0-16)
+        #[arg(long, conflicts_with = "built_in")]
+        pattern: Option<i32>,
+        /// Effect speed (0-255)
+        #[arg(short, long, default_value = "100")]
+        speed: i32,
+        /// LED brightness (0-255)
+        #[arg(short, long, default_value = "100")]
+        brightness: i32,
+        /// Number of LEDs to use (1-90, built-in effects only)
+        #[arg(short, long, requires = "built_in")]
+        pixel_len: Option<i32>,
+        /// Reverse the effect animation direction (built-in effects only)
+        #[arg(short, long, requires = "built_in")]
+        reverse: bool,
+        /// Custom pixel colors (format: 'R,G,B[:count][:disabled];...', patterns only)
+        #[arg(long, requires = "pattern")]
+        pixels: Option<String>,
+    },
+    /// List effects in the library
+    List,
+    /// Show an effect in the library
+    Show {
+        /// Effect name
+        name: String,
+    },
+    /// Remove an effect from the library
+    Remove {
+        /// Effect name
+        name: String,
+    },
+    /// Create or update effects on a device by name
+    #[command(after_help = "Examples:\n\
+    trimlight-cli library push \"Candy Cane\" --device abc123\n\
+    trimlight-cli library push --all")]
+    Push {
+        /// Effect names
+        #[arg(required_unless_present = "all")]
+        names: Vec<String>,
+        /// Push every effect in the library
+        #[arg(long, conflicts_with = "names")]
+        all: bool,
+        /// Device ID (optional, uses first device if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+    /// Copy effects saved on a device into the library
+    #[command(after_help = "Examples:\n\
+    # Import every saved effect\n\
+    trimlight-cli library pull --device abc123\n\
+    \n\
+    # Import only some effects\n\
+    trimlight-cli library pull \"Candy Cane\" \"Warm White\"")]
+    Pull {
+        /// Effect names (optional, imports all effects if not specified)
+        names: Vec<String>,
+        /// Device ID (optional, uses first device if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum SnapshotCommands {
+    /// Save everything on a device to a file
+    #[command(after_help = "Examples:\n\
+    trimlight-cli snapshot save --out house.json\n\
+    trimlight-cli snapshot save --device abc123 --out front.json")]
+    Save {
+        /// Device ID (optional, uses first device if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+        /// File to write
+        #[arg(short, long)]
+        out: String,
+    },
+    /// Replace everything on a device with a snapshot
+    #[command(after_help = "Examples:\n\
+    # Restore to the device the snapshot was taken from\n\
+    trimlight-cli snapshot restore house.json\n\
+    \n\
+    # Restore to another device without asking for confirmation\n\
+    trimlight-cli snapshot restore house.json --device def456 --yes")]
+    Restore {
+        /// Snapshot file
+        file: String,
+        /// Device ID (optional, uses the device the snapshot was taken from)
+        #[arg(short, long)]
+        device: Option<String>,
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum DoctorCommands {
+    /// Check schedules, effects, the combined effect and overlays for problems
+    #[command(
+        after_help = "Looks for schedules, combined effects and overlays that use deleted \
+    effects, disabled schedules, speed, brightness and pixel lengths outside their ranges, \
+    custom patterns longer than the LEDs on the ports, and invalid dates and times. Each \
+    problem is listed with a command that fixes it. Exits with status 1 when errors are found."
+    )]
+    Config {
+        /// Device ID (optional, uses first device if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum EffectCommands {
+    /// List saved effects
+    List {
+        /// Device ID (optional, uses first device if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+        /// Show detailed information for each effect
+        #[arg(long)]
+        details: bool,
+    },
+    /// List and search available effect modes
+    #[command(after_help = "Examples:\n\
+    # List all modes\n\
+    trimlight-cli effects modes\n\
+    \n\
+    # Search for modes containing 'rainbow'\n\
+    trimlight-cli effects modes --search rainbow\n\
+    \n\
+    # List only built-in modes\n\
+    trimlight-cli effects modes --built-in\n\
+    \n\
+    # List only patterns\n\
+    trimlight-cli effects modes --pattern\n\
+    \n\
+    Categories:\n\
+    - Built-in effects (modes 0-179)\n\
+    - Custom patterns for pixel-by-pixel control (patterns 0-16)")]
+    Modes {
+        /// Search for modes containing this text (case-insensitive)
+        #[arg(short, long)]
+        search: Option<String>,
+        /// Show only built-in effects
+        #[arg(long)]
+        built_in: bool,
+        /// Show only custom patterns
+        #[arg(long)]
+        pattern: bool,
+    },
+    /// Preview a built-in or custom effect
+    #[command(after_help = "Examples:\n\
+    # Preview a built-in effect\n\
+    trimlight-cli effects preview --built-in 1 --speed 150 --brightness 200\n\
+    \n\
+    # Preview a built-in effect with pixel length and reverse options\n\
+    trimlight-cli effects preview --built-in 1 --pixel-len 45 --reverse\n\
+    \n\
+    # Preview a custom pattern\n\
+    trimlight-cli effects preview --pattern 1 --speed 150 --brightness 200\n\
+    \n\
+    # Preview a custom pattern with pixel colors\n\
+    trimlight-cli effects preview --pattern 1 --pixels '255,0,0;0,255,0;0,0,255'\n\
+    \n\
+    # Preview a custom pattern with pixel counts and disabled flags\n\
+    trimlight-cli effects preview --pattern 1 --pixels '255,0,0:5;0,255,0;0,0,0:2:1'\n\
+    \n\
+    # Specify a particular device\n\
+    trimlight-cli effects preview --device abc123 --built-in 1\n\
+    \n\
+    Effect Types:\n\
+    - Built-in effects (modes 0-179): Support pixel_len and reverse options\n\
+    - Custom patterns (patterns 0-16): Support pixels option for custom colors\n\
+    \n\
+    Pixel Format:\n\
+    - Basic format: 'R,G,B' where each value is 0-255\n\
+    - Extended format: 'R,G,B[:count][:disabled]'\n\
+      - count: Optional number of consecutive pixels (default: 1)\n\
+      - disabled: Optional flag (0=enabled, 1=disabled, default: 0)\n\
+    - Multiple pixels are separated by semicolons\n\
+    - Examples:\n\
+      - '255,0,0' - Single red pixel\n\
+      - '255,0,0:5' - 5 consecutive red pixels\n\
+      - '255,0,0:5:0' - 5 consecutive red pixels (enabled)\n\
+      - '0,0,0:2:1' - 2 consecutive black pixels (disabled)")]
+    Preview {
+        #[command(flatten)]
+        devices: DeviceTargets,
+        /// Built-in effect mode number (0-179)
+        #[arg(long, conflicts_with = "pattern")]
+        built_in: Option<i32>,
+        /// Custom pattern number (0-16)
+        #[arg(long, conflicts_with = "built_in")]
         pattern: Option<i32>,
         /// Effect animation speed (0=slowest, 255=fastest)
         #[arg(short = 's', long, default_value = "100")]
@@ -1384,6 +2373,9 @@ enum EffectCommands {
     # Update just the name\n\
     trimlight-cli effects update --id 1 --name \"New Name\"\n\
     \n\
+    # Change only the brightness, keeping all other settings\n\
+    trimlight-cli effects update --id 3 --brightness 50\n\
+    \n\
     # Specify a particular device\n\
     trimlight-cli effects update --device abc123 --id 1 --built-in 1")]
     Update {
@@ -1426,6 +2418,39 @@ enum EffectCommands {
         /// Effect ID to delete
         #[arg(short, long)]
         id: i32,
+        /// Also delete schedules that run the effect and remove it from the combined effect and overlays
+        #[arg(long, conflicts_with = "force")]
+        cascade: bool,
+        /// Delete even if schedules, the combined effect or overlays still use the effect
+        #[arg(long, conflicts_with = "cascade")]
+        force: bool,
+        /// Don't ask before deleting with --cascade or --force
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Delete saved effects that nothing uses
+    #[command(
+        after_help = "An effect is unused when no schedule, the combined effect or an \
+    overlay refers to it and the device isn't running it. Examples:\n\
+    # See what would be deleted\n\
+    trimlight-cli effects gc --dry-run\n\
+    \n\
+    # Keep effects whose names start with \"Xmas\" or end in \"(keep)\"\n\
+    trimlight-cli effects gc --keep 'Xmas*' --keep '*(keep)'"
+    )]
+    Gc {
+        /// Device ID (optional, uses first device if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+        /// Keep effects whose names match this pattern (* and ? wildcards, case-insensitive; can be repeated)
+        #[arg(short, long)]
+        keep: Vec<String>,
+        /// Only list the effects that would be deleted
+        #[arg(long)]
+        dry_run: bool,
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
     },
     /// Manage combined effects (multiple effects running in sequence)
     #[command(subcommand)]
@@ -1438,9 +2463,8 @@ enum EffectCommands {
     # Specify a particular device\n\
     trimlight-cli effects view --device abc123 --id 1")]
     View {
-        /// Device ID (optional, uses first device if not specified)
-        #[arg(short, long)]
-        device: Option<String>,
+        #[command(flatten)]
+        devices: DeviceTargets,
         /// Effect ID to view
         #[arg(short, long)]
         id: i32,
@@ -1454,9 +2478,8 @@ enum EffectCommands {
 enum CombinedCommands {
     /// Set a combined effect sequence
     Set {
-        /// Device ID (optional, uses first device if not specified)
-        #[arg(short, long)]
-        device: Option<String>,
+        #[command(flatten)]
+        devices: DeviceTargets,
         /// List of effect IDs to run in sequence (comma-separated)
         #[arg(short, long)]
         effects: String,
@@ -1466,9 +2489,8 @@ enum CombinedCommands {
     },
     /// Clear the combined effect sequence
     Clear {
-        /// Device ID (optional, uses first device if not specified)
-        #[arg(short, long)]
-        device: Option<String>,
+        #[command(flatten)]
+        devices: DeviceTargets,
     },
 }
 
@@ -1485,9 +2507,8 @@ enum OverlayCommands {
     # Specify a particular device\n\
     trimlight-cli effects overlay add --device abc123 --lightning --target 1")]
     Add {
-        /// Device ID (optional, uses first device if not specified)
-        #[arg(short, long)]
-        device: Option<String>,
+        #[command(flatten)]
+        devices: DeviceTargets,
         /// Add a lightning overlay effect
         #[arg(long)]
         lightning: bool,
@@ -1506,9 +2527,8 @@ enum OverlayCommands {
     # Specify a particular device\n\
     trimlight-cli effects overlay clear --device abc123")]
     Clear {
-        /// Device ID (optional, uses first device if not specified)
-        #[arg(short, long)]
-        device: Option<String>,
+        #[command(flatten)]
+        devices: DeviceTargets,
     },
 }
 
@@ -1529,12 +2549,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let client_secret = env::var("TRIMLIGHT_CLIENT_SECRET")
         .expect("TRIMLIGHT_CLIENT_SECRET environment variable not set");
 
-    // Create Trimlight client
-    let client = TrimlightClient::new(client_id, client_secret);
-
     // Parse command line arguments
     let cli = Cli::parse();
 
+    // Create Trimlight client
+    let client = TrimlightClient::new(client_id, client_secret).with_strict_mode(cli.strict);
+
     // Handle commands
     match cli.command {
         Commands::List { page } => {
@@ -1567,11 +2587,32 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
             }
         }
-        Commands::Details { device } => {
-            let device_id = match device {
-                Some(id) => id,
-                None => get_default_device(&client).await?,
-            };
+        Commands::Details { devices } => {
+            if devices.is_bulk() {
+                let client = &client;
+                return run_on_devices(client, &devices, cli.json, |device_id| async move {
+                    let details = client.get_device_details(&device_id).await?;
+                    let summary = format!(
+                        "{}, {}, {} effect(s), {} schedule(s)",
+                        match details.connectivity {
+                            0 => "Offline",
+                            1 => "Online",
+                            _ => "Unknown",
+                        },
+                        match details.switch_state {
+                            0 => "Off",
+                            1 => "Manual Mode",
+                            2 => "Timer Mode",
+                            _ => "Unknown",
+                        },
+                        details.effects.len(),
+                        details.daily.len() + details.calendar.len()
+                    );
+                    Ok((summary, serde_json::to_value(details)?))
+                })
+                .await;
+            }
+            let device_id = devices.device_id(&client).await?;
             let details = client.get_device_details(&device_id).await?;
             if cli.json {
                 println!("{}", serde_json::to_string_pretty(&details)?);
@@ -1600,20 +2641,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
                 if !details.ports.is_empty() {
                     println!("\nPorts:");
-                    for port in details.ports {
+                    for port in &details.ports {
                         println!("  Port {}: {} to {}", port.id, port.start, port.end);
                     }
                 }
 
                 if !details.effects.is_empty() {
                     println!("\nStored Effects:");
-                    for effect in details.effects {
+                    for effect in &details.effects {
                         println!("  {}: {} (Mode: {})", effect.id, effect.name, effect.mode);
                     }
                 }
 
-                if let Some(current) = details.current_effect {
+                if let Some(current) = &details.current_effect {
                     println!("\nCurrent Effect:");
+                    if let Some(saved) = details.current_saved_effect() {
+                        println!("  Saved Effect: {}", effect_label(&details, saved.id));
+                    }
                     println!("  Mode: {}", current.mode);
                     println!("  Speed: {}", current.speed);
                     println!("  Brightness: {}", current.brightness);
@@ -1625,14 +2669,37 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     }
                 }
 
+                let combined = details.combined_effects();
+                if !combined.is_empty() {
+                    println!("\nCombined Effect:");
+                    for (id, _) in combined {
+                        println!("  {}", effect_label(&details, id));
+                    }
+                }
+
+                if !details.overlay_effects.is_empty() {
+                    println!("\nOverlay Effects:");
+                    for (overlay, _) in details.overlay_targets() {
+                        println!(
+                            "  {} on {}",
+                            match overlay.overlay_type {
+                                0 => "Lightning",
+                                1 => "Snow",
+                                _ => "Unknown",
+                            },
+                            effect_label(&details, overlay.target_effect)
+                        );
+                    }
+                }
+
                 if !details.daily.is_empty() {
                     println!("\nDaily Schedules:");
-                    for schedule in details.daily {
+                    for schedule in &details.daily {
                         if schedule.enable {
                             println!(
-                                "  Schedule {}: Effect {} from {:02}:{:02} to {:02}:{:02}",
+                                "  Schedule {}: {} from {:02}:{:02} to {:02}:{:02}",
                                 schedule.id,
-                                schedule.effect_id,
+                                effect_label(&details, schedule.effect_id),
                                 schedule.start_time.hours,
                                 schedule.start_time.minutes,
                                 schedule.end_time.hours,
@@ -1644,10 +2711,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
                 if !details.calendar.is_empty() {
                     println!("\nCalendar Schedules:");
-                    for schedule in details.calendar {
-                        println!("  Schedule {}: Effect {} from {}/{} to {}/{} ({:02}:{:02} to {:02}:{:02})",
+                    for schedule in &details.calendar {
+                        println!(
+                            "  Schedule {}: {} from {}/{} to {}/{} ({:02}:{:02} to {:02}:{:02})",
                             schedule.id,
-                            schedule.effect_id,
+                            effect_label(&details, schedule.effect_id),
                             schedule.start_date.month,
                             schedule.start_date.day,
                             schedule.end_date.month,
@@ -1662,7 +2730,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
         Commands::Switch {
-            device,
+            devices,
             off,
             manual,
             timer,
@@ -1686,10 +2754,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 2 // timer
             };
 
-            let device_id = match device {
-                Some(id) => id,
-                None => get_default_device(&client).await?,
-            };
+            if devices.is_bulk() {
+                let client = &client;
+                return run_on_devices(client, &devices, cli.json, |device_id| async move {
+                    outcome(
+                        client.set_device_switch_state(&device_id, state).await?,
+                        "State updated",
+                    )
+                })
+                .await;
+            }
+            let device_id = devices.device_id(&client).await?;
             let response = client.set_device_switch_state(&device_id, state).await?;
             if cli.json {
                 println!("{}", serde_json::to_string_pretty(&response)?);
@@ -1717,37 +2792,77 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
             }
         }
-        Commands::Schedule(schedule_command) => match schedule_command {
-            ScheduleCommands::List { device } => {
-                let device_id = match device {
-                    Some(id) => id,
-                    None => get_default_device(&client).await?,
-                };
-                let schedules = client.get_device_schedules(&device_id).await?;
-                if cli.json {
-                    println!("{}", serde_json::to_string_pretty(&schedules)?);
-                } else {
-                    println!("Daily Schedules:");
-                    for schedule in &schedules.daily {
-                        println!(
-                            "- Schedule {}: Effect {} from {:02}:{:02} to {:02}:{:02}",
-                            schedule.id,
-                            schedule.effect_id,
-                            schedule.start_time.hours,
-                            schedule.start_time.minutes,
-                            schedule.end_time.hours,
-                            schedule.end_time.minutes
-                        );
-                        println!(
-                            "  Repetition: {}",
-                            match schedule.repetition {
-                                0 => "Today Only",
-                                1 => "Everyday",
-                                2 => "Week Days",
-                                3 => "Weekend",
-                                _ => "Unknown",
-                            }
+        Commands::Plan {
+            file,
+            device,
+            prune,
+        } => {
+            let config = DeviceConfig::load(&file)?;
+            let device_id = match device {
+                Some(id) => id,
+                None => get_default_device(&client).await?,
+            };
+            let details = client.get_device_details(&device_id).await?;
+            let plan = plan_config(&config, &details, prune)?;
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&plan)?);
+            } else if plan.is_empty() {
+                println!("No changes. The device matches {}", file);
+            } else {
+                print_config_plan(&plan);
+                println!(
+                    "\n{} change(s). Run `trimlight-cli apply {}` to make them",
+                    plan.changes.len(),
+                    file
+                );
+            }
+        }
+        Commands::Apply {
+            file,
+            device,
+            prune,
+        } => {
+            let config = DeviceConfig::load(&file)?;
+            let device_id = match device {
+                Some(id) => id,
+                None => get_default_device(&client).await?,
+            };
+            let details = client.get_device_details(&device_id).await?;
+            let plan = plan_config(&config, &details, prune)?;
+            if !plan.is_empty() {
+                client.apply_config(&device_id, &plan).await?;
+            }
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&plan)?);
+            } else if plan.is_empty() {
+                println!("No changes. The device matches {}", file);
+            } else {
+                print_config_plan(&plan);
+                println!("\nApplied {} change(s)", plan.changes.len());
+            }
+        }
+        Commands::Schedule(schedule_command) => match schedule_command {
+            ScheduleCommands::List { device } => {
+                let device_id = match device {
+                    Some(id) => id,
+                    None => get_default_device(&client).await?,
+                };
+                let schedules = client.get_device_schedules(&device_id).await?;
+                if cli.json {
+                    println!("{}", serde_json::to_string_pretty(&schedules)?);
+                } else {
+                    println!("Daily Schedules:");
+                    for schedule in &schedules.daily {
+                        println!(
+                            "- Schedule {}: Effect {} from {:02}:{:02} to {:02}:{:02}",
+                            schedule.id,
+                            schedule.effect_id,
+                            schedule.start_time.hours,
+                            schedule.start_time.minutes,
+                            schedule.end_time.hours,
+                            schedule.end_time.minutes
                         );
+                        println!("  Repetition: {}", repetition_label(schedule.repetition));
                         println!(
                             "  Status: {}",
                             if schedule.enable {
@@ -1780,16 +2895,33 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
             }
             ScheduleCommands::Daily {
-                device,
+                devices,
                 effect,
                 start,
                 end,
                 repeat,
             } => {
-                let device_id = match device {
-                    Some(id) => id,
-                    None => get_default_device(&client).await?,
-                };
+                if devices.is_bulk() {
+                    let client = &client;
+                    let (start, end) = (&start, &end);
+                    return run_on_devices(client, &devices, cli.json, |device_id| async move {
+                        let name = check_saved_effects(client, &device_id, &[effect]).await?;
+                        outcome(
+                            client
+                                .add_daily_schedule(
+                                    &device_id,
+                                    effect,
+                                    start.clone(),
+                                    end.clone(),
+                                    repeat,
+                                )
+                                .await?,
+                            &format!("Daily schedule added for {}", name),
+                        )
+                    })
+                    .await;
+                }
+                let device_id = devices.device_id(&client).await?;
                 let response = client
                     .add_daily_schedule(&device_id, effect, start, end, repeat)
                     .await?;
@@ -1804,110 +2936,954 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
             }
             ScheduleCommands::Calendar {
-                device,
+                devices,
                 effect,
                 start_date,
                 end_date,
                 start_time,
                 end_time,
+            } => {
+                if devices.is_bulk() {
+                    let client = &client;
+                    let (start_date, end_date) = (&start_date, &end_date);
+                    let (start_time, end_time) = (&start_time, &end_time);
+                    return run_on_devices(client, &devices, cli.json, |device_id| async move {
+                        let name = check_saved_effects(client, &device_id, &[effect]).await?;
+                        outcome(
+                            client
+                                .add_calendar_schedule(
+                                    &device_id,
+                                    effect,
+                                    start_date.clone(),
+                                    end_date.clone(),
+                                    start_time.clone(),
+                                    end_time.clone(),
+                                )
+                                .await?,
+                            &format!("Calendar schedule added for {}", name),
+                        )
+                    })
+                    .await;
+                }
+                let device_id = devices.device_id(&client).await?;
+                let response = client
+                    .add_calendar_schedule(
+                        &device_id, effect, start_date, end_date, start_time, end_time,
+                    )
+                    .await?;
+                if cli.json {
+                    println!("{}", serde_json::to_string_pretty(&response)?);
+                } else {
+                    if response.code == 0 {
+                        println!("Calendar schedule added successfully");
+                    } else {
+                        println!("Error: {} (code: {})", response.desc, response.code);
+                    }
+                }
+            }
+            ScheduleCommands::Delete {
+                device,
+                id,
+                schedule_type,
             } => {
                 let device_id = match device {
                     Some(id) => id,
                     None => get_default_device(&client).await?,
                 };
                 let response = client
-                    .add_calendar_schedule(
-                        &device_id, effect, start_date, end_date, start_time, end_time,
+                    .delete_schedule(&device_id, id, &schedule_type)
+                    .await?;
+                if cli.json {
+                    println!("{}", serde_json::to_string_pretty(&response)?);
+                } else {
+                    if response.code == 0 {
+                        println!("Schedule deleted successfully");
+                    } else {
+                        println!("Error: {} (code: {})", response.desc, response.code);
+                    }
+                }
+            }
+            ScheduleCommands::Toggle {
+                device,
+                id,
+                schedule_type,
+                enable,
+            } => {
+                let device_id = match device {
+                    Some(id) => id,
+                    None => get_default_device(&client).await?,
+                };
+                let response = client
+                    .toggle_schedule(&device_id, id, &schedule_type, enable)
+                    .await?;
+                if cli.json {
+                    println!("{}", serde_json::to_string_pretty(&response)?);
+                } else {
+                    if response.code == 0 {
+                        println!("Schedule toggled successfully");
+                    } else {
+                        println!("Error: {} (code: {})", response.desc, response.code);
+                    }
+                }
+            }
+            ScheduleCommands::Modify {
+                device,
+                id,
+                schedule_type,
+                effect,
+                start_date,
+                end_date,
+                start,
+                end,
+                repeat,
+            } => {
+                let device_id = match device {
+                    Some(id) => id,
+                    None => get_default_device(&client).await?,
+                };
+                let response = client
+                    .modify_schedule(
+                        &device_id,
+                        id,
+                        &schedule_type,
+                        effect,
+                        start_date,
+                        end_date,
+                        start,
+                        end,
+                        repeat,
                     )
                     .await?;
                 if cli.json {
                     println!("{}", serde_json::to_string_pretty(&response)?);
                 } else {
                     if response.code == 0 {
-                        println!("Calendar schedule added successfully");
+                        println!("Schedule modified successfully");
                     } else {
                         println!("Error: {} (code: {})", response.desc, response.code);
                     }
                 }
             }
-            ScheduleCommands::Delete {
-                device,
-                id,
-                schedule_type,
-            } => {
+            ScheduleCommands::Check { device } => {
+                let device_id = match device {
+                    Some(id) => id,
+                    None => get_default_device(&client).await?,
+                };
+                let details = client.get_device_details(&device_id).await?;
+                let conflicts = find_schedule_conflicts(
+                    &details.daily,
+                    &details.calendar,
+                    details.current_date(),
+                );
+                if cli.json {
+                    println!("{}", serde_json::to_string_pretty(&conflicts)?);
+                } else if conflicts.is_empty() {
+                    println!("No schedule conflicts found");
+                } else {
+                    let rows: Vec<Vec<String>> = conflicts
+                        .iter()
+                        .map(|conflict| {
+                            let describe = |schedule: &ConflictingSchedule| {
+                                format!(
+                                    "{} {}: {}",
+                                    schedule.schedule_type,
+                                    schedule.id,
+                                    effect_label(&details, schedule.effect_id)
+                                )
+                            };
+                            vec![
+                                describe(&conflict.first),
+                                describe(&conflict.second),
+                                conflict.days.to_string(),
+                                format!(
+                                    "{:02}:{:02}-{:02}:{:02}",
+                                    conflict.start_time.hours,
+                                    conflict.start_time.minutes,
+                                    conflict.end_time.hours,
+                                    conflict.end_time.minutes
+                                ),
+                                format!(
+                                    "{} ({})",
+                                    describe(&conflict.runs),
+                                    conflict.explanation()
+                                ),
+                            ]
+                        })
+                        .collect();
+                    println!("Found {} schedule conflicts:\n", conflicts.len());
+                    print_table(
+                        &["Schedule", "Conflicts With", "Days", "Time", "Runs"],
+                        &rows,
+                    );
+                }
+                if !conflicts.is_empty() {
+                    std::process::exit(1);
+                }
+            }
+            ScheduleCommands::At { datetime, device } => {
+                let at = parse_datetime(&datetime)?;
+                let device_id = match device {
+                    Some(id) => id,
+                    None => get_default_device(&client).await?,
+                };
+                let details = client.get_device_details(&device_id).await?;
+                let schedules = details.schedules();
+                let evaluator = ScheduleEvaluator::new(
+                    &schedules,
+                    details.switch_state,
+                    details.current_date(),
+                );
+                let state = evaluator.state_at(at);
+                if cli.json {
+                    println!("{}", serde_json::to_string_pretty(&state)?);
+                } else {
+                    println!(
+                        "{}: {}",
+                        at.format("%a %Y-%m-%d %H:%M"),
+                        describe_state(&details, &state)
+                    );
+                }
+            }
+            ScheduleCommands::Next { device, count } => {
+                let device_id = match device {
+                    Some(id) => id,
+                    None => get_default_device(&client).await?,
+                };
+                let details = client.get_device_details(&device_id).await?;
+                let schedules = details.schedules();
+                let evaluator = ScheduleEvaluator::new(
+                    &schedules,
+                    details.switch_state,
+                    details.current_date(),
+                );
+                let transitions: Vec<Transition> = evaluator
+                    .transitions(details.current_time())
+                    .take(count)
+                    .collect();
+                if cli.json {
+                    println!("{}", serde_json::to_string_pretty(&transitions)?);
+                } else if transitions.is_empty() {
+                    println!(
+                        "No upcoming changes ({})",
+                        describe_state(&details, &evaluator.state_at(details.current_time()))
+                    );
+                } else {
+                    let rows: Vec<Vec<String>> = transitions
+                        .iter()
+                        .map(|transition| {
+                            let change = match transition.kind() {
+                                TransitionKind::On => "On",
+                                TransitionKind::Off => "Off",
+                                TransitionKind::EffectChange => "Change",
+                            };
+                            vec![
+                                transition.at.format("%a %Y-%m-%d %H:%M").to_string(),
+                                change.to_string(),
+                                describe_state(&details, &transition.to),
+                            ]
+                        })
+                        .collect();
+                    print_table(&["Time", "Change", "Lights"], &rows);
+                }
+            }
+            ScheduleCommands::Timeline { device, week } => {
+                let device_id = match device {
+                    Some(id) => id,
+                    None => get_default_device(&client).await?,
+                };
+                let details = client.get_device_details(&device_id).await?;
+                let day = match week {
+                    Some(week) => NaiveDate::parse_from_str(&week, "%Y-%m-%d")
+                        .map_err(|_| format!("Invalid week '{}'. Use YYYY-MM-DD format", week))?,
+                    None => details.current_date(),
+                };
+                let week_start = day - Duration::days(day.weekday().num_days_from_monday() as i64);
+                let timeline = build_timeline(&details, week_start);
+                if cli.json {
+                    println!("{}", serde_json::to_string_pretty(&timeline)?);
+                } else {
+                    print_timeline(&details, &timeline);
+                }
+            }
+            ScheduleCommands::Export {
+                device,
+                format,
+                output,
+            } => {
+                if format != "ics" {
+                    return Err(
+                        format!("Unsupported format '{}'. Supported formats: ics", format).into(),
+                    );
+                }
+                let device_id = match device {
+                    Some(id) => id,
+                    None => get_default_device(&client).await?,
+                };
+                let details = client.get_device_details(&device_id).await?;
+                let ics = export_ics(
+                    &details.schedules(),
+                    &details.effects,
+                    details.current_date(),
+                    chrono::Utc::now(),
+                );
+                match output {
+                    Some(path) => {
+                        std::fs::write(&path, ics)?;
+                        println!(
+                            "Exported {} daily and {} calendar schedules to {}",
+                            details.daily.len(),
+                            details.calendar.len(),
+                            path
+                        );
+                    }
+                    None => print!("{}", ics),
+                }
+            }
+            ScheduleCommands::Solar {
+                device,
+                ids,
+                lat,
+                lon,
+                start,
+                end,
+                date,
+                utc_offset,
+                dry_run,
+            } => {
+                if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+                    return Err("Latitude must be within ±90 and longitude within ±180".into());
+                }
+                let start: SolarTime = start.parse()?;
+                let end: Option<SolarTime> = end.map(|end| end.parse()).transpose()?;
+                let device_id = match device {
+                    Some(id) => id,
+                    None => get_default_device(&client).await?,
+                };
+                let details = client.get_device_details(&device_id).await?;
+                let date = match date {
+                    Some(date) => NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+                        .map_err(|_| format!("Invalid date '{}'. Use YYYY-MM-DD format", date))?,
+                    None => details.current_date(),
+                };
+                let utc_offset = match utc_offset {
+                    Some(offset) => parse_utc_offset(&offset)?,
+                    None => device_utc_offset(&details),
+                };
+                let sun = solar_times(date, lat, lon, utc_offset);
+
+                let new_start = start.resolve(&sun).ok_or_else(|| {
+                    format!("The sun does not rise or set on {} at this location", date)
+                })?;
+                let new_end = match &end {
+                    Some(end) => Some(end.resolve(&sun).ok_or_else(|| {
+                        format!("The sun does not rise or set on {} at this location", date)
+                    })?),
+                    None => None,
+                };
+
+                for id in &ids {
+                    if !details.daily.iter().any(|s| s.id == *id) {
+                        return Err(format!("Daily schedule {} not found", id).into());
+                    }
+                }
+                let mut updates = Vec::new();
+                for schedule in details.daily.iter().filter(|s| ids.contains(&s.id)) {
+                    let end_time = new_end.clone().unwrap_or_else(|| schedule.end_time.clone());
+                    let changed = schedule.start_time != new_start || schedule.end_time != end_time;
+                    updates.push(SolarUpdate {
+                        id: schedule.id,
+                        from: format!(
+                            "{}-{}",
+                            format_time(&schedule.start_time),
+                            format_time(&schedule.end_time)
+                        ),
+                        to: format!("{}-{}", format_time(&new_start), format_time(&end_time)),
+                        changed,
+                    });
+                    if changed && !dry_run {
+                        client
+                            .modify_schedule(
+                                &device_id,
+                                schedule.id,
+                                "daily",
+                                None,
+                                None,
+                                None,
+                                Some(format_time(&new_start)),
+                                Some(format_time(&end_time)),
+                                None,
+                            )
+                            .await?;
+                    }
+                }
+
+                if cli.json {
+                    println!("{}", serde_json::to_string_pretty(&updates)?);
+                } else {
+                    let format_sun = |time: Option<NaiveTime>| {
+                        time.map(|t| t.format("%H:%M").to_string())
+                            .unwrap_or_else(|| "-".to_string())
+                    };
+                    println!(
+                        "Sunrise {}, sunset {} on {}",
+                        format_sun(sun.sunrise),
+                        format_sun(sun.sunset),
+                        date
+                    );
+                    for update in &updates {
+                        if !update.changed {
+                            println!("Daily schedule {} already at {}", update.id, update.to);
+                        } else if dry_run {
+                            println!(
+                                "Daily schedule {} would move from {} to {}",
+                                update.id, update.from, update.to
+                            );
+                        } else {
+                            println!(
+                                "Daily schedule {} moved from {} to {}",
+                                update.id, update.from, update.to
+                            );
+                        }
+                    }
+                }
+            }
+            ScheduleCommands::Holidays {
+                device,
+                year,
+                preset,
+                effect,
+                start,
+                end,
+                dry_run,
+            } => {
+                let mut holidays: Vec<Holiday> = Vec::new();
+                for preset in &preset {
+                    for holiday in preset.parse::<HolidayPreset>()?.holidays() {
+                        if !holidays.contains(&holiday) {
+                            holidays.push(holiday);
+                        }
+                    }
+                }
+                let mut effect_names: HashMap<Holiday, String> = HashMap::new();
+                for mapping in &effect {
+                    let (holiday, name) = mapping.split_once('=').ok_or_else(|| {
+                        format!(
+                            "Invalid effect mapping '{}'. Use HOLIDAY=EFFECT_NAME",
+                            mapping
+                        )
+                    })?;
+                    effect_names.insert(holiday.parse()?, name.trim().to_string());
+                }
+                let (start_hours, start_minutes) = parse_time(&start)?;
+                let (end_hours, end_minutes) = parse_time(&end)?;
+                let start_time = ScheduleTime::new(start_hours, start_minutes)?;
+                let end_time = ScheduleTime::new(end_hours, end_minutes)?;
+
+                let device_id = match device {
+                    Some(id) => id,
+                    None => get_default_device(&client).await?,
+                };
+                let details = client.get_device_details(&device_id).await?;
+                let year = year.unwrap_or_else(|| details.current_date().year());
+
+                let mut mapped = Vec::new();
+                let mut missing = Vec::new();
+                for holiday in holidays {
+                    let name = effect_names
+                        .get(&holiday)
+                        .map(String::as_str)
+                        .unwrap_or(holiday.name());
+                    match details
+                        .effects
+                        .iter()
+                        .find(|e| e.name.eq_ignore_ascii_case(name))
+                    {
+                        Some(effect) => mapped.push((holiday, effect.id)),
+                        None => missing.push(SkippedEvent {
+                            summary: holiday.name().to_string(),
+                            reason: format!(
+                                "no saved effect named '{}', map one with --effect {}=NAME",
+                                name,
+                                holiday.key()
+                            ),
+                        }),
+                    }
+                }
+                let plan = plan_holiday_schedules(
+                    year,
+                    &mapped,
+                    &details.calendar,
+                    &start_time,
+                    &end_time,
+                )?;
+
+                if cli.json {
+                    println!(
+                        "{}",
+                        serde_json::to_string_pretty(&serde_json::json!({
+                            "year": year,
+                            "add": plan.add,
+                            "remove": plan.remove,
+                            "unchanged": plan.unchanged,
+                            "skipped": missing,
+                        }))?
+                    );
+                } else {
+                    let describe = |schedule: &CalendarSchedule| {
+                        format!(
+                            "calendar {} to {} {}-{}: {}",
+                            format_date(&schedule.start_date),
+                            format_date(&schedule.end_date),
+                            format_time(&schedule.start_time),
+                            format_time(&schedule.end_time),
+                            effect_label(&details, schedule.effect_id)
+                        )
+                    };
+                    for schedule in &plan.remove {
+                        println!("- {} (id {})", describe(schedule), schedule.id);
+                    }
+                    for schedule in &plan.add {
+                        println!("+ {}", describe(schedule));
+                    }
+                    for schedule in &plan.unchanged {
+                        println!("= {} (already on device)", describe(schedule));
+                    }
+                    for skipped in &missing {
+                        println!("! {}: {}", skipped.summary, skipped.reason);
+                    }
+                }
+
+                if dry_run {
+                    if !cli.json {
+                        println!(
+                            "\nDry run: {} to add, {} to remove, {} already on device",
+                            plan.add.len(),
+                            plan.remove.len(),
+                            plan.unchanged.len()
+                        );
+                    }
+                } else {
+                    for schedule in &plan.remove {
+                        client
+                            .delete_schedule(&device_id, schedule.id, "calendar")
+                            .await?;
+                    }
+                    for schedule in &plan.add {
+                        client
+                            .add_calendar_schedule(
+                                &device_id,
+                                schedule.effect_id,
+                                format_date(&schedule.start_date),
+                                format_date(&schedule.end_date),
+                                format_time(&schedule.start_time),
+                                format_time(&schedule.end_time),
+                            )
+                            .await?;
+                    }
+                }
+            }
+            ScheduleCommands::Import {
+                file,
+                device,
+                dry_run,
+            } => {
+                let ics = std::fs::read_to_string(&file)?;
+                let device_id = match device {
+                    Some(id) => id,
+                    None => get_default_device(&client).await?,
+                };
+                let details = client.get_device_details(&device_id).await?;
+                let import = import_ics(&ics, &details.effects, details.current_date())?;
+
+                let schedules = details.schedules();
+                let mut daily_count = schedules.daily.len();
+                let mut added = Vec::new();
+                let mut unchanged = Vec::new();
+                let mut skipped: Vec<SkippedEvent> = import.skipped.clone();
+                for event in &import.events {
+                    let is_daily = matches!(event.schedule, ImportedSchedule::Daily { .. });
+                    if event.schedule.exists_in(&schedules) {
+                        unchanged.push(event);
+                    } else if is_daily && daily_count >= DAILY_SCHEDULE_SLOTS {
+                        skipped.push(SkippedEvent {
+                            summary: event.summary.clone(),
+                            reason: "the device has no free daily schedule".to_string(),
+                        });
+                    } else {
+                        daily_count += is_daily as usize;
+                        added.push(event);
+                    }
+                }
+
+                if cli.json {
+                    println!(
+                        "{}",
+                        serde_json::to_string_pretty(&serde_json::json!({
+                            "added": added,
+                            "unchanged": unchanged,
+                            "skipped": skipped,
+                        }))?
+                    );
+                } else {
+                    for event in &added {
+                        println!("+ {}", describe_imported(&details, &event.schedule));
+                        for note in &event.notes {
+                            println!("    note: {}", note);
+                        }
+                    }
+                    for event in &unchanged {
+                        println!(
+                            "= {} (already on device)",
+                            describe_imported(&details, &event.schedule)
+                        );
+                    }
+                    for event in &skipped {
+                        println!("! {}: {}", event.summary, event.reason);
+                    }
+                }
+
+                if dry_run {
+                    if !cli.json {
+                        println!(
+                            "\nDry run: {} to add, {} already on device, {} can't be imported",
+                            added.len(),
+                            unchanged.len(),
+                            skipped.len()
+                        );
+                    }
+                } else {
+                    for event in &added {
+                        match &event.schedule {
+                            ImportedSchedule::Daily {
+                                effect_id,
+                                start_time,
+                                end_time,
+                                repetition,
+                            } => {
+                                client
+                                    .add_daily_schedule(
+                                        &device_id,
+                                        *effect_id,
+                                        format_time(start_time),
+                                        format_time(end_time),
+                                        *repetition,
+                                    )
+                                    .await?;
+                            }
+                            ImportedSchedule::Calendar {
+                                effect_id,
+                                start_date,
+                                end_date,
+                                start_time,
+                                end_time,
+                            } => {
+                                client
+                                    .add_calendar_schedule(
+                                        &device_id,
+                                        *effect_id,
+                                        format_date(start_date),
+                                        format_date(end_date),
+                                        format_time(start_time),
+                                        format_time(end_time),
+                                    )
+                                    .await?;
+                            }
+                        }
+                    }
+                    if !cli.json {
+                        println!(
+                            "\nAdded {} schedules, {} can't be imported",
+                            added.len(),
+                            skipped.len()
+                        );
+                    }
+                }
+            }
+        },
+        Commands::Clone {
+            from,
+            to,
+            merge: _,
+            replace,
+            yes,
+        } => {
+            let mode = if replace {
+                CloneMode::Replace
+            } else {
+                CloneMode::Merge
+            };
+            if replace && !yes {
+                let prompt = format!("Delete all effects and schedules on {} first?", to);
+                if !confirm(&prompt)? {
+                    println!("Clone cancelled");
+                    return Ok(());
+                }
+            }
+            let report = client.clone_device(&from, &to, mode).await?;
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                for (old, new) in &report.effect_ids {
+                    println!("Effect {} on {} is {} on {}", old, from, new, to);
+                }
+                for skipped in &report.skipped {
+                    println!("Skipped: {}", skipped);
+                }
+                println!(
+                    "Added {} and updated {} effect(s), added {} daily and {} calendar schedule(s)",
+                    report.added_effects, report.updated_effects, report.daily, report.calendar
+                );
+            }
+        }
+        Commands::Doctor(command) => match command {
+            DoctorCommands::Config { device } => {
                 let device_id = match device {
                     Some(id) => id,
                     None => get_default_device(&client).await?,
                 };
-                let response = client
-                    .delete_schedule(&device_id, id, &schedule_type)
-                    .await?;
+                let details = client.get_device_details(&device_id).await?;
+                let findings = validate(&device_id, &details);
                 if cli.json {
-                    println!("{}", serde_json::to_string_pretty(&response)?);
+                    println!("{}", serde_json::to_string_pretty(&findings)?);
+                } else if findings.is_empty() {
+                    println!("No problems found");
                 } else {
-                    if response.code == 0 {
-                        println!("Schedule deleted successfully");
+                    print_findings(&findings);
+                }
+                if findings.iter().any(|f| f.severity == Severity::Error) {
+                    std::process::exit(1);
+                }
+            }
+        },
+        Commands::Diff { from, to } => {
+            let old = load_device_state(&client, &from).await?;
+            let new = load_device_state(&client, &to).await?;
+            let changes = diff(&old, &new);
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&changes)?);
+            } else if changes.is_empty() {
+                println!("No differences");
+            } else {
+                print_device_diff(&from, &to, &changes);
+            }
+            if !changes.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        Commands::Library { library, command } => {
+            let path = match library.or_else(|| env::var("TRIMLIGHT_LIBRARY").ok()) {
+                Some(path) => std::path::PathBuf::from(path),
+                None => dirs::home_dir()
+                    .ok_or("Can't find the home directory. Pass --library")?
+                    .join(".trimlight")
+                    .join("effects.toml"),
+            };
+            let mut effects = EffectLibrary::open(path)?;
+            match command {
+                LibraryCommands::Add {
+                    name,
+                    built_in,
+                    pattern,
+                    speed,
+                    brightness,
+                    pixel_len,
+                    reverse,
+                    pixels,
+                } => {
+                    let pixels = match pixels {
+                        Some(pixels) => parse_pixels(&pixels)?,
+                        None => Vec::new(),
+                    };
+                    let effect = EffectConfig {
+                        name: name.clone(),
+                        built_in,
+                        pattern,
+                        speed,
+                        brightness,
+                        pixel_len: built_in.map(|_| pixel_len.unwrap_or(Effect::DEFAULT_PIXEL_LEN)),
+                        reverse: built_in.map(|_| reverse),
+                        pixels: pixels.iter().map(PixelConfig::from).collect(),
+                    };
+                    let replaced = effects.insert(effect)?;
+                    effects.save()?;
+                    if replaced.is_some() {
+                        println!("Replaced {} in {}", name, effects.path().display());
                     } else {
-                        println!("Error: {} (code: {})", response.desc, response.code);
+                        println!("Added {} to {}", name, effects.path().display());
                     }
                 }
-            }
-            ScheduleCommands::Toggle { device, id, enable } => {
-                let device_id = match device {
-                    Some(id) => id,
-                    None => get_default_device(&client).await?,
-                };
-                let response = client.toggle_schedule(&device_id, id, enable).await?;
-                if cli.json {
-                    println!("{}", serde_json::to_string_pretty(&response)?);
-                } else {
-                    if response.code == 0 {
-                        println!("Schedule toggled successfully");
+                LibraryCommands::List => {
+                    if cli.json {
+                        println!("{}", serde_json::to_string_pretty(effects.effects())?);
+                    } else if effects.effects().is_empty() {
+                        println!("No effects in {}", effects.path().display());
                     } else {
-                        println!("Error: {} (code: {})", response.desc, response.code);
+                        for effect in effects.effects() {
+                            let kind = match (effect.built_in, effect.pattern) {
+                                (Some(mode), _) => format!("built-in {}", mode),
+                                (_, Some(mode)) => format!("pattern {}", mode),
+                                _ => "unknown".to_string(),
+                            };
+                            println!(
+                                "{:<24} {:<14} speed {:>3}  brightness {:>3}",
+                                effect.name, kind, effect.speed, effect.brightness
+                            );
+                        }
+                    }
+                }
+                LibraryCommands::Show { name } => {
+                    let effect = effects
+                        .get(&name)
+                        .ok_or_else(|| format!("No effect named {} in the library", name))?;
+                    if cli.json {
+                        println!("{}", serde_json::to_string_pretty(effect)?);
+                    } else {
+                        print!("{}", toml::to_string(effect)?);
+                    }
+                }
+                LibraryCommands::Remove { name } => {
+                    if effects.remove(&name).is_none() {
+                        return Err(format!("No effect named {} in the library", name).into());
+                    }
+                    effects.save()?;
+                    println!("Removed {} from {}", name, effects.path().display());
+                }
+                LibraryCommands::Push { names, all, device } => {
+                    let selected: Vec<&EffectConfig> = if all {
+                        effects.effects().iter().collect()
+                    } else {
+                        names
+                            .iter()
+                            .map(|name| {
+                                effects.get(name).ok_or_else(|| {
+                                    format!("No effect named {} in the library", name)
+                                })
+                            })
+                            .collect::<Result<_, _>>()?
+                    };
+                    let device_id = match device {
+                        Some(id) => id,
+                        None => get_default_device(&client).await?,
+                    };
+                    let mut results = Vec::new();
+                    for effect in selected {
+                        let outcome = client
+                            .ensure_effect(&device_id, &effect.to_effect()?)
+                            .await?;
+                        if !cli.json {
+                            match outcome {
+                                EnsureAction::Created { id: Some(id) } => {
+                                    println!("Created {} (id={})", effect.name, id)
+                                }
+                                EnsureAction::Created { id: None } => {
+                                    println!("Created {}", effect.name)
+                                }
+                                EnsureAction::Updated { id } => {
+                                    println!("Updated {} (id={})", effect.name, id)
+                                }
+                                EnsureAction::Unchanged { id } => {
+                                    println!("{} is up to date (id={})", effect.name, id)
+                                }
+                            }
+                        }
+                        results.push(serde_json::json!({
+                            "name": effect.name,
+                            "outcome": outcome,
+                        }));
+                    }
+                    if cli.json {
+                        println!("{}", serde_json::to_string_pretty(&results)?);
+                    }
+                }
+                LibraryCommands::Pull { names, device } => {
+                    let device_id = match device {
+                        Some(id) => id,
+                        None => get_default_device(&client).await?,
+                    };
+                    let details = client.get_device_details(&device_id).await?;
+                    for name in &names {
+                        if details.effect_by_name(name).is_none() {
+                            return Err(format!("No effect named {} on the device", name).into());
+                        }
+                    }
+                    let mut pulled = Vec::new();
+                    for effect in &details.effects {
+                        if !names.is_empty() && !names.contains(&effect.name) {
+                            continue;
+                        }
+                        let config = EffectConfig::from(effect);
+                        match effects.insert(config.clone()) {
+                            Ok(replaced) => {
+                                if !cli.json {
+                                    let action = if replaced.is_some() {
+                                        "Replaced"
+                                    } else {
+                                        "Added"
+                                    };
+                                    println!("{} {}", action, effect.name);
+                                }
+                                pulled.push(config);
+                            }
+                            Err(e) => eprintln!("Skipped {}: {}", effect.name, e),
+                        }
+                    }
+                    effects.save()?;
+                    if cli.json {
+                        println!("{}", serde_json::to_string_pretty(&pulled)?);
                     }
                 }
             }
-            ScheduleCommands::Modify {
-                device,
-                id,
-                schedule_type,
-                effect,
-                start,
-                end,
-                repeat,
-            } => {
+        }
+        Commands::Snapshot(snapshot_command) => match snapshot_command {
+            SnapshotCommands::Save { device, out } => {
                 let device_id = match device {
                     Some(id) => id,
                     None => get_default_device(&client).await?,
                 };
-                let response = client
-                    .modify_schedule(&device_id, id, &schedule_type, effect, start, end, repeat)
-                    .await?;
+                let details = client.get_device_details(&device_id).await?;
+                let snapshot = Snapshot::new(&device_id, details);
+                snapshot.save(&out)?;
                 if cli.json {
-                    println!("{}", serde_json::to_string_pretty(&response)?);
+                    println!("{}", snapshot.to_json()?);
                 } else {
-                    if response.code == 0 {
-                        println!("Schedule modified successfully");
-                    } else {
-                        println!("Error: {} (code: {})", response.desc, response.code);
-                    }
+                    let device = &snapshot.device;
+                    println!(
+                        "Saved {} with {} effect(s), {} daily and {} calendar schedule(s) to {}",
+                        device.name,
+                        device.effects.len(),
+                        device.daily.len(),
+                        device.calendar.len(),
+                        out
+                    );
                 }
             }
-            ScheduleCommands::Check { device } => {
-                let device_id = match device {
-                    Some(id) => id,
-                    None => get_default_device(&client).await?,
-                };
-                let response = client.check_schedule_conflicts(&device_id).await?;
+            SnapshotCommands::Restore { file, device, yes } => {
+                let snapshot = Snapshot::load(&file)?;
+                let device_id = device.unwrap_or_else(|| snapshot.device_id.clone());
+                if !yes {
+                    let prompt = format!(
+                        "Replace all effects and schedules on {} with {} from {}?",
+                        device_id, snapshot.device.name, snapshot.created_at
+                    );
+                    if !confirm(&prompt)? {
+                        println!("Restore cancelled");
+                        return Ok(());
+                    }
+                }
+                let report = client.restore_snapshot(&device_id, &snapshot).await?;
                 if cli.json {
-                    println!("{}", serde_json::to_string_pretty(&response)?);
+                    println!("{}", serde_json::to_string_pretty(&report)?);
                 } else {
-                    if response.code == 0 {
-                        println!("No schedule conflicts found");
-                    } else {
-                        println!("Error: {} (code: {})", response.desc, response.code);
+                    for (old, new) in &report.effect_ids {
+                        println!("Effect {} restored as {}", old, new);
+                    }
+                    for skipped in &report.skipped {
+                        println!("Skipped: {}", skipped);
                     }
+                    println!(
+                        "Restored {} effect(s), {} daily and {} calendar schedule(s)",
+                        report.added_effects, report.daily, report.calendar
+                    );
                 }
             }
         },
@@ -2025,7 +4001,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     }
                 }
                 EffectCommands::Preview {
-                    device,
+                    devices,
                     built_in,
                     pattern,
                     speed,
@@ -2037,14 +4013,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     // Validate that either built_in or pattern is specified
                     let (mode, category) = match (built_in, pattern) {
                         (Some(mode), None) => {
-                            if mode < 0 || mode > 179 {
+                            if !(0..=179).contains(&mode) {
                                 eprintln!("Invalid built-in mode. Must be between 0 and 179");
                                 std::process::exit(1);
                             }
                             (mode, 0) // Category 0 for built-in
                         }
                         (None, Some(mode)) => {
-                            if mode < 0 || mode > 16 {
+                            if !(0..=16).contains(&mode) {
                                 eprintln!("Invalid pattern number. Must be between 0 and 16");
                                 std::process::exit(1);
                             }
@@ -2056,20 +4032,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         }
                     };
 
-                    if speed < 0 || speed > 255 {
+                    if !(0..=255).contains(&speed) {
                         eprintln!("Invalid speed. Must be between 0 and 255");
                         std::process::exit(1);
                     }
-                    if brightness < 0 || brightness > 255 {
+                    if !(0..=255).contains(&brightness) {
                         eprintln!("Invalid brightness. Must be between 0 and 255");
                         std::process::exit(1);
                     }
 
-                    let device_id = match device {
-                        Some(id) => id,
-                        None => get_default_device(&client).await?,
-                    };
-
                     // Parse pixels if provided for custom patterns
                     let parsed_pixels: Option<Vec<Pixel>> = if let Some(pixels_str) = pixels {
                         match parse_pixels(&pixels_str) {
@@ -2083,12 +4054,49 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         None
                     };
 
+                    if category == 0 && !(1..=90).contains(&pixel_len) {
+                        eprintln!("Invalid pixel length. Must be between 1 and 90");
+                        std::process::exit(1);
+                    }
+                    if category == 1 && parsed_pixels.is_none() {
+                        eprintln!("The --pixels parameter is required for custom patterns");
+                        std::process::exit(1);
+                    }
+
+                    if devices.is_bulk() {
+                        let client = &client;
+                        let parsed_pixels = &parsed_pixels;
+                        return run_on_devices(
+                            client,
+                            &devices,
+                            cli.json,
+                            |device_id| async move {
+                                let response = if category == 0 {
+                                    client
+                                        .preview_builtin_effect(
+                                            &device_id, mode, speed, brightness, pixel_len, reverse,
+                                        )
+                                        .await?
+                                } else {
+                                    client
+                                        .preview_custom_effect(
+                                            &device_id,
+                                            mode,
+                                            speed,
+                                            brightness,
+                                            parsed_pixels.clone(),
+                                        )
+                                        .await?
+                                };
+                                outcome(response, "Preview started")
+                            },
+                        )
+                        .await;
+                    }
+                    let device_id = devices.device_id(&client).await?;
+
                     let response = if category == 0 {
                         // Built-in effect
-                        if pixel_len < 1 || pixel_len > 90 {
-                            eprintln!("Invalid pixel length. Must be between 1 and 90");
-                            std::process::exit(1);
-                        }
                         client
                             .preview_builtin_effect(
                                 &device_id, mode, speed, brightness, pixel_len, reverse,
@@ -2096,10 +4104,6 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                             .await?
                     } else {
                         // Custom pattern
-                        if parsed_pixels.is_none() {
-                            eprintln!("The --pixels parameter is required for custom patterns");
-                            std::process::exit(1);
-                        }
                         client
                             .preview_custom_effect(
                                 &device_id,
@@ -2135,7 +4139,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     // Validate that either built_in or pattern is specified
                     match (built_in, pattern) {
                         (Some(mode), None) => {
-                            if mode < 0 || mode > 179 {
+                            if !(0..=179).contains(&mode) {
                                 eprintln!("Invalid built-in mode. Must be between 0 and 179");
                                 std::process::exit(1);
                             }
@@ -2181,7 +4185,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                             }
                         }
                         (None, Some(mode)) => {
-                            if mode < 0 || mode > 16 {
+                            if !(0..=16).contains(&mode) {
                                 eprintln!("Invalid pattern number. Must be between 0 and 16");
                                 std::process::exit(1);
                             }
@@ -2259,7 +4263,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     // Validate mode numbers if provided
                     match (built_in, pattern) {
                         (Some(mode), None) => {
-                            if mode < 0 || mode > 179 {
+                            if !(0..=179).contains(&mode) {
                                 eprintln!("Invalid built-in mode. Must be between 0 and 179");
                                 std::process::exit(1);
                             }
@@ -2293,7 +4297,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                             }
                         }
                         (None, Some(mode)) => {
-                            if mode < 0 || mode > 16 {
+                            if !(0..=16).contains(&mode) {
                                 eprintln!("Invalid pattern number. Must be between 0 and 16");
                                 std::process::exit(1);
                             }
@@ -2344,15 +4348,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                 None => get_default_device(&client).await?,
                             };
 
-                            // Get current effect details to determine if it's built-in or custom
-                            let details = client.get_device_details(&device_id).await?;
-                            let effect = details
-                                .effects
-                                .iter()
-                                .find(|e| e.id == id)
-                                .ok_or_else(|| format!("Effect with ID {} not found", id))?;
+                            // Get current effect to determine if it's built-in or custom
+                            let effect = client.get_effect(&device_id, id).await?;
 
-                            let response = if effect.category == 1 {
+                            let response = if effect.is_builtin() {
                                 client
                                     .update_builtin_effect(
                                         &device_id,
@@ -2395,12 +4394,58 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         }
                     }
                 }
-                EffectCommands::Delete { device, id } => {
+                EffectCommands::Delete {
+                    device,
+                    id,
+                    cascade,
+                    force,
+                    yes,
+                } => {
                     let device_id = match device {
                         Some(id) => id,
                         None => get_default_device(&client).await?,
                     };
-                    let response = client.delete_effect(&device_id, id).await?;
+                    let mode = if cascade {
+                        DeleteMode::Cascade
+                    } else if force {
+                        DeleteMode::Force
+                    } else {
+                        DeleteMode::Safe
+                    };
+
+                    let details = client.get_device_details(&device_id).await?;
+                    let references = details.effect_references(id);
+                    if !references.is_empty() {
+                        if mode == DeleteMode::Safe {
+                            eprintln!(
+                                "Pass --cascade to remove these too, or --force to leave them \
+                                 pointing at a missing effect"
+                            );
+                            return Err(TrimlightError::EffectInUse {
+                                effect_id: id,
+                                references,
+                            }
+                            .into());
+                        }
+                        // Keep stdout clean for --json
+                        let name = details.effect_name(id).unwrap_or("unknown");
+                        eprintln!("Effect {} ({}) is used by:", id, name);
+                        for reference in &references {
+                            eprintln!("  {}", reference);
+                        }
+                        let prompt = match mode {
+                            DeleteMode::Force => {
+                                "Delete the effect and leave these pointing at a missing effect?"
+                            }
+                            _ => "Remove these and delete the effect?",
+                        };
+                        if !yes && !confirm(prompt)? {
+                            eprintln!("Delete cancelled");
+                            return Ok(());
+                        }
+                    }
+
+                    let response = client.delete_effect(&device_id, id, mode).await?;
                     if cli.json {
                         println!("{}", serde_json::to_string_pretty(&response)?);
                     } else {
@@ -2411,18 +4456,96 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         }
                     }
                 }
+                EffectCommands::Gc {
+                    device,
+                    keep,
+                    dry_run,
+                    yes,
+                } => {
+                    let keep = glob_set(&keep)?;
+                    let device_id = match device {
+                        Some(id) => id,
+                        None => get_default_device(&client).await?,
+                    };
+                    let details = client.get_device_details(&device_id).await?;
+                    let unused: Vec<&Effect> = details
+                        .unused_effects()
+                        .into_iter()
+                        .filter(|e| !keep.is_match(&e.name))
+                        .collect();
+
+                    if unused.is_empty() {
+                        if cli.json {
+                            println!("[]");
+                        } else {
+                            println!("No unused effects");
+                        }
+                        return Ok(());
+                    }
+                    if !cli.json {
+                        println!("Unused effects:");
+                        for effect in &unused {
+                            println!("  {}: {}", effect.id, effect.name);
+                        }
+                    }
+                    if dry_run {
+                        if cli.json {
+                            println!("{}", serde_json::to_string_pretty(&unused)?);
+                        } else {
+                            println!("Dry run: nothing was deleted");
+                        }
+                        return Ok(());
+                    }
+                    if !yes && !confirm(&format!("Delete {} effect(s)?", unused.len()))? {
+                        eprintln!("Nothing deleted");
+                        if cli.json {
+                            println!("[]");
+                        }
+                        return Ok(());
+                    }
+
+                    let mut deleted = Vec::new();
+                    let mut failed = 0;
+                    for effect in unused {
+                        // Safe mode catches anything that started using the effect
+                        // since the device was read
+                        match client
+                            .delete_effect(&device_id, effect.id, DeleteMode::Safe)
+                            .await
+                        {
+                            Ok(response) if response.code == 0 => deleted.push(effect),
+                            Ok(response) => {
+                                failed += 1;
+                                eprintln!(
+                                    "Failed to delete effect {} ({}): {}",
+                                    effect.id, effect.name, response.desc
+                                );
+                            }
+                            Err(e) => {
+                                failed += 1;
+                                eprintln!(
+                                    "Failed to delete effect {} ({}): {}",
+                                    effect.id, effect.name, e
+                                );
+                            }
+                        }
+                    }
+                    if cli.json {
+                        println!("{}", serde_json::to_string_pretty(&deleted)?);
+                    } else {
+                        println!("Deleted {} effect(s)", deleted.len());
+                    }
+                    if failed > 0 {
+                        std::process::exit(1);
+                    }
+                }
                 EffectCommands::Combined(combined_command) => {
                     match combined_command {
                         CombinedCommands::Set {
-                            device,
+                            devices,
                             effects,
                             interval,
                         } => {
-                            let device_id = match device {
-                                Some(id) => id,
-                                None => get_default_device(&client).await?,
-                            };
-
                             // Parse effect IDs
                             let effect_ids: Vec<i32> = effects
                                 .split(',')
@@ -2437,11 +4560,35 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                 std::process::exit(1);
                             }
 
-                            if interval < 1 || interval > 3600 {
+                            if !(1..=3600).contains(&interval) {
                                 eprintln!("Error: Interval must be between 1 and 3600 seconds");
                                 std::process::exit(1);
                             }
 
+                            if devices.is_bulk() {
+                                let client = &client;
+                                let effect_ids = &effect_ids;
+                                return run_on_devices(
+                                    client,
+                                    &devices,
+                                    cli.json,
+                                    |device_id| async move {
+                                        let names =
+                                            check_saved_effects(client, &device_id, effect_ids)
+                                                .await?;
+                                        outcome(
+                                            client
+                                                .set_combined_effect(
+                                                    &device_id, effect_ids, interval,
+                                                )
+                                                .await?,
+                                            &format!("Combined effect set to {}", names),
+                                        )
+                                    },
+                                )
+                                .await;
+                            }
+                            let device_id = devices.device_id(&client).await?;
                             let response = client
                                 .set_combined_effect(&device_id, &effect_ids, interval)
                                 .await?;
@@ -2455,11 +4602,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                 }
                             }
                         }
-                        CombinedCommands::Clear { device } => {
-                            let device_id = match device {
-                                Some(id) => id,
-                                None => get_default_device(&client).await?,
-                            };
+                        CombinedCommands::Clear { devices } => {
+                            if devices.is_bulk() {
+                                let client = &client;
+                                return run_on_devices(
+                                    client,
+                                    &devices,
+                                    cli.json,
+                                    |device_id| async move {
+                                        outcome(
+                                            client.clear_combined_effect(&device_id).await?,
+                                            "Combined effect cleared",
+                                        )
+                                    },
+                                )
+                                .await;
+                            }
+                            let device_id = devices.device_id(&client).await?;
 
                             let response = client.clear_combined_effect(&device_id).await?;
                             if cli.json {
@@ -2474,11 +4633,24 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         }
                     }
                 }
-                EffectCommands::View { device, id } => {
-                    let device_id = match device {
-                        Some(id) => id,
-                        None => get_default_device(&client).await?,
-                    };
+                EffectCommands::View { devices, id } => {
+                    if devices.is_bulk() {
+                        let client = &client;
+                        return run_on_devices(
+                            client,
+                            &devices,
+                            cli.json,
+                            |device_id| async move {
+                                let name = check_saved_effects(client, &device_id, &[id]).await?;
+                                outcome(
+                                    client.view_effect(&device_id, id).await?,
+                                    &format!("Loaded {}", name),
+                                )
+                            },
+                        )
+                        .await;
+                    }
+                    let device_id = devices.device_id(&client).await?;
                     let response = client.view_effect(&device_id, id).await?;
                     if cli.json {
                         println!("{}", serde_json::to_string_pretty(&response)?);
@@ -2493,16 +4665,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 EffectCommands::Overlay(overlay_command) => {
                     match overlay_command {
                         OverlayCommands::Add {
-                            device,
+                            devices,
                             lightning,
                             snow,
                             target,
                         } => {
-                            let device_id = match device {
-                                Some(id) => id,
-                                None => get_default_device(&client).await?,
-                            };
-
                             // Validate that exactly one effect type is selected
                             match (lightning, snow) {
                                 (true, false) => (),
@@ -2517,6 +4684,31 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
                             let overlay_type = if lightning { 0 } else { 1 };
 
+                            if devices.is_bulk() {
+                                let client = &client;
+                                return run_on_devices(
+                                    client,
+                                    &devices,
+                                    cli.json,
+                                    |device_id| async move {
+                                        let name =
+                                            check_saved_effects(client, &device_id, &[target])
+                                                .await?;
+                                        outcome(
+                                            client
+                                                .add_overlay_effect(
+                                                    &device_id,
+                                                    overlay_type,
+                                                    target,
+                                                )
+                                                .await?,
+                                            &format!("Overlay added to {}", name),
+                                        )
+                                    },
+                                )
+                                .await;
+                            }
+                            let device_id = devices.device_id(&client).await?;
                             let response = client
                                 .add_overlay_effect(&device_id, overlay_type, target)
                                 .await?;
@@ -2530,11 +4722,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                 );
                             }
                         }
-                        OverlayCommands::Clear { device } => {
-                            let device_id = match device {
-                                Some(id) => id,
-                                None => get_default_device(&client).await?,
-                            };
+                        OverlayCommands::Clear { devices } => {
+                            if devices.is_bulk() {
+                                let client = &client;
+                                return run_on_devices(
+                                    client,
+                                    &devices,
+                                    cli.json,
+                                    |device_id| async move {
+                                        outcome(
+                                            client.clear_overlay_effects(&device_id).await?,
+                                            "Overlays cleared",
+                                        )
+                                    },
+                                )
+                                .await;
+                            }
+                            let device_id = devices.device_id(&client).await?;
 
                             let response = client.clear_overlay_effects(&device_id).await?;
                             if cli.json {
@@ -2551,3 +4755,80 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn devices() -> Vec<Device> {
+        ["Front Roof", "Front Porch", "Garage"]
+            .iter()
+            .enumerate()
+            .map(|(i, name)| Device {
+                device_id: format!("dev{}", i),
+                name: name.to_string(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn targets(all: bool, match_name: Option<&str>) -> DeviceTargets {
+        DeviceTargets {
+            device: None,
+            all,
+            match_name: match_name.map(str::to_string),
+            parallel: 4,
+        }
+    }
+
+    fn ids(devices: &[Device]) -> Vec<&str> {
+        devices.iter().map(|d| d.device_id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_select_devices() {
+        let all = select_devices(devices(), &targets(true, None)).unwrap();
+        assert_eq!(ids(&all), ["dev0", "dev1", "dev2"]);
+
+        let front = select_devices(devices(), &targets(false, Some("front*"))).unwrap();
+        assert_eq!(ids(&front), ["dev0", "dev1"]);
+
+        let error = select_devices(devices(), &targets(false, Some("Back*"))).unwrap_err();
+        assert_eq!(error.to_string(), "No devices named like 'Back*'");
+        let error = select_devices(Vec::new(), &targets(true, None)).unwrap_err();
+        assert_eq!(error.to_string(), "No devices found");
+    }
+
+    #[test]
+    fn test_bulk_exit_status() {
+        let result = |ok: bool| DeviceResult {
+            device_id: "dev0".to_string(),
+            name: "Front Roof".to_string(),
+            ok,
+            result: String::new(),
+            response: None,
+        };
+        assert_eq!(bulk_exit_status(&[result(true), result(true)]), 0);
+        assert_eq!(bulk_exit_status(&[result(true), result(false)]), 1);
+    }
+
+    #[test]
+    fn test_saved_effect_names() {
+        let details: DeviceDetails = serde_json::from_value(serde_json::json!({
+            "effects": [
+                {"id": 1, "name": "Warm White", "category": 1, "mode": 0},
+                {"id": 4, "name": "Candy Cane", "category": 2, "mode": 1}
+            ]
+        }))
+        .unwrap();
+        assert_eq!(
+            saved_effect_names(&details, &[4, 1]).unwrap(),
+            "Candy Cane, Warm White"
+        );
+        assert!(matches!(
+            saved_effect_names(&details, &[1, 3]),
+            Err(TrimlightError::ApiError { code: 404, message })
+                if message == "Effect 3 not found on this device"
+        ));
+    }
+}
@@ -6,10 +6,14 @@ use reqwest::{
 };
 use serde::Serialize;
 use sha2::Sha256;
+use std::collections::{HashMap, HashSet};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::config::{ConfigChange, ConfigPlan};
+use crate::conflicts::{find_schedule_conflicts, ScheduleConflict};
 use crate::error::TrimlightError;
 use crate::models::*;
+use crate::snapshot::{CloneMode, RestoreReport, Snapshot};
 use crate::utils;
 
 const DEFAULT_API_BASE_URL: &str = "https://trimlight.ledhue.com/trimlight";
@@ -20,6 +24,7 @@ pub struct TrimlightClient {
     client_id: String,
     client_secret: String,
     api_base_url: String,
+    strict: bool,
 }
 
 impl TrimlightClient {
@@ -29,9 +34,17 @@ impl TrimlightClient {
             client_id: client_id.into(),
             client_secret: client_secret.into(),
             api_base_url: DEFAULT_API_BASE_URL.to_string(),
+            strict: false,
         }
     }
 
+    /// Fail with `TrimlightError::UnexpectedFields` when a response contains
+    /// fields the models don't declare, instead of preserving them silently
+    pub fn with_strict_mode(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
     #[cfg(test)]
     pub fn with_base_url(
         client_id: impl Into<String>,
@@ -43,6 +56,7 @@ impl TrimlightClient {
             client_id: client_id.into(),
             client_secret: client_secret.into(),
             api_base_url: api_base_url.into(),
+            strict: false,
         }
     }
 
@@ -82,7 +96,7 @@ impl TrimlightClient {
     ) -> Result<U, TrimlightError>
     where
         T: Serialize + ?Sized,
-        U: for<'de> serde::de::Deserialize<'de> + Default,
+        U: for<'de> serde::de::Deserialize<'de> + Default + UnknownFields,
     {
         let url = format!("{}{}", self.api_base_url, endpoint);
         let mut req = self.client.request(method, &url);
@@ -107,7 +121,15 @@ impl TrimlightClient {
             });
         }
 
-        Ok(api_response.payload.unwrap_or_default())
+        let payload = api_response.payload.unwrap_or_default();
+        if self.strict {
+            let unknown = payload.unknown_fields();
+            if !unknown.is_empty() {
+                return Err(TrimlightError::UnexpectedFields(unknown));
+            }
+        }
+
+        Ok(payload)
     }
 
     // Device Management Methods
@@ -123,6 +145,21 @@ impl TrimlightClient {
             .await
     }
 
+    /// Every device on the account, reading the list page by page
+    pub async fn get_all_devices(&self) -> Result<Vec<Device>, TrimlightError> {
+        let mut devices = Vec::new();
+        let mut page = 1;
+        loop {
+            let response = self.get_device_list(Some(page)).await?;
+            let last_page = response.data.is_empty();
+            devices.extend(response.data);
+            if last_page || devices.len() >= response.total.max(0) as usize {
+                return Ok(devices);
+            }
+            page += 1;
+        }
+    }
+
     pub async fn get_device_details(
         &self,
         device_id: &str,
@@ -184,6 +221,58 @@ impl TrimlightClient {
         .await
     }
 
+    pub async fn set_color_order(
+        &self,
+        device_id: &str,
+        color_order: i32,
+    ) -> Result<BasicResponse, TrimlightError> {
+        let body = serde_json::json!({
+            "deviceId": device_id,
+            "payload": {
+                "colorOrder": color_order
+            }
+        });
+
+        self.request(
+            Method::POST,
+            "/v1/oauth/resources/device/update",
+            Some(&body),
+        )
+        .await
+    }
+
+    /// Set the pixel range of each port, numbering the ports from 0
+    pub async fn set_ports(
+        &self,
+        device_id: &str,
+        ports: &[(i32, i32)],
+    ) -> Result<BasicResponse, TrimlightError> {
+        let ports: Vec<_> = ports
+            .iter()
+            .enumerate()
+            .map(|(id, (start, end))| {
+                serde_json::json!({
+                    "id": id,
+                    "start": start,
+                    "end": end
+                })
+            })
+            .collect();
+        let body = serde_json::json!({
+            "deviceId": device_id,
+            "payload": {
+                "ports": ports
+            }
+        });
+
+        self.request(
+            Method::POST,
+            "/v1/oauth/resources/device/update",
+            Some(&body),
+        )
+        .await
+    }
+
     // Effect Management Methods
     pub async fn preview_builtin_effect(
         &self,
@@ -241,6 +330,7 @@ impl TrimlightClient {
         .await
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn add_builtin_effect(
         &self,
         device_id: &str,
@@ -325,6 +415,11 @@ impl TrimlightClient {
         Ok(api_response)
     }
 
+    /// Update a saved effect as a built-in effect
+    ///
+    /// The effect is fetched from the device first and only the supplied
+    /// fields are changed, so `None` leaves the current value untouched.
+    #[allow(clippy::too_many_arguments)]
     pub async fn update_builtin_effect(
         &self,
         device_id: &str,
@@ -336,28 +431,29 @@ impl TrimlightClient {
         pixel_len: Option<i32>,
         reverse: Option<bool>,
     ) -> Result<BasicResponse, TrimlightError> {
-        let body = serde_json::json!({
-            "deviceId": device_id,
-            "payload": {
-                "id": effect_id,
-                "category": 1,
-                "name": name,
-                "mode": mode,
-                "speed": speed,
-                "brightness": brightness,
-                "pixelLen": pixel_len,
-                "reverse": reverse
-            }
+        let mut effect = self.get_effect(device_id, effect_id).await?;
+        merge_common_fields(&mut effect, name, speed, brightness);
+
+        let (current_pixel_len, current_reverse) = match effect.kind() {
+            Ok(EffectKind::Builtin {
+                pixel_len, reverse, ..
+            }) => (pixel_len, reverse),
+            _ => (Effect::DEFAULT_PIXEL_LEN, false),
+        };
+        effect.set_kind(EffectKind::Builtin {
+            mode: BuiltinMode::try_from(mode.unwrap_or(effect.mode))?,
+            pixel_len: pixel_len.unwrap_or(current_pixel_len),
+            reverse: reverse.unwrap_or(current_reverse),
         });
 
-        self.request(
-            Method::POST,
-            "/v1/oauth/resources/device/effect/save",
-            Some(&body),
-        )
-        .await
+        self.save_effect(device_id, &effect).await
     }
 
+    /// Update a saved effect as a custom effect
+    ///
+    /// The effect is fetched from the device first and only the supplied
+    /// fields are changed, so `None` leaves the current value untouched.
+    #[allow(clippy::too_many_arguments)]
     pub async fn update_custom_effect(
         &self,
         device_id: &str,
@@ -367,18 +463,47 @@ impl TrimlightClient {
         speed: Option<i32>,
         brightness: Option<i32>,
         pixels: Option<Vec<Pixel>>,
+    ) -> Result<BasicResponse, TrimlightError> {
+        let mut effect = self.get_effect(device_id, effect_id).await?;
+        merge_common_fields(&mut effect, name, speed, brightness);
+
+        let current_pixels = match effect.kind() {
+            Ok(EffectKind::Custom { pixels, .. }) => pixels,
+            _ => Vec::new(),
+        };
+        effect.set_kind(EffectKind::Custom {
+            mode: CustomMode::try_from(mode.unwrap_or(effect.mode))?,
+            pixels: pixels.unwrap_or(current_pixels),
+        });
+
+        self.save_effect(device_id, &effect).await
+    }
+
+    /// Get a saved effect by ID
+    pub async fn get_effect(
+        &self,
+        device_id: &str,
+        effect_id: i32,
+    ) -> Result<Effect, TrimlightError> {
+        let details = self.get_device_details(device_id).await?;
+        details
+            .effects
+            .into_iter()
+            .find(|e| e.id == effect_id)
+            .ok_or_else(|| TrimlightError::ApiError {
+                code: 404,
+                message: format!("Effect {} not found", effect_id),
+            })
+    }
+
+    async fn save_effect(
+        &self,
+        device_id: &str,
+        effect: &Effect,
     ) -> Result<BasicResponse, TrimlightError> {
         let body = serde_json::json!({
             "deviceId": device_id,
-            "payload": {
-                "id": effect_id,
-                "category": 2,
-                "name": name,
-                "mode": mode,
-                "speed": speed,
-                "brightness": brightness,
-                "pixels": pixels
-            }
+            "payload": effect
         });
 
         self.request(
@@ -389,11 +514,34 @@ impl TrimlightClient {
         .await
     }
 
+    /// Delete a saved effect
+    ///
+    /// With [`DeleteMode::Safe`] the device is checked first and the call
+    /// fails with [`TrimlightError::EffectInUse`] when schedules, the combined
+    /// effect or overlays still use the effect. [`DeleteMode::Cascade`]
+    /// removes those references before deleting, and [`DeleteMode::Force`]
+    /// deletes without checking.
     pub async fn delete_effect(
         &self,
         device_id: &str,
         effect_id: i32,
+        mode: DeleteMode,
     ) -> Result<BasicResponse, TrimlightError> {
+        if mode != DeleteMode::Force {
+            let details = self.get_device_details(device_id).await?;
+            let references = details.effect_references(effect_id);
+            if !references.is_empty() {
+                if mode == DeleteMode::Safe {
+                    return Err(TrimlightError::EffectInUse {
+                        effect_id,
+                        references,
+                    });
+                }
+                self.remove_effect_references(device_id, &details, effect_id)
+                    .await?;
+            }
+        }
+
         let body = serde_json::json!({
             "deviceId": device_id,
             "payload": {
@@ -409,20 +557,60 @@ impl TrimlightClient {
         .await
     }
 
+    /// Delete schedules that run an effect and drop it from the combined
+    /// effect and overlays
+    async fn remove_effect_references(
+        &self,
+        device_id: &str,
+        details: &DeviceDetails,
+        effect_id: i32,
+    ) -> Result<(), TrimlightError> {
+        for schedule in details.daily_schedules_for_effect(effect_id) {
+            self.delete_schedule(device_id, schedule.id, "daily")
+                .await?;
+        }
+        for schedule in details.calendar_schedules_for_effect(effect_id) {
+            self.delete_schedule(device_id, schedule.id, "calendar")
+                .await?;
+        }
+        if let Some(combined) = &details.combined_effect {
+            if combined.effect_ids.contains(&effect_id) {
+                let remaining: Vec<i32> = combined
+                    .effect_ids
+                    .iter()
+                    .copied()
+                    .filter(|id| *id != effect_id)
+                    .collect();
+                if remaining.is_empty() {
+                    self.clear_combined_effect(device_id).await?;
+                } else {
+                    self.set_combined_effect(device_id, &remaining, combined.interval)
+                        .await?;
+                }
+            }
+        }
+        if !details.overlays_for_effect(effect_id).is_empty() {
+            let remaining: Vec<(i32, i32)> = details
+                .overlay_effects
+                .iter()
+                .filter(|o| o.target_effect != effect_id)
+                .map(|o| (o.overlay_type, o.target_effect))
+                .collect();
+            if remaining.is_empty() {
+                self.clear_overlay_effects(device_id).await?;
+            } else {
+                self.set_overlay_effects(device_id, &remaining).await?;
+            }
+        }
+        Ok(())
+    }
+
     pub async fn view_effect(
         &self,
         device_id: &str,
         effect_id: i32,
     ) -> Result<BasicResponse, TrimlightError> {
-        let details = self.get_device_details(device_id).await?;
-        let effect = details
-            .effects
-            .iter()
-            .find(|e| e.id == effect_id)
-            .ok_or_else(|| TrimlightError::ApiError {
-                code: 404,
-                message: format!("Effect {} not found", effect_id),
-            })?;
+        let effect = self.get_effect(device_id, effect_id).await?;
 
         let body = serde_json::json!({
             "deviceId": device_id,
@@ -445,6 +633,94 @@ impl TrimlightClient {
         .await
     }
 
+    /// Save a new effect, returning the ID the device assigned when it reports one
+    async fn add_effect(
+        &self,
+        device_id: &str,
+        effect: &Effect,
+    ) -> Result<Option<i32>, TrimlightError> {
+        let response = match effect.kind()? {
+            EffectKind::Builtin {
+                mode,
+                pixel_len,
+                reverse,
+            } => {
+                self.add_builtin_effect(
+                    device_id,
+                    &effect.name,
+                    mode.value(),
+                    effect.speed,
+                    effect.brightness,
+                    Some(pixel_len),
+                    Some(reverse),
+                )
+                .await?
+            }
+            EffectKind::Custom { mode, pixels } => {
+                self.add_custom_effect(
+                    device_id,
+                    &effect.name,
+                    mode.value(),
+                    effect.speed,
+                    effect.brightness,
+                    pixels,
+                )
+                .await?
+            }
+        };
+        if response.code != 0 {
+            return Err(TrimlightError::ApiError {
+                code: response.code,
+                message: response.desc,
+            });
+        }
+        Ok(response
+            .payload
+            .as_ref()
+            .and_then(|p| p.get("id"))
+            .and_then(|id| id.as_i64())
+            .map(|id| id as i32))
+    }
+
+    /// Add a daily schedule, disabling it straight away when `enable` is false
+    async fn add_daily_schedule_with_state(
+        &self,
+        device_id: &str,
+        effect_id: i32,
+        start: String,
+        end: String,
+        repetition: i32,
+        enable: bool,
+    ) -> Result<(), TrimlightError> {
+        let existing: HashSet<i32> = if enable {
+            HashSet::new()
+        } else {
+            self.get_device_schedules(device_id)
+                .await?
+                .daily
+                .iter()
+                .map(|s| s.id)
+                .collect()
+        };
+        self.add_daily_schedule(device_id, effect_id, start, end, repetition)
+            .await?;
+
+        // New schedules start enabled, so find the one just added to disable it
+        if !enable {
+            let added = self
+                .get_device_schedules(device_id)
+                .await?
+                .daily
+                .into_iter()
+                .find(|s| !existing.contains(&s.id) && s.effect_id == effect_id);
+            if let Some(added) = added {
+                self.toggle_schedule(device_id, added.id, "daily", false)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
     // Schedule Management Methods
     pub async fn get_device_schedules(
         &self,
@@ -454,6 +730,7 @@ impl TrimlightClient {
         Ok(DeviceSchedules {
             daily: details.daily,
             calendar: details.calendar,
+            ..Default::default()
         })
     }
 
@@ -567,12 +844,25 @@ impl TrimlightClient {
         self.request(Method::POST, endpoint, Some(&body)).await
     }
 
+    /// Enable or disable a daily schedule
+    ///
+    /// Calendar schedules have no enable flag, so they cannot be toggled;
+    /// delete them instead.
     pub async fn toggle_schedule(
         &self,
         device_id: &str,
         schedule_id: i32,
+        schedule_type: &str,
         enable: bool,
     ) -> Result<BasicResponse, TrimlightError> {
+        let endpoint = schedule_update_endpoint(schedule_type)?;
+        if !endpoint.contains("/daily/") {
+            return Err(TrimlightError::ApiError {
+                code: 400,
+                message: "Calendar schedules cannot be enabled or disabled, delete them instead"
+                    .to_string(),
+            });
+        }
         let body = serde_json::json!({
             "deviceId": device_id,
             "payload": {
@@ -581,75 +871,86 @@ impl TrimlightClient {
             }
         });
 
-        self.request(
-            Method::POST,
-            "/v1/oauth/resources/device/schedule/daily/update",
-            Some(&body),
-        )
-        .await
+        self.request(Method::POST, endpoint, Some(&body)).await
     }
 
+    /// Modify a daily or calendar schedule
+    ///
+    /// The schedule is fetched from the device first and only the supplied
+    /// fields are changed, so `None` leaves the current value untouched.
+    /// Dates only apply to calendar schedules and repetition only to daily
+    /// schedules.
+    #[allow(clippy::too_many_arguments)]
     pub async fn modify_schedule(
         &self,
         device_id: &str,
         schedule_id: i32,
         schedule_type: &str,
         effect_id: Option<i32>,
+        start_date: Option<String>,
+        end_date: Option<String>,
         start: Option<String>,
-        end: String,
+        end: Option<String>,
         repetition: Option<i32>,
     ) -> Result<BasicResponse, TrimlightError> {
-        let endpoint = match schedule_type.to_lowercase().as_str() {
-            "daily" => "/v1/oauth/resources/device/schedule/daily/update",
-            "calendar" => "/v1/oauth/resources/device/schedule/calendar/update",
-            _ => {
-                return Err(TrimlightError::ApiError {
-                    code: 400,
-                    message: "Invalid schedule type. Must be 'daily' or 'calendar'".to_string(),
-                })
-            }
-        };
+        let endpoint = schedule_update_endpoint(schedule_type)?;
+        let is_daily = endpoint.contains("/daily/");
+        if is_daily && (start_date.is_some() || end_date.is_some()) {
+            return Err(TrimlightError::ApiError {
+                code: 400,
+                message: "Daily schedules have no dates, use repetition instead".to_string(),
+            });
+        }
+        if !is_daily && repetition.is_some() {
+            return Err(TrimlightError::ApiError {
+                code: 400,
+                message: "Calendar schedules have no repetition, use dates instead".to_string(),
+            });
+        }
 
         let schedules = self.get_device_schedules(device_id).await?;
-        let schedule = if schedule_type == "daily" {
-            schedules.daily.iter().find(|s| s.id == schedule_id)
-        } else {
-            None
-        };
-
-        let schedule = schedule.ok_or_else(|| TrimlightError::ApiError {
+        let not_found = || TrimlightError::ApiError {
             code: 404,
             message: format!("Schedule {} not found", schedule_id),
-        })?;
-
-        let (start_hours, start_minutes) = if let Some(start_time) = start {
-            utils::parse_time(&start_time)?
-        } else {
-            (schedule.start_time.hours, schedule.start_time.minutes)
         };
-        let (end_hours, end_minutes) = utils::parse_time(&end)?;
 
-        let mut payload = serde_json::json!({
-            "id": schedule_id,
-            "enable": schedule.enable,
-            "effectId": effect_id.unwrap_or(schedule.effect_id),
-            "startTime": {
-                "hours": start_hours,
-                "minutes": start_minutes
-            },
-            "endTime": {
-                "hours": end_hours,
-                "minutes": end_minutes
+        // Serialize the fetched schedule so fields unknown to this library are kept
+        let payload = if is_daily {
+            let mut updated = schedules
+                .daily
+                .into_iter()
+                .find(|s| s.id == schedule_id)
+                .ok_or_else(not_found)?;
+            if let Some(effect_id) = effect_id {
+                updated.effect_id = effect_id;
             }
-        });
-
-        if schedule_type == "daily" {
+            merge_times(&mut updated.start_time, &mut updated.end_time, start, end)?;
             if let Some(rep) = repetition {
-                payload["repetition"] = serde_json::json!(rep);
-            } else {
-                payload["repetition"] = serde_json::json!(schedule.repetition);
+                updated.repetition = rep;
             }
-        }
+            serde_json::to_value(&updated)?
+        } else {
+            let mut updated = schedules
+                .calendar
+                .into_iter()
+                .find(|s| s.id == schedule_id)
+                .ok_or_else(not_found)?;
+            if let Some(effect_id) = effect_id {
+                updated.effect_id = effect_id;
+            }
+            if let Some(start_date) = start_date {
+                let (month, day) = utils::parse_date(&start_date)?;
+                updated.start_date.month = month;
+                updated.start_date.day = day;
+            }
+            if let Some(end_date) = end_date {
+                let (month, day) = utils::parse_date(&end_date)?;
+                updated.end_date.month = month;
+                updated.end_date.day = day;
+            }
+            merge_times(&mut updated.start_time, &mut updated.end_time, start, end)?;
+            serde_json::to_value(&updated)?
+        };
 
         let body = serde_json::json!({
             "deviceId": device_id,
@@ -659,92 +960,17 @@ impl TrimlightClient {
         self.request(Method::POST, endpoint, Some(&body)).await
     }
 
+    /// Find schedules on the device that would run at the same time
     pub async fn check_schedule_conflicts(
         &self,
         device_id: &str,
-    ) -> Result<BasicResponse, TrimlightError> {
-        let schedules = self.get_device_schedules(device_id).await?;
-        let mut conflicts = Vec::new();
-
-        // Check daily schedule conflicts
-        for (i, schedule1) in schedules.daily.iter().enumerate() {
-            if !schedule1.enable {
-                continue;
-            }
-
-            for schedule2 in schedules.daily.iter().skip(i + 1) {
-                if !schedule2.enable {
-                    continue;
-                }
-
-                // Check if schedules have overlapping repetition patterns
-                let overlapping_days = match (schedule1.repetition, schedule2.repetition) {
-                    (0, _) | (_, 0) => false, // Today only doesn't conflict
-                    (1, _) | (_, 1) => true,  // Everyday conflicts with everything
-                    (2, 2) => true,           // Weekdays overlap with weekdays
-                    (3, 3) => true,           // Weekend overlaps with weekend
-                    (2, 3) | (3, 2) => false, // Weekdays don't overlap with weekend
-                    _ => false,
-                };
-
-                if overlapping_days {
-                    // Check time overlap
-                    let start1 = schedule1.start_time.hours * 60 + schedule1.start_time.minutes;
-                    let end1 = schedule1.end_time.hours * 60 + schedule1.end_time.minutes;
-                    let start2 = schedule2.start_time.hours * 60 + schedule2.start_time.minutes;
-                    let end2 = schedule2.end_time.hours * 60 + schedule2.end_time.minutes;
-
-                    if (start1 <= end2 && end1 >= start2) || (start2 <= end1 && end2 >= start1) {
-                        conflicts.push(format!(
-                            "Daily schedules {} and {} have overlapping times",
-                            schedule1.id, schedule2.id
-                        ));
-                    }
-                }
-            }
-        }
-
-        // Check calendar schedule conflicts
-        for (i, schedule1) in schedules.calendar.iter().enumerate() {
-            for schedule2 in schedules.calendar.iter().skip(i + 1) {
-                // Check date overlap
-                let start1 = schedule1.start_date.month * 31 + schedule1.start_date.day;
-                let end1 = schedule1.end_date.month * 31 + schedule1.end_date.day;
-                let start2 = schedule2.start_date.month * 31 + schedule2.start_date.day;
-                let end2 = schedule2.end_date.month * 31 + schedule2.end_date.day;
-
-                if (start1 <= end2 && end1 >= start2) || (start2 <= end1 && end2 >= start1) {
-                    // Check time overlap
-                    let time_start1 =
-                        schedule1.start_time.hours * 60 + schedule1.start_time.minutes;
-                    let time_end1 = schedule1.end_time.hours * 60 + schedule1.end_time.minutes;
-                    let time_start2 =
-                        schedule2.start_time.hours * 60 + schedule2.start_time.minutes;
-                    let time_end2 = schedule2.end_time.hours * 60 + schedule2.end_time.minutes;
-
-                    if (time_start1 <= time_end2 && time_end1 >= time_start2)
-                        || (time_start2 <= time_end1 && time_end2 >= time_start1)
-                    {
-                        conflicts.push(format!(
-                            "Calendar schedules {} and {} have overlapping dates and times",
-                            schedule1.id, schedule2.id
-                        ));
-                    }
-                }
-            }
-        }
-
-        if conflicts.is_empty() {
-            Ok(BasicResponse {
-                code: 0,
-                desc: "No conflicts found".to_string(),
-            })
-        } else {
-            Ok(BasicResponse {
-                code: 1,
-                desc: conflicts.join("\n"),
-            })
-        }
+    ) -> Result<Vec<ScheduleConflict>, TrimlightError> {
+        let details = self.get_device_details(device_id).await?;
+        Ok(find_schedule_conflicts(
+            &details.daily,
+            &details.calendar,
+            details.current_date(),
+        ))
     }
 
     // Combined Effect Methods
@@ -815,14 +1041,25 @@ impl TrimlightClient {
         .await
     }
 
-    pub async fn clear_overlay_effects(
+    /// Replace all overlay effects with `(overlay_type, target_effect_id)` pairs
+    pub async fn set_overlay_effects(
         &self,
         device_id: &str,
+        overlays: &[(i32, i32)],
     ) -> Result<BasicResponse, TrimlightError> {
+        let overlays: Vec<_> = overlays
+            .iter()
+            .map(|(overlay_type, target_effect_id)| {
+                serde_json::json!({
+                    "overlayType": overlay_type,
+                    "targetEffect": target_effect_id
+                })
+            })
+            .collect();
         let body = serde_json::json!({
             "deviceId": device_id,
             "payload": {
-                "overlayEffects": []
+                "overlayEffects": overlays
             }
         });
 
@@ -833,36 +1070,720 @@ impl TrimlightClient {
         )
         .await
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use mockito::Server;
+    pub async fn clear_overlay_effects(
+        &self,
+        device_id: &str,
+    ) -> Result<BasicResponse, TrimlightError> {
+        let body = serde_json::json!({
+            "deviceId": device_id,
+            "payload": {
+                "overlayEffects": []
+            }
+        });
 
-    async fn create_test_client(server: &Server) -> TrimlightClient {
-        TrimlightClient::with_base_url("test_id", "test_secret", server.url())
+        self.request(
+            Method::POST,
+            "/v1/oauth/resources/device/effect/overlay",
+            Some(&body),
+        )
+        .await
     }
 
-    #[tokio::test]
-    async fn test_get_device_list() {
-        let mut server = Server::new_async().await;
-        let mock_response = serde_json::json!({
-            "code": 0,
-            "desc": "Success",
-            "payload": {
-                "total": 1,
-                "current": 1,
-                "data": [{
-                    "deviceId": "test123",
-                    "name": "Test Device",
-                    "switchState": 1,
-                    "connectivity": 1,
-                    "state": 1,
-                    "fwVersionName": "1.0.0"
-                }]
+    // Configuration Methods
+    /// Carry out the changes planned by [`plan_config`](crate::plan_config)
+    ///
+    /// Changes are applied in plan order and the first failure stops the run.
+    /// Effects are created before anything refers to them; the device is then
+    /// fetched again to look up the IDs it assigned. Pruned effects that are
+    /// still used by a section the config doesn't manage fail with
+    /// [`TrimlightError::EffectInUse`].
+    pub async fn apply_config(
+        &self,
+        device_id: &str,
+        plan: &ConfigPlan,
+    ) -> Result<(), TrimlightError> {
+        let mut effect_ids: Option<HashMap<String, i32>> = None;
+        let no_effects = HashMap::new();
+
+        for change in &plan.changes {
+            let refers_to_effects = matches!(
+                change,
+                ConfigChange::AddDaily { .. }
+                    | ConfigChange::ModifyDaily { .. }
+                    | ConfigChange::AddCalendar { .. }
+                    | ConfigChange::ModifyCalendar { .. }
+                    | ConfigChange::SetCombined { .. }
+                    | ConfigChange::SetOverlays { .. }
+            );
+            if refers_to_effects && effect_ids.is_none() {
+                let details = self.get_device_details(device_id).await?;
+                effect_ids = Some(
+                    details
+                        .effects
+                        .into_iter()
+                        .map(|e| (e.name, e.id))
+                        .collect(),
+                );
             }
-        });
+            let id_of =
+                |name: &str| effect_id_for(effect_ids.as_ref().unwrap_or(&no_effects), name);
+
+            match change {
+                ConfigChange::SetName { to, .. } => {
+                    self.set_device_name(device_id, to).await?;
+                }
+                ConfigChange::SetColorOrder { to, .. } => {
+                    self.set_color_order(device_id, *to).await?;
+                }
+                ConfigChange::SetPorts { to, .. } => {
+                    let ports: Vec<(i32, i32)> = to.iter().map(|p| (p.start, p.end)).collect();
+                    self.set_ports(device_id, &ports).await?;
+                }
+                ConfigChange::AddEffect { effect } => {
+                    self.add_effect(device_id, effect).await?;
+                }
+                ConfigChange::UpdateEffect { effect, .. } => {
+                    self.save_effect(device_id, effect).await?;
+                }
+                ConfigChange::DeleteEffect { id, .. } => {
+                    self.delete_effect(device_id, *id, DeleteMode::Safe).await?;
+                }
+                ConfigChange::AddDaily { schedule } => {
+                    self.add_daily_schedule_with_state(
+                        device_id,
+                        id_of(&schedule.effect)?,
+                        schedule.start.clone(),
+                        schedule.end.clone(),
+                        schedule.repeat.value(),
+                        schedule.enabled,
+                    )
+                    .await?;
+                }
+                ConfigChange::ModifyDaily { id, from, to } => {
+                    self.modify_schedule(
+                        device_id,
+                        *id,
+                        "daily",
+                        Some(id_of(&to.effect)?),
+                        None,
+                        None,
+                        Some(to.start.clone()),
+                        Some(to.end.clone()),
+                        Some(to.repeat.value()),
+                    )
+                    .await?;
+                    if from.enabled != to.enabled {
+                        self.toggle_schedule(device_id, *id, "daily", to.enabled)
+                            .await?;
+                    }
+                }
+                ConfigChange::DeleteDaily { id, .. } => {
+                    self.delete_schedule(device_id, *id, "daily").await?;
+                }
+                ConfigChange::AddCalendar { schedule } => {
+                    self.add_calendar_schedule(
+                        device_id,
+                        id_of(&schedule.effect)?,
+                        schedule.start_date.clone(),
+                        schedule.end_date.clone(),
+                        schedule.start.clone(),
+                        schedule.end.clone(),
+                    )
+                    .await?;
+                }
+                ConfigChange::ModifyCalendar { id, to, .. } => {
+                    self.modify_schedule(
+                        device_id,
+                        *id,
+                        "calendar",
+                        Some(id_of(&to.effect)?),
+                        Some(to.start_date.clone()),
+                        Some(to.end_date.clone()),
+                        Some(to.start.clone()),
+                        Some(to.end.clone()),
+                        None,
+                    )
+                    .await?;
+                }
+                ConfigChange::DeleteCalendar { id, .. } => {
+                    self.delete_schedule(device_id, *id, "calendar").await?;
+                }
+                ConfigChange::SetCombined { to, .. } => {
+                    if to.effects.is_empty() {
+                        self.clear_combined_effect(device_id).await?;
+                    } else {
+                        let ids = to
+                            .effects
+                            .iter()
+                            .map(|name| id_of(name))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        self.set_combined_effect(device_id, &ids, to.interval)
+                            .await?;
+                    }
+                }
+                ConfigChange::SetOverlays { to, .. } => {
+                    if to.is_empty() {
+                        self.clear_overlay_effects(device_id).await?;
+                    } else {
+                        let overlays = to
+                            .iter()
+                            .map(|o| Ok((o.overlay_type.value(), id_of(&o.effect)?)))
+                            .collect::<Result<Vec<_>, TrimlightError>>()?;
+                        self.set_overlay_effects(device_id, &overlays).await?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Idempotent Methods
+    /// Make sure a device has an effect with the name and settings of `effect`
+    ///
+    /// The effect is created when the device has none with its name and
+    /// otherwise updated in place, keeping its ID so schedules that use it
+    /// pick up the change. The ID of `effect` is ignored.
+    pub async fn ensure_effect(
+        &self,
+        device_id: &str,
+        effect: &Effect,
+    ) -> Result<EnsureAction, TrimlightError> {
+        let details = self.get_device_details(device_id).await?;
+        match details.effect_by_name(&effect.name) {
+            Some(existing) if existing.has_same_settings(effect) => {
+                Ok(EnsureAction::Unchanged { id: existing.id })
+            }
+            Some(existing) => {
+                let mut updated = existing.clone();
+                updated.speed = effect.speed;
+                updated.brightness = effect.brightness;
+                updated.set_kind(effect.kind()?);
+                self.save_effect(device_id, &updated).await?;
+                Ok(EnsureAction::Updated { id: existing.id })
+            }
+            None => Ok(EnsureAction::Created {
+                id: self.add_effect(device_id, effect).await?,
+            }),
+        }
+    }
+
+    /// Make sure a daily schedule runs an effect between two times
+    ///
+    /// A schedule with the same effect, start and end time is a match; it is
+    /// updated when its repetition differs or it is disabled. Otherwise a new
+    /// schedule is added.
+    pub async fn ensure_daily_schedule(
+        &self,
+        device_id: &str,
+        effect_id: i32,
+        start: String,
+        end: String,
+        repetition: i32,
+    ) -> Result<EnsureAction, TrimlightError> {
+        let start_time = parse_schedule_time(&start)?;
+        let end_time = parse_schedule_time(&end)?;
+        let daily = self.get_device_schedules(device_id).await?.daily;
+        let existing = daily.iter().find(|s| {
+            s.effect_id == effect_id && s.start_time == start_time && s.end_time == end_time
+        });
+
+        match existing {
+            Some(schedule) => {
+                if schedule.repetition == repetition && schedule.enable {
+                    return Ok(EnsureAction::Unchanged { id: schedule.id });
+                }
+                if schedule.repetition != repetition {
+                    self.modify_schedule(
+                        device_id,
+                        schedule.id,
+                        "daily",
+                        None,
+                        None,
+                        None,
+                        None,
+                        None,
+                        Some(repetition),
+                    )
+                    .await?;
+                }
+                if !schedule.enable {
+                    self.toggle_schedule(device_id, schedule.id, "daily", true)
+                        .await?;
+                }
+                Ok(EnsureAction::Updated { id: schedule.id })
+            }
+            None => {
+                self.add_daily_schedule(device_id, effect_id, start, end, repetition)
+                    .await?;
+                let id = self
+                    .get_device_schedules(device_id)
+                    .await?
+                    .daily
+                    .iter()
+                    .find(|s| s.effect_id == effect_id && !daily.iter().any(|d| d.id == s.id))
+                    .map(|s| s.id);
+                Ok(EnsureAction::Created { id })
+            }
+        }
+    }
+
+    /// Make sure a calendar schedule runs an effect between two dates
+    ///
+    /// A schedule with the same effect, start and end date is a match; its
+    /// times are updated when they differ. Otherwise a new schedule is added.
+    pub async fn ensure_calendar_schedule(
+        &self,
+        device_id: &str,
+        effect_id: i32,
+        start_date: String,
+        end_date: String,
+        start_time: String,
+        end_time: String,
+    ) -> Result<EnsureAction, TrimlightError> {
+        let (start_month, start_day) = utils::parse_date(&start_date)?;
+        let (end_month, end_day) = utils::parse_date(&end_date)?;
+        let start = parse_schedule_time(&start_time)?;
+        let end = parse_schedule_time(&end_time)?;
+        let calendar = self.get_device_schedules(device_id).await?.calendar;
+        let existing = calendar.iter().find(|s| {
+            s.effect_id == effect_id
+                && (s.start_date.month, s.start_date.day) == (start_month, start_day)
+                && (s.end_date.month, s.end_date.day) == (end_month, end_day)
+        });
+
+        match existing {
+            Some(schedule) if schedule.start_time == start && schedule.end_time == end => {
+                Ok(EnsureAction::Unchanged { id: schedule.id })
+            }
+            Some(schedule) => {
+                self.modify_schedule(
+                    device_id,
+                    schedule.id,
+                    "calendar",
+                    None,
+                    None,
+                    None,
+                    Some(start_time),
+                    Some(end_time),
+                    None,
+                )
+                .await?;
+                Ok(EnsureAction::Updated { id: schedule.id })
+            }
+            None => {
+                self.add_calendar_schedule(
+                    device_id, effect_id, start_date, end_date, start_time, end_time,
+                )
+                .await?;
+                let id = self
+                    .get_device_schedules(device_id)
+                    .await?
+                    .calendar
+                    .iter()
+                    .find(|s| s.effect_id == effect_id && !calendar.iter().any(|c| c.id == s.id))
+                    .map(|s| s.id);
+                Ok(EnsureAction::Created { id })
+            }
+        }
+    }
+
+    // Snapshot Methods
+    /// Recreate a snapshot on a device from scratch
+    ///
+    /// See [`restore_details`](Self::restore_details).
+    pub async fn restore_snapshot(
+        &self,
+        device_id: &str,
+        snapshot: &Snapshot,
+    ) -> Result<RestoreReport, TrimlightError> {
+        self.restore_details(device_id, &snapshot.device).await
+    }
+
+    /// Replace everything saved on a device with the state in `details`
+    ///
+    /// Overlays, the combined effect, schedules and saved effects are deleted
+    /// first. The name, color order and ports are then set, the effects saved
+    /// again and everything that referred to them pointed at the IDs the
+    /// device assigned. Schedules, combined effect entries and overlays that
+    /// refer to an effect missing from `details` are skipped and reported.
+    pub async fn restore_details(
+        &self,
+        device_id: &str,
+        details: &DeviceDetails,
+    ) -> Result<RestoreReport, TrimlightError> {
+        self.copy_device_state(device_id, details, CloneMode::Replace, true)
+            .await
+    }
+
+    /// Copy saved effects, schedules, the combined effect and overlays from
+    /// one device to another
+    ///
+    /// The name, color order and ports of the target are left alone since
+    /// they belong to its hardware. With [`CloneMode::Replace`] the target is
+    /// cleared first as in [`restore_details`](Self::restore_details). With
+    /// [`CloneMode::Merge`] its effects are kept and those with the same name
+    /// as a source effect are updated in place, schedules already on the target
+    /// aren't added twice, and overlays are added to the existing ones.
+    pub async fn clone_device(
+        &self,
+        from_device_id: &str,
+        to_device_id: &str,
+        mode: CloneMode,
+    ) -> Result<RestoreReport, TrimlightError> {
+        let source = self.get_device_details(from_device_id).await?;
+        self.copy_device_state(to_device_id, &source, mode, false)
+            .await
+    }
+
+    async fn copy_device_state(
+        &self,
+        device_id: &str,
+        details: &DeviceDetails,
+        mode: CloneMode,
+        device_settings: bool,
+    ) -> Result<RestoreReport, TrimlightError> {
+        check_copyable(details)?;
+        let mut current = self.get_device_details(device_id).await?;
+        if mode == CloneMode::Replace {
+            if !current.overlay_effects.is_empty() {
+                self.clear_overlay_effects(device_id).await?;
+            }
+            if current
+                .combined_effect
+                .as_ref()
+                .is_some_and(|c| !c.effect_ids.is_empty())
+            {
+                self.clear_combined_effect(device_id).await?;
+            }
+            for schedule in &current.calendar {
+                self.delete_schedule(device_id, schedule.id, "calendar")
+                    .await?;
+            }
+            for schedule in &current.daily {
+                self.delete_schedule(device_id, schedule.id, "daily")
+                    .await?;
+            }
+            for effect in &current.effects {
+                self.delete_effect(device_id, effect.id, DeleteMode::Force)
+                    .await?;
+            }
+            current.overlay_effects.clear();
+            current.daily.clear();
+            current.calendar.clear();
+            current.effects.clear();
+        }
+
+        if device_settings {
+            if details.name != current.name {
+                self.set_device_name(device_id, &details.name).await?;
+            }
+            if details.color_order != current.color_order {
+                self.set_color_order(device_id, details.color_order).await?;
+            }
+            if !details.ports.is_empty() {
+                let ports: Vec<(i32, i32)> =
+                    details.ports.iter().map(|p| (p.start, p.end)).collect();
+                self.set_ports(device_id, &ports).await?;
+            }
+        }
+
+        let mut report = RestoreReport::default();
+        let mut unassigned = Vec::new();
+        for effect in &details.effects {
+            // When merging, an effect with the same name is reused
+            let existing = current.effects.iter().find(|e| {
+                e.name == effect.name && !report.effect_ids.values().any(|id| *id == e.id)
+            });
+            if let Some(existing) = existing {
+                if !existing.has_same_settings(effect) {
+                    let mut updated = existing.clone();
+                    updated.speed = effect.speed;
+                    updated.brightness = effect.brightness;
+                    updated.set_kind(effect.kind()?);
+                    self.save_effect(device_id, &updated).await?;
+                    report.updated_effects += 1;
+                }
+                report.effect_ids.insert(effect.id, existing.id);
+                continue;
+            }
+            match self.add_effect(device_id, effect).await? {
+                Some(id) => {
+                    report.effect_ids.insert(effect.id, id);
+                }
+                None => unassigned.push(effect),
+            }
+            report.added_effects += 1;
+        }
+        // The device didn't report every new ID, so match the rest by name
+        if !unassigned.is_empty() {
+            let saved = self.get_device_details(device_id).await?.effects;
+            for effect in unassigned {
+                let new = saved.iter().find(|e| {
+                    e.name == effect.name
+                        && !report.effect_ids.values().any(|id| *id == e.id)
+                        && !current.effects.iter().any(|c| c.id == e.id)
+                });
+                if let Some(new) = new {
+                    report.effect_ids.insert(effect.id, new.id);
+                }
+            }
+        }
+        let remap = |report: &mut RestoreReport, what: String, effect_id: i32| {
+            let id = report.effect_ids.get(&effect_id).copied();
+            if id.is_none() {
+                report
+                    .skipped
+                    .push(format!("{} refers to missing effect {}", what, effect_id));
+            }
+            id
+        };
+
+        let mut free_daily_slots = DAILY_SCHEDULE_SLOTS.saturating_sub(current.daily.len());
+        for schedule in &details.daily {
+            let what = format!("Daily schedule {}", schedule.id);
+            let Some(effect_id) = remap(&mut report, what, schedule.effect_id) else {
+                continue;
+            };
+            let already_there = current.daily.iter().any(|s| {
+                s.effect_id == effect_id
+                    && s.start_time == schedule.start_time
+                    && s.end_time == schedule.end_time
+                    && s.repetition == schedule.repetition
+            });
+            if already_there {
+                continue;
+            }
+            if free_daily_slots == 0 {
+                report.skipped.push(format!(
+                    "Daily schedule {} skipped, no free daily schedule slot",
+                    schedule.id
+                ));
+                continue;
+            }
+            self.add_daily_schedule_with_state(
+                device_id,
+                effect_id,
+                format!(
+                    "{:02}:{:02}",
+                    schedule.start_time.hours, schedule.start_time.minutes
+                ),
+                format!(
+                    "{:02}:{:02}",
+                    schedule.end_time.hours, schedule.end_time.minutes
+                ),
+                schedule.repetition,
+                schedule.enable,
+            )
+            .await?;
+            free_daily_slots -= 1;
+            report.daily += 1;
+        }
+        for schedule in &details.calendar {
+            let what = format!("Calendar schedule {}", schedule.id);
+            let Some(effect_id) = remap(&mut report, what, schedule.effect_id) else {
+                continue;
+            };
+            let already_there = current.calendar.iter().any(|s| {
+                s.effect_id == effect_id
+                    && s.start_date == schedule.start_date
+                    && s.end_date == schedule.end_date
+                    && s.start_time == schedule.start_time
+                    && s.end_time == schedule.end_time
+            });
+            if already_there {
+                continue;
+            }
+            self.add_calendar_schedule(
+                device_id,
+                effect_id,
+                format!(
+                    "{:02}-{:02}",
+                    schedule.start_date.month, schedule.start_date.day
+                ),
+                format!(
+                    "{:02}-{:02}",
+                    schedule.end_date.month, schedule.end_date.day
+                ),
+                format!(
+                    "{:02}:{:02}",
+                    schedule.start_time.hours, schedule.start_time.minutes
+                ),
+                format!(
+                    "{:02}:{:02}",
+                    schedule.end_time.hours, schedule.end_time.minutes
+                ),
+            )
+            .await?;
+            report.calendar += 1;
+        }
+
+        if let Some(combined) = details.combined_effect.as_ref() {
+            let ids: Vec<i32> = combined
+                .effect_ids
+                .iter()
+                .filter_map(|id| remap(&mut report, "Combined effect".to_string(), *id))
+                .collect();
+            if !ids.is_empty() {
+                self.set_combined_effect(device_id, &ids, combined.interval)
+                    .await?;
+            }
+        }
+        let existing: Vec<(i32, i32)> = current
+            .overlay_effects
+            .iter()
+            .map(|o| (o.overlay_type, o.target_effect))
+            .collect();
+        let mut overlays = existing.clone();
+        for overlay in &details.overlay_effects {
+            let Some(id) = remap(&mut report, "Overlay".to_string(), overlay.target_effect) else {
+                continue;
+            };
+            if !overlays.contains(&(overlay.overlay_type, id)) {
+                overlays.push((overlay.overlay_type, id));
+            }
+        }
+        if overlays != existing {
+            self.set_overlay_effects(device_id, &overlays).await?;
+        }
+
+        Ok(report)
+    }
+}
+
+/// Check everything [`TrimlightClient::copy_device_state`] writes, so a bad
+/// snapshot fails before anything on the target is deleted
+///
+/// Schedules that refer to a missing effect are skipped when copying, so
+/// they aren't checked.
+fn check_copyable(details: &DeviceDetails) -> Result<(), TrimlightError> {
+    for effect in &details.effects {
+        effect.kind()?;
+    }
+    let copied = |effect_id: i32| details.effects.iter().any(|e| e.id == effect_id);
+    for schedule in details.daily.iter().filter(|s| copied(s.effect_id)) {
+        ScheduleTime::new(schedule.start_time.hours, schedule.start_time.minutes)?;
+        ScheduleTime::new(schedule.end_time.hours, schedule.end_time.minutes)?;
+    }
+    for schedule in details.calendar.iter().filter(|s| copied(s.effect_id)) {
+        schedule.start_date.validate()?;
+        schedule.end_date.validate()?;
+        ScheduleTime::new(schedule.start_time.hours, schedule.start_time.minutes)?;
+        ScheduleTime::new(schedule.end_time.hours, schedule.end_time.minutes)?;
+    }
+    Ok(())
+}
+
+fn parse_schedule_time(time: &str) -> Result<ScheduleTime, TrimlightError> {
+    let (hours, minutes) = utils::parse_time(time)?;
+    ScheduleTime::new(hours, minutes)
+}
+
+/// Look up an effect ID by name, accepting `#ID` for effects without a name
+fn effect_id_for(ids: &HashMap<String, i32>, name: &str) -> Result<i32, TrimlightError> {
+    ids.get(name)
+        .copied()
+        .or_else(|| name.strip_prefix('#').and_then(|id| id.parse().ok()))
+        .ok_or_else(|| TrimlightError::ApiError {
+            code: 404,
+            message: format!("Effect '{}' not found", name),
+        })
+}
+
+fn schedule_update_endpoint(schedule_type: &str) -> Result<&'static str, TrimlightError> {
+    match schedule_type.to_lowercase().as_str() {
+        "daily" => Ok("/v1/oauth/resources/device/schedule/daily/update"),
+        "calendar" => Ok("/v1/oauth/resources/device/schedule/calendar/update"),
+        _ => Err(TrimlightError::ApiError {
+            code: 400,
+            message: "Invalid schedule type. Must be 'daily' or 'calendar'".to_string(),
+        }),
+    }
+}
+
+fn merge_times(
+    start_time: &mut ScheduleTime,
+    end_time: &mut ScheduleTime,
+    start: Option<String>,
+    end: Option<String>,
+) -> Result<(), TrimlightError> {
+    if let Some(start) = start {
+        let (hours, minutes) = utils::parse_time(&start)?;
+        start_time.hours = hours;
+        start_time.minutes = minutes;
+    }
+    if let Some(end) = end {
+        let (hours, minutes) = utils::parse_time(&end)?;
+        end_time.hours = hours;
+        end_time.minutes = minutes;
+    }
+    Ok(())
+}
+
+fn merge_common_fields(
+    effect: &mut Effect,
+    name: Option<&str>,
+    speed: Option<i32>,
+    brightness: Option<i32>,
+) {
+    if let Some(name) = name {
+        effect.name = name.to_string();
+    }
+    if let Some(speed) = speed {
+        effect.speed = speed;
+    }
+    if let Some(brightness) = brightness {
+        effect.brightness = brightness;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Server;
+
+    async fn create_test_client(server: &Server) -> TrimlightClient {
+        TrimlightClient::with_base_url("test_id", "test_secret", server.url())
+    }
+
+    async fn mock_device_effects(server: &mut Server, effects: serde_json::Value) -> mockito::Mock {
+        let details_response = serde_json::json!({
+            "code": 0,
+            "desc": "Success",
+            "payload": {
+                "effects": effects
+            }
+        });
+
+        server
+            .mock("POST", "/v1/oauth/resources/device/get")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(details_response.to_string())
+            .create_async()
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_get_device_list() {
+        let mut server = Server::new_async().await;
+        let mock_response = serde_json::json!({
+            "code": 0,
+            "desc": "Success",
+            "payload": {
+                "total": 1,
+                "current": 1,
+                "data": [{
+                    "deviceId": "test123",
+                    "name": "Test Device",
+                    "switchState": 1,
+                    "connectivity": 1,
+                    "state": 1,
+                    "fwVersionName": "1.0.0"
+                }]
+            }
+        });
 
         let _m = server
             .mock("GET", "/v1/oauth/resources/devices")
@@ -885,6 +1806,55 @@ mod tests {
         assert_eq!(result.data[0].name, "Test Device");
     }
 
+    #[tokio::test]
+    async fn test_get_all_devices() {
+        let mut server = Server::new_async().await;
+        let device = |id: &str| {
+            serde_json::json!({
+                "deviceId": id,
+                "name": format!("Device {}", id),
+                "switchState": 1,
+                "connectivity": 1,
+                "state": 1,
+                "fwVersionName": "1.0.0"
+            })
+        };
+        let page = |current: i32, data: Vec<serde_json::Value>| {
+            serde_json::json!({
+                "code": 0,
+                "desc": "Success",
+                "payload": {"total": 3, "current": current, "data": data}
+            })
+        };
+
+        let first = server
+            .mock("GET", "/v1/oauth/resources/devices")
+            .match_body(mockito::Matcher::Json(serde_json::json!({"page": 1})))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(page(1, vec![device("a"), device("b")]).to_string())
+            .expect(1)
+            .create_async()
+            .await;
+        let second = server
+            .mock("GET", "/v1/oauth/resources/devices")
+            .match_body(mockito::Matcher::Json(serde_json::json!({"page": 2})))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(page(2, vec![device("c")]).to_string())
+            .expect(1)
+            .create_async()
+            .await;
+
+        let client = create_test_client(&server).await;
+        let devices = client.get_all_devices().await.unwrap();
+
+        let ids: Vec<&str> = devices.iter().map(|d| d.device_id.as_str()).collect();
+        assert_eq!(ids, ["a", "b", "c"]);
+        first.assert_async().await;
+        second.assert_async().await;
+    }
+
     #[tokio::test]
     async fn test_set_device_switch_state() {
         let mut server = Server::new_async().await;
@@ -1003,6 +1973,43 @@ mod tests {
         ));
     }
 
+    #[tokio::test]
+    async fn test_strict_mode() {
+        let mut server = Server::new_async().await;
+        let mock_response = serde_json::json!({
+            "code": 0,
+            "desc": "Success",
+            "payload": {
+                "total": 1,
+                "current": 1,
+                "data": [{
+                    "deviceId": "test123",
+                    "name": "Test Device",
+                    "region": "us"
+                }]
+            }
+        });
+
+        let _m = server
+            .mock("GET", "/v1/oauth/resources/devices")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(mock_response.to_string())
+            .expect(2)
+            .create_async()
+            .await;
+
+        let client = create_test_client(&server).await;
+        let result = client.get_device_list(None).await.unwrap();
+        assert_eq!(result.data[0].extra["region"], "us");
+
+        let result = client.with_strict_mode(true).get_device_list(None).await;
+        assert!(matches!(
+            result,
+            Err(TrimlightError::UnexpectedFields(fields)) if fields == vec!["data[0].region".to_string()]
+        ));
+    }
+
     #[tokio::test]
     async fn test_auth_headers() {
         let mut server = Server::new_async().await;
@@ -1054,216 +2061,939 @@ mod tests {
             .match_body(mockito::Matcher::JsonString(r#"{"deviceId":"test123","payload":{"category":1,"mode":1,"speed":5,"brightness":100,"pixelLen":50,"reverse":false}}"#.to_string()))
             .with_status(200)
             .with_header("content-type", "application/json")
-            .with_body(mock_response.to_string())
+            .with_body(mock_response.to_string())
+            .create_async()
+            .await;
+
+        let client = create_test_client(&server).await;
+        let result = client
+            .preview_builtin_effect("test123", 1, 5, 100, 50, false)
+            .await
+            .unwrap();
+
+        assert_eq!(result.code, 0);
+        assert_eq!(result.desc, "Success");
+    }
+
+    #[tokio::test]
+    async fn test_delete_effect() {
+        let mut server = Server::new_async().await;
+        let mock_response = serde_json::json!({
+            "code": 0,
+            "desc": "Success",
+            "payload": {
+                "code": 0,
+                "desc": "Success"
+            }
+        });
+
+        let _m = server
+            .mock("POST", "/v1/oauth/resources/device/effect/delete")
+            .match_header("authorization", mockito::Matcher::Any)
+            .match_header("S-ClientId", mockito::Matcher::Any)
+            .match_header("S-Timestamp", mockito::Matcher::Any)
+            .match_body(mockito::Matcher::JsonString(
+                r#"{"deviceId":"test123","payload":{"id":1}}"#.to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(mock_response.to_string())
+            .create_async()
+            .await;
+
+        let client = create_test_client(&server).await;
+        let result = client
+            .delete_effect("test123", 1, DeleteMode::Force)
+            .await
+            .unwrap();
+
+        assert_eq!(result.code, 0);
+        assert_eq!(result.desc, "Success");
+    }
+
+    #[tokio::test]
+    async fn test_delete_effect_in_use() {
+        let mut server = Server::new_async().await;
+        let details = serde_json::json!({
+            "code": 0,
+            "desc": "Success",
+            "payload": {
+                "effects": [{"id": 1, "name": "Warm White"}, {"id": 2, "name": "Candy Cane"}],
+                "daily": [{"id": 0, "enable": true, "effectId": 1, "repetition": 0}],
+                "combinedEffect": {"effectIds": [1, 2], "interval": 30},
+                "overlayEffects": [{"overlayType": 1, "targetEffect": 1}]
+            }
+        });
+        let _details = server
+            .mock("POST", "/v1/oauth/resources/device/get")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(details.to_string())
+            .create_async()
+            .await;
+        let ok = r#"{"code":0,"desc":"Success"}"#;
+        let mock = |server: &mut Server, path: &str, body: &str| {
+            server
+                .mock("POST", path)
+                .match_body(mockito::Matcher::JsonString(body.to_string()))
+                .with_status(200)
+                .with_header("content-type", "application/json")
+                .with_body(ok)
+                .expect(1)
+                .create()
+        };
+        let delete = mock(
+            &mut server,
+            "/v1/oauth/resources/device/effect/delete",
+            r#"{"deviceId":"test123","payload":{"id":1}}"#,
+        );
+
+        let client = create_test_client(&server).await;
+        let error = client
+            .delete_effect("test123", 1, DeleteMode::Safe)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Effect 1 is still used by daily schedule 0, combined effect, snow overlay"
+        );
+
+        let schedule = mock(
+            &mut server,
+            "/v1/oauth/resources/device/schedule/daily/delete",
+            r#"{"deviceId":"test123","payload":{"id":0}}"#,
+        );
+        let combined = mock(
+            &mut server,
+            "/v1/oauth/resources/device/effect/combined/set",
+            r#"{"deviceId":"test123","payload":{"effectIds":[2],"interval":30}}"#,
+        );
+        let overlays = mock(
+            &mut server,
+            "/v1/oauth/resources/device/effect/overlay",
+            r#"{"deviceId":"test123","payload":{"overlayEffects":[]}}"#,
+        );
+        client
+            .delete_effect("test123", 1, DeleteMode::Cascade)
+            .await
+            .unwrap();
+        schedule.assert_async().await;
+        combined.assert_async().await;
+        overlays.assert_async().await;
+        delete.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_add_daily_schedule() {
+        let mut server = Server::new_async().await;
+        let mock_response = serde_json::json!({
+            "code": 0,
+            "desc": "Success",
+            "payload": {
+                "code": 0,
+                "desc": "Success"
+            }
+        });
+
+        let _m = server.mock("POST", "/v1/oauth/resources/device/schedule/daily/add")
+            .match_header("authorization", mockito::Matcher::Any)
+            .match_header("S-ClientId", mockito::Matcher::Any)
+            .match_header("S-Timestamp", mockito::Matcher::Any)
+            .match_body(mockito::Matcher::JsonString(r#"{"deviceId":"test123","payload":{"id":-1,"enable":true,"effectId":1,"repetition":1,"startTime":{"hours":8,"minutes":0},"endTime":{"hours":20,"minutes":0}}}"#.to_string()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(mock_response.to_string())
+            .create_async()
+            .await;
+
+        let client = create_test_client(&server).await;
+        let result = client
+            .add_daily_schedule("test123", 1, "08:00".to_string(), "20:00".to_string(), 1)
+            .await
+            .unwrap();
+
+        assert_eq!(result.code, 0);
+        assert_eq!(result.desc, "Success");
+    }
+
+    #[tokio::test]
+    async fn test_add_calendar_schedule() {
+        let mut server = Server::new_async().await;
+        let mock_response = serde_json::json!({
+            "code": 0,
+            "desc": "Success",
+            "payload": {
+                "code": 0,
+                "desc": "Success"
+            }
+        });
+
+        let _m = server.mock("POST", "/v1/oauth/resources/device/schedule/calendar/add")
+            .match_header("authorization", mockito::Matcher::Any)
+            .match_header("S-ClientId", mockito::Matcher::Any)
+            .match_header("S-Timestamp", mockito::Matcher::Any)
+            .match_body(mockito::Matcher::JsonString(r#"{"deviceId":"test123","payload":{"id":0,"effectId":1,"startDate":{"month":12,"day":25},"endDate":{"month":12,"day":31},"startTime":{"hours":17,"minutes":0},"endTime":{"hours":23,"minutes":0}}}"#.to_string()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(mock_response.to_string())
+            .create_async()
+            .await;
+
+        let client = create_test_client(&server).await;
+        let result = client
+            .add_calendar_schedule(
+                "test123",
+                1,
+                "12-25".to_string(),
+                "12-31".to_string(),
+                "17:00".to_string(),
+                "23:00".to_string(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.code, 0);
+        assert_eq!(result.desc, "Success");
+    }
+
+    #[tokio::test]
+    async fn test_delete_schedule() {
+        let mut server = Server::new_async().await;
+        let mock_response = serde_json::json!({
+            "code": 0,
+            "desc": "Success",
+            "payload": {
+                "code": 0,
+                "desc": "Success"
+            }
+        });
+
+        let _m = server
+            .mock("POST", "/v1/oauth/resources/device/schedule/daily/delete")
+            .match_header("authorization", mockito::Matcher::Any)
+            .match_header("S-ClientId", mockito::Matcher::Any)
+            .match_header("S-Timestamp", mockito::Matcher::Any)
+            .match_body(mockito::Matcher::JsonString(
+                r#"{"deviceId":"test123","payload":{"id":1}}"#.to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(mock_response.to_string())
+            .create_async()
+            .await;
+
+        let client = create_test_client(&server).await;
+        let result = client.delete_schedule("test123", 1, "daily").await.unwrap();
+
+        assert_eq!(result.code, 0);
+        assert_eq!(result.desc, "Success");
+    }
+
+    #[tokio::test]
+    async fn test_toggle_schedule() {
+        let mut server = Server::new_async().await;
+        let mock_response = serde_json::json!({
+            "code": 0,
+            "desc": "Success",
+            "payload": {
+                "code": 0,
+                "desc": "Success"
+            }
+        });
+
+        let _m = server
+            .mock("POST", "/v1/oauth/resources/device/schedule/daily/update")
+            .match_header("authorization", mockito::Matcher::Any)
+            .match_header("S-ClientId", mockito::Matcher::Any)
+            .match_header("S-Timestamp", mockito::Matcher::Any)
+            .match_body(mockito::Matcher::JsonString(
+                r#"{"deviceId":"test123","payload":{"id":1,"enable":true}}"#.to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(mock_response.to_string())
+            .create_async()
+            .await;
+
+        let client = create_test_client(&server).await;
+        let result = client
+            .toggle_schedule("test123", 1, "daily", true)
+            .await
+            .unwrap();
+
+        assert_eq!(result.code, 0);
+        assert_eq!(result.desc, "Success");
+    }
+
+    #[tokio::test]
+    async fn test_toggle_calendar_schedule() {
+        let mut server = Server::new_async().await;
+        let mock_response = serde_json::json!({
+            "code": 0,
+            "desc": "Success",
+            "payload": {
+                "code": 0,
+                "desc": "Success"
+            }
+        });
+
+        let m = server
+            .mock(
+                "POST",
+                "/v1/oauth/resources/device/schedule/calendar/update",
+            )
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(mock_response.to_string())
+            .expect(0)
+            .create_async()
+            .await;
+
+        let client = create_test_client(&server).await;
+        let result = client
+            .toggle_schedule("test123", 2, "calendar", false)
+            .await;
+        assert!(matches!(
+            result,
+            Err(TrimlightError::ApiError { code: 400, message })
+                if message == "Calendar schedules cannot be enabled or disabled, delete them instead"
+        ));
+        m.assert_async().await;
+
+        let result = client.toggle_schedule("test123", 2, "weekly", false).await;
+        assert!(matches!(
+            result,
+            Err(TrimlightError::ApiError { code: 400, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_modify_schedule() {
+        let mut server = Server::new_async().await;
+
+        // Mock for get_device_schedules (which calls get_device_details)
+        let details_response = serde_json::json!({
+            "code": 0,
+            "desc": "Success",
+            "payload": {
+                "daily": [{
+                    "id": 1,
+                    "enable": true,
+                    "effectId": 1,
+                    "repetition": 1,
+                    "startTime": {
+                        "hours": 8,
+                        "minutes": 0
+                    },
+                    "endTime": {
+                        "hours": 20,
+                        "minutes": 0
+                    }
+                }],
+                "calendar": []
+            }
+        });
+
+        let _m1 = server
+            .mock("POST", "/v1/oauth/resources/device/get")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(details_response.to_string())
+            .create_async()
+            .await;
+
+        // Mock for modify_schedule
+        let update_response = serde_json::json!({
+            "code": 0,
+            "desc": "Success",
+            "payload": {
+                "code": 0,
+                "desc": "Success"
+            }
+        });
+
+        let _m2 = server.mock("POST", "/v1/oauth/resources/device/schedule/daily/update")
+            .match_body(mockito::Matcher::JsonString(r#"{"deviceId":"test123","payload":{"id":1,"enable":true,"effectId":2,"startTime":{"hours":9,"minutes":0},"endTime":{"hours":21,"minutes":0},"repetition":1}}"#.to_string()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(update_response.to_string())
+            .create_async()
+            .await;
+
+        let client = create_test_client(&server).await;
+        let result = client
+            .modify_schedule(
+                "test123",
+                1,
+                "daily",
+                Some(2),
+                None,
+                None,
+                Some("09:00".to_string()),
+                Some("21:00".to_string()),
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.code, 0);
+        assert_eq!(result.desc, "Success");
+    }
+
+    #[tokio::test]
+    async fn test_modify_schedule_keeps_unknown_fields() {
+        let mut server = Server::new_async().await;
+        let details = serde_json::json!({
+            "code": 0,
+            "desc": "Success",
+            "payload": {
+                "daily": [{"id": 1, "enable": true, "effectId": 1, "repetition": 1,
+                           "startTime": {"hours": 8, "minutes": 0, "offset": 5},
+                           "endTime": {"hours": 20, "minutes": 0}, "sunset": false}]
+            }
+        });
+        let _details = server
+            .mock("POST", "/v1/oauth/resources/device/get")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(details.to_string())
+            .create_async()
+            .await;
+        let update = server
+            .mock("POST", "/v1/oauth/resources/device/schedule/daily/update")
+            .match_body(mockito::Matcher::JsonString(
+                r#"{"deviceId":"test123","payload":{"id":1,"enable":true,"effectId":1,"repetition":1,"startTime":{"hours":8,"minutes":0,"offset":5},"endTime":{"hours":21,"minutes":0},"sunset":false}}"#
+                    .to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"code":0,"desc":"Success"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let client = create_test_client(&server).await;
+        client
+            .modify_schedule(
+                "test123",
+                1,
+                "daily",
+                None,
+                None,
+                None,
+                None,
+                Some("21:00".to_string()),
+                None,
+            )
+            .await
+            .unwrap();
+        update.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_apply_config() {
+        let mut server = Server::new_async().await;
+        let ok = serde_json::json!({"code": 0, "desc": "Success"}).to_string();
+
+        let before: DeviceDetails = serde_json::from_value(serde_json::json!({
+            "effects": [{"id": 1, "name": "Warm White", "category": 1, "mode": 0,
+                         "speed": 100, "brightness": 100, "pixelLen": 30, "reverse": false}]
+        }))
+        .unwrap();
+        let config = crate::DeviceConfig::from_toml(
+            r#"
+            [[effects]]
+            name = "Warm White"
+            built_in = 0
+
+            [[effects]]
+            name = "Storm"
+            built_in = 42
+
+            [[daily]]
+            effect = "Storm"
+            start = "18:00"
+            end = "23:00"
+
+            [combined]
+            effects = ["Warm White", "Storm"]
+            interval = 30
+            "#,
+        )
+        .unwrap();
+        let plan = crate::plan_config(&config, &before, false).unwrap();
+        assert_eq!(plan.changes.len(), 3);
+
+        // The device as it is after the new effect was saved
+        let after = serde_json::json!({
+            "code": 0,
+            "desc": "Success",
+            "payload": {
+                "effects": [
+                    {"id": 1, "name": "Warm White", "category": 1, "mode": 0},
+                    {"id": 7, "name": "Storm", "category": 1, "mode": 42}
+                ]
+            }
+        });
+        let _details = server
+            .mock("POST", "/v1/oauth/resources/device/get")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(after.to_string())
+            .create_async()
+            .await;
+        let add_effect = server.mock("POST", "/v1/oauth/resources/device/effect/save")
+            .match_body(mockito::Matcher::JsonString(r#"{"deviceId":"test123","payload":{"name":"Storm","category":1,"mode":42,"speed":100,"brightness":100,"pixelLen":30,"reverse":false}}"#.to_string()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(&ok)
+            .expect(1)
+            .create_async()
+            .await;
+        let add_daily = server.mock("POST", "/v1/oauth/resources/device/schedule/daily/add")
+            .match_body(mockito::Matcher::JsonString(r#"{"deviceId":"test123","payload":{"id":-1,"enable":true,"effectId":7,"repetition":1,"startTime":{"hours":18,"minutes":0},"endTime":{"hours":23,"minutes":0}}}"#.to_string()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(&ok)
+            .expect(1)
+            .create_async()
+            .await;
+        let combined = server
+            .mock("POST", "/v1/oauth/resources/device/effect/combined/set")
+            .match_body(mockito::Matcher::JsonString(
+                r#"{"deviceId":"test123","payload":{"effectIds":[1,7],"interval":30}}"#.to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(&ok)
+            .expect(1)
             .create_async()
             .await;
 
         let client = create_test_client(&server).await;
-        let result = client
-            .preview_builtin_effect("test123", 1, 5, 100, 50, false)
-            .await
-            .unwrap();
+        client.apply_config("test123", &plan).await.unwrap();
 
-        assert_eq!(result.code, 0);
-        assert_eq!(result.desc, "Success");
+        add_effect.assert_async().await;
+        add_daily.assert_async().await;
+        combined.assert_async().await;
     }
 
     #[tokio::test]
-    async fn test_delete_effect() {
+    async fn test_restore_details() {
         let mut server = Server::new_async().await;
-        let mock_response = serde_json::json!({
+        let ok = serde_json::json!({"code": 0, "desc": "Success"}).to_string();
+
+        let current = serde_json::json!({
             "code": 0,
             "desc": "Success",
             "payload": {
-                "code": 0,
-                "desc": "Success"
+                "name": "Front",
+                "effects": [{"id": 9, "name": "Old", "category": 1, "mode": 3}],
+                "calendar": [{"id": 2, "effectId": 9}]
             }
         });
-
-        let _m = server
-            .mock("POST", "/v1/oauth/resources/device/effect/delete")
-            .match_header("authorization", mockito::Matcher::Any)
-            .match_header("S-ClientId", mockito::Matcher::Any)
-            .match_header("S-Timestamp", mockito::Matcher::Any)
-            .match_body(mockito::Matcher::JsonString(
-                r#"{"deviceId":"test123","payload":{"id":1}}"#.to_string(),
-            ))
+        let snapshot: DeviceDetails = serde_json::from_value(serde_json::json!({
+            "name": "Front",
+            "effects": [{"id": 4, "name": "Candy Cane", "category": 2, "mode": 1,
+                         "speed": 100, "brightness": 200, "pixels": []}],
+            "calendar": [
+                {"id": 0, "effectId": 4, "startDate": {"month": 12, "day": 1},
+                 "endDate": {"month": 12, "day": 31}, "startTime": {"hours": 17, "minutes": 0},
+                 "endTime": {"hours": 23, "minutes": 0}},
+                {"id": 1, "effectId": 99}
+            ],
+            "combinedEffect": {"effectIds": [4], "interval": 60},
+            "overlayEffects": [{"overlayType": 1, "targetEffect": 4}]
+        }))
+        .unwrap();
+
+        let mut mocks = Vec::new();
+        let _details = server
+            .mock("POST", "/v1/oauth/resources/device/get")
             .with_status(200)
             .with_header("content-type", "application/json")
-            .with_body(mock_response.to_string())
+            .with_body(current.to_string())
             .create_async()
             .await;
+        for (path, body, response) in [
+            (
+                "/v1/oauth/resources/device/schedule/calendar/delete",
+                r#"{"deviceId":"test123","payload":{"id":2}}"#,
+                ok.clone(),
+            ),
+            (
+                "/v1/oauth/resources/device/effect/delete",
+                r#"{"deviceId":"test123","payload":{"id":9}}"#,
+                ok.clone(),
+            ),
+            (
+                "/v1/oauth/resources/device/effect/save",
+                r#"{"deviceId":"test123","payload":{"name":"Candy Cane","category":2,"mode":1,"speed":100,"brightness":200,"pixels":[]}}"#,
+                serde_json::json!({"code": 0, "desc": "Success", "payload": {"id": 12}})
+                    .to_string(),
+            ),
+            (
+                "/v1/oauth/resources/device/schedule/calendar/add",
+                r#"{"deviceId":"test123","payload":{"id":0,"effectId":12,"startDate":{"month":12,"day":1},"endDate":{"month":12,"day":31},"startTime":{"hours":17,"minutes":0},"endTime":{"hours":23,"minutes":0}}}"#,
+                ok.clone(),
+            ),
+            (
+                "/v1/oauth/resources/device/effect/combined/set",
+                r#"{"deviceId":"test123","payload":{"effectIds":[12],"interval":60}}"#,
+                ok.clone(),
+            ),
+            (
+                "/v1/oauth/resources/device/effect/overlay",
+                r#"{"deviceId":"test123","payload":{"overlayEffects":[{"overlayType":1,"targetEffect":12}]}}"#,
+                ok.clone(),
+            ),
+        ] {
+            let mock = server
+                .mock("POST", path)
+                .match_body(mockito::Matcher::JsonString(body.to_string()))
+                .with_status(200)
+                .with_header("content-type", "application/json")
+                .with_body(response)
+                .expect(1)
+                .create_async()
+                .await;
+            mocks.push(mock);
+        }
 
         let client = create_test_client(&server).await;
-        let result = client.delete_effect("test123", 1).await.unwrap();
+        let report = client.restore_details("test123", &snapshot).await.unwrap();
 
-        assert_eq!(result.code, 0);
-        assert_eq!(result.desc, "Success");
+        for mock in &mocks {
+            mock.assert_async().await;
+        }
+        assert_eq!(report.effect_ids.get(&4), Some(&12));
+        assert_eq!(report.calendar, 1);
+        assert_eq!(
+            report.skipped,
+            ["Calendar schedule 1 refers to missing effect 99"]
+        );
     }
 
     #[tokio::test]
-    async fn test_add_daily_schedule() {
+    async fn test_restore_invalid_snapshot_leaves_device_untouched() {
         let mut server = Server::new_async().await;
-        let mock_response = serde_json::json!({
+        let current = serde_json::json!({
             "code": 0,
             "desc": "Success",
             "payload": {
-                "code": 0,
-                "desc": "Success"
+                "name": "Front",
+                "effects": [{"id": 9, "name": "Old", "category": 1, "mode": 3}],
+                "daily": [{"id": 0, "enable": true, "effectId": 9, "repetition": 1}],
+                "calendar": [{"id": 2, "effectId": 9}],
+                "combinedEffect": {"effectIds": [9], "interval": 60},
+                "overlayEffects": [{"overlayType": 1, "targetEffect": 9}]
             }
         });
-
-        let _m = server.mock("POST", "/v1/oauth/resources/device/schedule/daily/add")
-            .match_header("authorization", mockito::Matcher::Any)
-            .match_header("S-ClientId", mockito::Matcher::Any)
-            .match_header("S-Timestamp", mockito::Matcher::Any)
-            .match_body(mockito::Matcher::JsonString(r#"{"deviceId":"test123","payload":{"id":-1,"enable":true,"effectId":1,"repetition":1,"startTime":{"hours":8,"minutes":0},"endTime":{"hours":20,"minutes":0}}}"#.to_string()))
+        server
+            .mock("POST", "/v1/oauth/resources/device/get")
             .with_status(200)
             .with_header("content-type", "application/json")
-            .with_body(mock_response.to_string())
+            .with_body(current.to_string())
+            .create_async()
+            .await;
+        // Nothing but the details may be requested
+        let untouched = server
+            .mock(
+                "POST",
+                mockito::Matcher::Regex("^/v1/oauth/resources/device/.+/".to_string()),
+            )
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::json!({"code": 0, "desc": "Success"}).to_string())
+            .expect(0)
             .create_async()
             .await;
 
         let client = create_test_client(&server).await;
-        let result = client
-            .add_daily_schedule("test123", 1, "08:00".to_string(), "20:00".to_string(), 1)
-            .await
-            .unwrap();
+        let bad_effect: DeviceDetails = serde_json::from_value(serde_json::json!({
+            "effects": [
+                {"id": 4, "name": "Candy Cane", "category": 2, "mode": 1, "pixels": []},
+                {"id": 5, "name": "Broken", "category": 2, "mode": 40}
+            ]
+        }))
+        .unwrap();
+        let result = client.restore_details("test123", &bad_effect).await;
+        assert!(matches!(
+            result,
+            Err(TrimlightError::ApiError { code: 400, message })
+                if message == "Invalid custom mode 40. Must be between 0 and 16"
+        ));
 
-        assert_eq!(result.code, 0);
-        assert_eq!(result.desc, "Success");
+        let bad_date: DeviceDetails = serde_json::from_value(serde_json::json!({
+            "effects": [{"id": 4, "name": "Candy Cane", "category": 2, "mode": 1}],
+            "calendar": [{"id": 0, "effectId": 4, "startDate": {"month": 2, "day": 30},
+                          "endDate": {"month": 3, "day": 1}}]
+        }))
+        .unwrap();
+        let result = client.restore_details("test123", &bad_date).await;
+        assert!(matches!(
+            result,
+            Err(TrimlightError::ApiError { code: 400, message }) if message == "Invalid date values"
+        ));
+
+        untouched.assert_async().await;
     }
 
     #[tokio::test]
-    async fn test_add_calendar_schedule() {
+    async fn test_clone_device_merge() {
         let mut server = Server::new_async().await;
-        let mock_response = serde_json::json!({
+        let ok = serde_json::json!({"code": 0, "desc": "Success"}).to_string();
+
+        let source = serde_json::json!({
             "code": 0,
             "desc": "Success",
             "payload": {
-                "code": 0,
-                "desc": "Success"
+                "name": "Front",
+                "effects": [
+                    {"id": 1, "name": "Warm White", "category": 1, "mode": 0, "speed": 100,
+                     "brightness": 200, "pixelLen": 30, "reverse": false},
+                    {"id": 2, "name": "Storm", "category": 1, "mode": 42, "speed": 100,
+                     "brightness": 100}
+                ],
+                "daily": [{"id": 0, "enable": true, "effectId": 2, "repetition": 1,
+                           "startTime": {"hours": 18, "minutes": 0},
+                           "endTime": {"hours": 23, "minutes": 0}}],
+                "calendar": [{"id": 5, "effectId": 1, "startDate": {"month": 12, "day": 1},
+                              "endDate": {"month": 12, "day": 31}}],
+                "overlayEffects": [{"overlayType": 0, "targetEffect": 2}]
+            }
+        });
+        let target = serde_json::json!({
+            "code": 0,
+            "desc": "Success",
+            "payload": {
+                "name": "Back",
+                "effects": [{"id": 7, "name": "Warm White", "category": 1, "mode": 0,
+                             "speed": 100, "brightness": 100, "pixelLen": 30, "reverse": false}],
+                "daily": [{"id": 1, "enable": true, "effectId": 7, "repetition": 2}],
+                "calendar": [{"id": 3, "effectId": 7, "startDate": {"month": 12, "day": 1},
+                              "endDate": {"month": 12, "day": 31}}],
+                "overlayEffects": [{"overlayType": 1, "targetEffect": 7}]
             }
         });
+        for (device_id, details) in [("front", &source), ("back", &target)] {
+            server
+                .mock("POST", "/v1/oauth/resources/device/get")
+                .match_body(mockito::Matcher::PartialJson(
+                    serde_json::json!({ "deviceId": device_id }),
+                ))
+                .with_status(200)
+                .with_header("content-type", "application/json")
+                .with_body(details.to_string())
+                .create_async()
+                .await;
+        }
 
-        let _m = server.mock("POST", "/v1/oauth/resources/device/schedule/calendar/add")
-            .match_header("authorization", mockito::Matcher::Any)
-            .match_header("S-ClientId", mockito::Matcher::Any)
-            .match_header("S-Timestamp", mockito::Matcher::Any)
-            .match_body(mockito::Matcher::JsonString(r#"{"deviceId":"test123","payload":{"id":0,"effectId":1,"startDate":{"month":12,"day":25},"endDate":{"month":12,"day":31},"startTime":{"hours":17,"minutes":0},"endTime":{"hours":23,"minutes":0}}}"#.to_string()))
-            .with_status(200)
-            .with_header("content-type", "application/json")
-            .with_body(mock_response.to_string())
+        let mut mocks = Vec::new();
+        for (path, body, response) in [
+            (
+                "/v1/oauth/resources/device/effect/save",
+                r#"{"deviceId":"back","payload":{"id":7,"name":"Warm White","category":1,"mode":0,"speed":100,"brightness":200,"pixelLen":30,"reverse":false}}"#,
+                ok.clone(),
+            ),
+            (
+                "/v1/oauth/resources/device/effect/save",
+                r#"{"deviceId":"back","payload":{"name":"Storm","category":1,"mode":42,"speed":100,"brightness":100,"pixelLen":30,"reverse":false}}"#,
+                serde_json::json!({"code": 0, "desc": "Success", "payload": {"id": 8}}).to_string(),
+            ),
+            (
+                "/v1/oauth/resources/device/schedule/daily/add",
+                r#"{"deviceId":"back","payload":{"id":-1,"enable":true,"effectId":8,"repetition":1,"startTime":{"hours":18,"minutes":0},"endTime":{"hours":23,"minutes":0}}}"#,
+                ok.clone(),
+            ),
+            (
+                "/v1/oauth/resources/device/effect/overlay",
+                r#"{"deviceId":"back","payload":{"overlayEffects":[{"overlayType":1,"targetEffect":7},{"overlayType":0,"targetEffect":8}]}}"#,
+                ok.clone(),
+            ),
+        ] {
+            let mock = server
+                .mock("POST", path)
+                .match_body(mockito::Matcher::JsonString(body.to_string()))
+                .with_status(200)
+                .with_header("content-type", "application/json")
+                .with_body(response)
+                .expect(1)
+                .create_async()
+                .await;
+            mocks.push(mock);
+        }
+        // The calendar schedule is already on the target
+        let calendar_add = server
+            .mock("POST", "/v1/oauth/resources/device/schedule/calendar/add")
+            .expect(0)
             .create_async()
             .await;
 
         let client = create_test_client(&server).await;
-        let result = client
-            .add_calendar_schedule(
-                "test123",
-                1,
-                "12-25".to_string(),
-                "12-31".to_string(),
-                "17:00".to_string(),
-                "23:00".to_string(),
-            )
+        let report = client
+            .clone_device("front", "back", CloneMode::Merge)
             .await
             .unwrap();
 
-        assert_eq!(result.code, 0);
-        assert_eq!(result.desc, "Success");
+        for mock in &mocks {
+            mock.assert_async().await;
+        }
+        calendar_add.assert_async().await;
+        assert_eq!(report.effect_ids.get(&1), Some(&7));
+        assert_eq!(report.effect_ids.get(&2), Some(&8));
+        assert_eq!((report.added_effects, report.updated_effects), (1, 1));
+        assert_eq!((report.daily, report.calendar), (1, 0));
     }
 
     #[tokio::test]
-    async fn test_delete_schedule() {
+    async fn test_ensure_effect() {
         let mut server = Server::new_async().await;
-        let mock_response = serde_json::json!({
+        let details = serde_json::json!({
             "code": 0,
             "desc": "Success",
             "payload": {
-                "code": 0,
-                "desc": "Success"
+                "effects": [{"id": 4, "name": "Candy Cane", "category": 2, "mode": 1,
+                             "speed": 100, "brightness": 100, "pixels": []}]
             }
         });
-
-        let _m = server
-            .mock("POST", "/v1/oauth/resources/device/schedule/daily/delete")
-            .match_header("authorization", mockito::Matcher::Any)
-            .match_header("S-ClientId", mockito::Matcher::Any)
-            .match_header("S-Timestamp", mockito::Matcher::Any)
-            .match_body(mockito::Matcher::JsonString(
-                r#"{"deviceId":"test123","payload":{"id":1}}"#.to_string(),
-            ))
+        let _details = server
+            .mock("POST", "/v1/oauth/resources/device/get")
             .with_status(200)
             .with_header("content-type", "application/json")
-            .with_body(mock_response.to_string())
+            .with_body(details.to_string())
+            .create_async()
+            .await;
+        let save = server.mock("POST", "/v1/oauth/resources/device/effect/save")
+            .match_body(mockito::Matcher::JsonString(r#"{"deviceId":"test123","payload":{"id":4,"name":"Candy Cane","category":2,"mode":1,"speed":100,"brightness":200,"pixels":[]}}"#.to_string()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"code":0,"desc":"Success"}"#)
+            .expect(1)
             .create_async()
             .await;
 
         let client = create_test_client(&server).await;
-        let result = client.delete_schedule("test123", 1, "daily").await.unwrap();
-
-        assert_eq!(result.code, 0);
-        assert_eq!(result.desc, "Success");
+        let mut effect = Effect {
+            name: "Candy Cane".to_string(),
+            category: 2,
+            mode: 1,
+            speed: 100,
+            brightness: 100,
+            pixels: Some(Vec::new()),
+            ..Default::default()
+        };
+        assert_eq!(
+            client.ensure_effect("test123", &effect).await.unwrap(),
+            EnsureAction::Unchanged { id: 4 }
+        );
+        effect.brightness = 200;
+        assert_eq!(
+            client.ensure_effect("test123", &effect).await.unwrap(),
+            EnsureAction::Updated { id: 4 }
+        );
+        save.assert_async().await;
     }
 
     #[tokio::test]
-    async fn test_toggle_schedule() {
+    async fn test_ensure_schedules() {
         let mut server = Server::new_async().await;
-        let mock_response = serde_json::json!({
+        let details = serde_json::json!({
             "code": 0,
             "desc": "Success",
             "payload": {
-                "code": 0,
-                "desc": "Success"
+                "daily": [{"id": 0, "enable": false, "effectId": 1, "repetition": 1,
+                           "startTime": {"hours": 18, "minutes": 0},
+                           "endTime": {"hours": 23, "minutes": 0}}],
+                "calendar": [{"id": 2, "effectId": 4, "startDate": {"month": 12, "day": 1},
+                              "endDate": {"month": 12, "day": 31},
+                              "startTime": {"hours": 17, "minutes": 0},
+                              "endTime": {"hours": 23, "minutes": 0}}]
             }
         });
-
-        let _m = server
+        let _details = server
+            .mock("POST", "/v1/oauth/resources/device/get")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(details.to_string())
+            .create_async()
+            .await;
+        let ok = r#"{"code":0,"desc":"Success"}"#;
+        let enable = server
             .mock("POST", "/v1/oauth/resources/device/schedule/daily/update")
-            .match_header("authorization", mockito::Matcher::Any)
-            .match_header("S-ClientId", mockito::Matcher::Any)
-            .match_header("S-Timestamp", mockito::Matcher::Any)
             .match_body(mockito::Matcher::JsonString(
-                r#"{"deviceId":"test123","payload":{"id":1,"enable":true}}"#.to_string(),
+                r#"{"deviceId":"test123","payload":{"id":0,"enable":true}}"#.to_string(),
             ))
             .with_status(200)
             .with_header("content-type", "application/json")
-            .with_body(mock_response.to_string())
+            .with_body(ok)
+            .expect(1)
+            .create_async()
+            .await;
+        let add_calendar = server
+            .mock("POST", "/v1/oauth/resources/device/schedule/calendar/add")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(ok)
+            .expect(1)
             .create_async()
             .await;
 
         let client = create_test_client(&server).await;
-        let result = client.toggle_schedule("test123", 1, true).await.unwrap();
+        // Same effect and times, but disabled
+        let action = client
+            .ensure_daily_schedule("test123", 1, "18:00".into(), "23:00".into(), 1)
+            .await
+            .unwrap();
+        assert_eq!(action, EnsureAction::Updated { id: 0 });
+        enable.assert_async().await;
 
-        assert_eq!(result.code, 0);
-        assert_eq!(result.desc, "Success");
+        let action = client
+            .ensure_calendar_schedule(
+                "test123",
+                4,
+                "12-01".into(),
+                "12-31".into(),
+                "17:00".into(),
+                "23:00".into(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(action, EnsureAction::Unchanged { id: 2 });
+
+        // Different dates, so a new schedule
+        let action = client
+            .ensure_calendar_schedule(
+                "test123",
+                4,
+                "11-27".into(),
+                "11-30".into(),
+                "17:00".into(),
+                "23:00".into(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(action, EnsureAction::Created { id: None });
+        add_calendar.assert_async().await;
     }
 
     #[tokio::test]
-    async fn test_modify_schedule() {
+    async fn test_modify_calendar_schedule() {
         let mut server = Server::new_async().await;
 
-        // Mock for get_device_schedules (which calls get_device_details)
         let details_response = serde_json::json!({
             "code": 0,
             "desc": "Success",
             "payload": {
-                "daily": [{
-                    "id": 1,
-                    "enable": true,
-                    "effectId": 1,
-                    "repetition": 1,
-                    "startTime": {
-                        "hours": 8,
-                        "minutes": 0
-                    },
-                    "endTime": {
-                        "hours": 20,
-                        "minutes": 0
-                    }
-                }],
-                "calendar": []
+                "daily": [],
+                "calendar": [{
+                    "id": 3,
+                    "effectId": 4,
+                    "startDate": {"month": 12, "day": 1},
+                    "endDate": {"month": 12, "day": 31},
+                    "startTime": {"hours": 17, "minutes": 0},
+                    "endTime": {"hours": 23, "minutes": 0}
+                }]
             }
         });
 
@@ -1275,7 +3005,6 @@ mod tests {
             .create_async()
             .await;
 
-        // Mock for modify_schedule
         let update_response = serde_json::json!({
             "code": 0,
             "desc": "Success",
@@ -1285,8 +3014,9 @@ mod tests {
             }
         });
 
-        let _m2 = server.mock("POST", "/v1/oauth/resources/device/schedule/daily/update")
-            .match_body(mockito::Matcher::JsonString(r#"{"deviceId":"test123","payload":{"id":1,"enable":true,"effectId":2,"startTime":{"hours":9,"minutes":0},"endTime":{"hours":21,"minutes":0},"repetition":1}}"#.to_string()))
+        // Only the start date and end time change
+        let _m2 = server.mock("POST", "/v1/oauth/resources/device/schedule/calendar/update")
+            .match_body(mockito::Matcher::JsonString(r#"{"deviceId":"test123","payload":{"id":3,"effectId":4,"startDate":{"month":11,"day":27},"endDate":{"month":12,"day":31},"startTime":{"hours":17,"minutes":0},"endTime":{"hours":22,"minutes":30}}}"#.to_string()))
             .with_status(200)
             .with_header("content-type", "application/json")
             .with_body(update_response.to_string())
@@ -1297,18 +3027,54 @@ mod tests {
         let result = client
             .modify_schedule(
                 "test123",
-                1,
-                "daily",
-                Some(2),
-                Some("09:00".to_string()),
-                "21:00".to_string(),
+                3,
+                "calendar",
+                None,
+                Some("11-27".to_string()),
+                None,
+                None,
+                Some("22:30".to_string()),
                 None,
             )
             .await
             .unwrap();
-
         assert_eq!(result.code, 0);
-        assert_eq!(result.desc, "Success");
+
+        let missing = client
+            .modify_schedule(
+                "test123",
+                9,
+                "calendar",
+                Some(1),
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await;
+        assert!(matches!(
+            missing,
+            Err(TrimlightError::ApiError { code: 404, .. })
+        ));
+
+        let repetition = client
+            .modify_schedule(
+                "test123",
+                3,
+                "calendar",
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some(1),
+            )
+            .await;
+        assert!(matches!(
+            repetition,
+            Err(TrimlightError::ApiError { code: 400, .. })
+        ));
     }
 
     #[tokio::test]
@@ -1353,10 +3119,11 @@ mod tests {
         let client = create_test_client(&server).await;
         let result = client.check_schedule_conflicts("test123").await.unwrap();
 
-        assert_eq!(result.code, 1);
-        assert!(result
-            .desc
-            .contains("Daily schedules 1 and 2 have overlapping times"));
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].first.id, 1);
+        assert_eq!(result[0].second.id, 2);
+        assert_eq!(result[0].start_time, ScheduleTime::new(10, 0).unwrap());
+        assert_eq!(result[0].end_time, ScheduleTime::new(12, 0).unwrap());
     }
 
     #[tokio::test]
@@ -1510,18 +3277,21 @@ mod tests {
                 count: 1,
                 color: 0xFF0000, // Red
                 disable: false,
+                ..Default::default()
             },
             Pixel {
                 index: 1,
                 count: 1,
                 color: 0x00FF00, // Green
                 disable: false,
+                ..Default::default()
             },
             Pixel {
                 index: 2,
                 count: 1,
                 color: 0x0000FF, // Blue
                 disable: false,
+                ..Default::default()
             },
         ];
 
@@ -1611,12 +3381,14 @@ mod tests {
                 count: 1,
                 color: 0xFF0000, // Red
                 disable: false,
+                ..Default::default()
             },
             Pixel {
                 index: 1,
                 count: 1,
                 color: 0x00FF00, // Green
                 disable: false,
+                ..Default::default()
             },
         ];
 
@@ -1655,6 +3427,20 @@ mod tests {
     #[tokio::test]
     async fn test_update_builtin_effect() {
         let mut server = Server::new_async().await;
+        let _details = mock_device_effects(
+            &mut server,
+            serde_json::json!([{
+                "id": 1,
+                "name": "Old Effect",
+                "category": 1,
+                "mode": 0,
+                "speed": 100,
+                "brightness": 100,
+                "pixelLen": 30,
+                "reverse": false
+            }]),
+        )
+        .await;
         let mock_response = serde_json::json!({
             "code": 0,
             "desc": "Success",
@@ -1697,6 +3483,19 @@ mod tests {
     #[tokio::test]
     async fn test_update_custom_effect() {
         let mut server = Server::new_async().await;
+        let _details = mock_device_effects(
+            &mut server,
+            serde_json::json!([{
+                "id": 1,
+                "name": "Old Effect",
+                "category": 2,
+                "mode": 0,
+                "speed": 100,
+                "brightness": 100,
+                "pixels": []
+            }]),
+        )
+        .await;
         let mock_response = serde_json::json!({
             "code": 0,
             "desc": "Success",
@@ -1712,12 +3511,14 @@ mod tests {
                 count: 1,
                 color: 0xFF0000, // Red
                 disable: false,
+                ..Default::default()
             },
             Pixel {
                 index: 1,
                 count: 2,
                 color: 0x00FF00, // Green
                 disable: false,
+                ..Default::default()
             },
         ];
 
@@ -1749,4 +3550,61 @@ mod tests {
         assert_eq!(result.code, 0);
         assert_eq!(result.desc, "Success");
     }
+
+    #[tokio::test]
+    async fn test_update_effect_partial() {
+        let mut server = Server::new_async().await;
+        let _details = mock_device_effects(
+            &mut server,
+            serde_json::json!([{
+                "id": 3,
+                "name": "Warm White",
+                "category": 1,
+                "mode": 164,
+                "speed": 120,
+                "brightness": 200,
+                "pixelLen": 45,
+                "reverse": true
+            }]),
+        )
+        .await;
+
+        let mock_response = serde_json::json!({
+            "code": 0,
+            "desc": "Success"
+        });
+
+        // Only the brightness changes, everything else is sent as stored on the device
+        let _m = server.mock("POST", "/v1/oauth/resources/device/effect/save")
+            .match_body(mockito::Matcher::JsonString(r#"{"deviceId":"test123","payload":{"id":3,"name":"Warm White","category":1,"mode":164,"speed":120,"brightness":50,"pixelLen":45,"reverse":true}}"#.to_string()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(mock_response.to_string())
+            .create_async()
+            .await;
+
+        let client = create_test_client(&server).await;
+        let result = client
+            .update_builtin_effect("test123", 3, None, None, None, Some(50), None, None)
+            .await
+            .unwrap();
+
+        assert_eq!(result.code, 0);
+    }
+
+    #[tokio::test]
+    async fn test_update_effect_not_found() {
+        let mut server = Server::new_async().await;
+        let _details = mock_device_effects(&mut server, serde_json::json!([])).await;
+
+        let client = create_test_client(&server).await;
+        let result = client
+            .update_custom_effect("test123", 9, None, None, None, Some(50), None)
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(TrimlightError::ApiError { code: 404, message }) if message == "Effect 9 not found"
+        ));
+    }
 }
@@ -0,0 +1,1258 @@
+//! Declarative device configuration.
+//!
+//! A [`DeviceConfig`] describes the desired state of a device in a TOML or
+//! YAML file: its name, color order, ports, saved effects, schedules, combined
+//! effect and overlays. Effects are referred to by name so the file does not
+//! depend on the IDs a device assigns. [`plan_config`] compares a config with
+//! the device and lists the changes, which
+//! [`TrimlightClient::apply_config`](crate::TrimlightClient::apply_config)
+//! then carries out.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use crate::error::{invalid, TrimlightError};
+use crate::models::{
+    CalendarSchedule, DailySchedule, DeviceDetails, Effect, EffectKind, Pixel, ScheduleDate,
+    ScheduleTime, DAILY_SCHEDULE_SLOTS,
+};
+use crate::utils;
+
+/// Desired state of a device
+///
+/// Sections left out of the file are not managed, so a config with only
+/// effects leaves schedules alone. A section that is present but empty
+/// removes everything in it from the device.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_order: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ports: Option<Vec<PortConfig>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub effects: Vec<EffectConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily: Option<Vec<DailyConfig>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub calendar: Option<Vec<CalendarConfig>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub combined: Option<CombinedConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overlays: Option<Vec<OverlayConfig>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PortConfig {
+    pub start: i32,
+    pub end: i32,
+}
+
+/// A saved effect, with exactly one of `built_in` or `pattern` set
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EffectConfig {
+    pub name: String,
+    /// Built-in effect mode (0-179)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub built_in: Option<i32>,
+    /// Custom effect pattern (0-16)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<i32>,
+    #[serde(default = "default_level")]
+    pub speed: i32,
+    #[serde(default = "default_level")]
+    pub brightness: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pixel_len: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reverse: Option<bool>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pixels: Vec<PixelConfig>,
+}
+
+/// A segment of a custom effect
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PixelConfig {
+    /// Color as `#RRGGBB`
+    pub color: String,
+    #[serde(default = "default_count")]
+    pub count: i32,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub disable: bool,
+}
+
+/// How often a daily schedule runs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Repetition {
+    Today,
+    #[default]
+    Everyday,
+    Weekdays,
+    Weekend,
+}
+
+impl Repetition {
+    /// Value used by the API
+    pub fn value(&self) -> i32 {
+        match self {
+            Repetition::Today => 0,
+            Repetition::Everyday => 1,
+            Repetition::Weekdays => 2,
+            Repetition::Weekend => 3,
+        }
+    }
+
+    pub fn from_value(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(Repetition::Today),
+            1 => Some(Repetition::Everyday),
+            2 => Some(Repetition::Weekdays),
+            3 => Some(Repetition::Weekend),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Repetition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            Repetition::Today => "today",
+            Repetition::Everyday => "everyday",
+            Repetition::Weekdays => "weekdays",
+            Repetition::Weekend => "weekend",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DailyConfig {
+    /// Name of the effect to run
+    pub effect: String,
+    /// Start time (HH:MM)
+    pub start: String,
+    /// End time (HH:MM)
+    pub end: String,
+    #[serde(default)]
+    pub repeat: Repetition,
+    #[serde(default = "default_enabled", skip_serializing_if = "is_true")]
+    pub enabled: bool,
+}
+
+impl fmt::Display for DailyConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{} {}: {}",
+            self.start, self.end, self.repeat, self.effect
+        )?;
+        if !self.enabled {
+            write!(f, " (disabled)")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CalendarConfig {
+    /// Name of the effect to run
+    pub effect: String,
+    /// First day (MM-DD)
+    pub start_date: String,
+    /// Last day (MM-DD)
+    pub end_date: String,
+    /// Start time (HH:MM)
+    pub start: String,
+    /// End time (HH:MM)
+    pub end: String,
+}
+
+impl fmt::Display for CalendarConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} to {} {}-{}: {}",
+            self.start_date, self.end_date, self.start, self.end, self.effect
+        )
+    }
+}
+
+/// Effects to cycle through; an empty list clears the combined effect
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CombinedConfig {
+    pub effects: Vec<String>,
+    /// Seconds per effect
+    #[serde(default)]
+    pub interval: i32,
+}
+
+impl fmt::Display for CombinedConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.effects.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{} every {}s", self.effects.join(", "), self.interval)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OverlayType {
+    Lightning,
+    Snow,
+}
+
+impl OverlayType {
+    /// Value used by the API
+    pub fn value(&self) -> i32 {
+        match self {
+            OverlayType::Lightning => 0,
+            OverlayType::Snow => 1,
+        }
+    }
+
+    pub fn from_value(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(OverlayType::Lightning),
+            1 => Some(OverlayType::Snow),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OverlayConfig {
+    #[serde(rename = "type")]
+    pub overlay_type: OverlayType,
+    /// Name of the effect the overlay applies to
+    pub effect: String,
+}
+
+impl fmt::Display for OverlayConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let overlay = match self.overlay_type {
+            OverlayType::Lightning => "lightning",
+            OverlayType::Snow => "snow",
+        };
+        write!(f, "{} on {}", overlay, self.effect)
+    }
+}
+
+fn default_level() -> i32 {
+    100
+}
+
+fn default_count() -> i32 {
+    1
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+impl DeviceConfig {
+    pub fn from_toml(text: &str) -> Result<Self, TrimlightError> {
+        toml::from_str(text).map_err(|e| invalid(format!("Invalid config: {}", e)))
+    }
+
+    pub fn from_yaml(text: &str) -> Result<Self, TrimlightError> {
+        serde_yaml::from_str(text).map_err(|e| invalid(format!("Invalid config: {}", e)))
+    }
+
+    /// Read a config file, choosing the format from the extension
+    /// (`.toml`, `.yaml` or `.yml`)
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TrimlightError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|source| TrimlightError::IoError {
+            context: format!("Can't read config {}", path.display()),
+            source,
+        })?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Self::from_toml(&text),
+            Some("yaml") | Some("yml") => Self::from_yaml(&text),
+            _ => Err(invalid(format!(
+                "Unknown config format for {}. Use a .toml, .yaml or .yml file",
+                path.display()
+            ))),
+        }
+    }
+
+    pub fn to_toml(&self) -> Result<String, TrimlightError> {
+        toml::to_string(self).map_err(|e| invalid(format!("Can't write config: {}", e)))
+    }
+
+    pub fn to_yaml(&self) -> Result<String, TrimlightError> {
+        serde_yaml::to_string(self).map_err(|e| invalid(format!("Can't write config: {}", e)))
+    }
+
+    /// Describe everything on a device, as a starting point for a config file
+    pub fn from_details(details: &DeviceDetails) -> Self {
+        let names = EffectNames::new(details);
+        Self {
+            name: Some(details.name.clone()),
+            color_order: Some(details.color_order),
+            ports: Some(
+                details
+                    .ports
+                    .iter()
+                    .map(|p| PortConfig {
+                        start: p.start,
+                        end: p.end,
+                    })
+                    .collect(),
+            ),
+            effects: details.effects.iter().map(EffectConfig::from).collect(),
+            daily: Some(details.daily.iter().map(|s| names.daily(s)).collect()),
+            calendar: Some(details.calendar.iter().map(|s| names.calendar(s)).collect()),
+            combined: Some(names.combined(details)),
+            overlays: Some(names.overlays(details)),
+        }
+    }
+}
+
+impl EffectConfig {
+    /// The effect as the API represents it, without an ID
+    pub fn to_effect(&self) -> Result<Effect, TrimlightError> {
+        let kind = match (self.built_in, self.pattern) {
+            (Some(mode), None) => EffectKind::Builtin {
+                mode: mode.try_into()?,
+                pixel_len: self.pixel_len.unwrap_or(Effect::DEFAULT_PIXEL_LEN),
+                reverse: self.reverse.unwrap_or(false),
+            },
+            (None, Some(mode)) => EffectKind::Custom {
+                mode: mode.try_into()?,
+                pixels: self
+                    .pixels
+                    .iter()
+                    .enumerate()
+                    .map(|(index, pixel)| pixel.to_pixel(index as i32))
+                    .collect::<Result<_, _>>()?,
+            },
+            _ => {
+                return Err(invalid(format!(
+                    "Effect '{}' must set exactly one of built_in or pattern",
+                    self.name
+                )))
+            }
+        };
+        if !(0..=255).contains(&self.speed) || !(0..=255).contains(&self.brightness) {
+            return Err(invalid(format!(
+                "Effect '{}' speed and brightness must be between 0 and 255",
+                self.name
+            )));
+        }
+        let mut effect = Effect {
+            name: self.name.clone(),
+            speed: self.speed,
+            brightness: self.brightness,
+            ..Default::default()
+        };
+        effect.set_kind(kind);
+        Ok(effect)
+    }
+}
+
+impl From<&Effect> for EffectConfig {
+    fn from(effect: &Effect) -> Self {
+        let mut config = EffectConfig {
+            name: effect.name.clone(),
+            built_in: None,
+            pattern: None,
+            speed: effect.speed,
+            brightness: effect.brightness,
+            pixel_len: None,
+            reverse: None,
+            pixels: Vec::new(),
+        };
+        match effect.kind() {
+            Ok(EffectKind::Builtin {
+                mode,
+                pixel_len,
+                reverse,
+            }) => {
+                config.built_in = Some(mode.value());
+                config.pixel_len = Some(pixel_len);
+                config.reverse = Some(reverse);
+            }
+            Ok(EffectKind::Custom { mode, pixels }) => {
+                config.pattern = Some(mode.value());
+                config.pixels = pixels.iter().map(PixelConfig::from).collect();
+            }
+            // Keep the raw mode so the effect still shows up in the file
+            Err(_) => config.built_in = Some(effect.mode),
+        }
+        config
+    }
+}
+
+impl PixelConfig {
+    fn to_pixel(&self, index: i32) -> Result<Pixel, TrimlightError> {
+        let color = self
+            .color
+            .strip_prefix('#')
+            .filter(|hex| hex.len() == 6)
+            .and_then(|hex| i32::from_str_radix(hex, 16).ok())
+            .ok_or_else(|| {
+                invalid(format!(
+                    "Invalid color '{}'. Use #RRGGBB format",
+                    self.color
+                ))
+            })?;
+        Ok(Pixel {
+            index,
+            count: self.count,
+            color,
+            disable: self.disable,
+            ..Default::default()
+        })
+    }
+}
+
+impl From<&Pixel> for PixelConfig {
+    fn from(pixel: &Pixel) -> Self {
+        Self {
+            color: format!("#{:06x}", pixel.color & 0xffffff),
+            count: pixel.count,
+            disable: pixel.disable,
+        }
+    }
+}
+
+/// A field that differs between the device and the config
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub from: String,
+    pub to: String,
+}
+
+impl fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} -> {}", self.field, self.from, self.to)
+    }
+}
+
+/// Fields of `current` that would change to become `wanted`, ignoring the ID
+/// and unknown fields
+pub fn effect_field_changes(current: &Effect, wanted: &Effect) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    let mut field = |field: &str, from: String, to: String| {
+        if from != to {
+            changes.push(FieldChange {
+                field: field.to_string(),
+                from,
+                to,
+            });
+        }
+    };
+    let category = |effect: &Effect| match effect.kind() {
+        Ok(EffectKind::Builtin { .. }) => "built-in".to_string(),
+        Ok(EffectKind::Custom { .. }) => "custom".to_string(),
+        Err(_) => format!("category {}", effect.category),
+    };
+
+    field("name", current.name.clone(), wanted.name.clone());
+    field("category", category(current), category(wanted));
+    field("mode", current.mode.to_string(), wanted.mode.to_string());
+    field("speed", current.speed.to_string(), wanted.speed.to_string());
+    field(
+        "brightness",
+        current.brightness.to_string(),
+        wanted.brightness.to_string(),
+    );
+    match (current.kind(), wanted.kind()) {
+        (
+            Ok(EffectKind::Builtin {
+                pixel_len: from_len,
+                reverse: from_reverse,
+                ..
+            }),
+            Ok(EffectKind::Builtin {
+                pixel_len: to_len,
+                reverse: to_reverse,
+                ..
+            }),
+        ) => {
+            field("pixel_len", from_len.to_string(), to_len.to_string());
+            field("reverse", from_reverse.to_string(), to_reverse.to_string());
+        }
+        (
+            Ok(EffectKind::Custom { pixels: from, .. }),
+            Ok(EffectKind::Custom { pixels: to, .. }),
+        ) => {
+            let describe = |pixels: &[Pixel]| {
+                pixels
+                    .iter()
+                    .map(|p| {
+                        let disabled = if p.disable { " off" } else { "" };
+                        format!("#{:06x}x{}{}", p.color & 0xffffff, p.count, disabled)
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            };
+            field("pixels", describe(&from), describe(&to));
+        }
+        _ => {}
+    }
+    changes
+}
+
+/// One change needed to bring a device in line with its config
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum ConfigChange {
+    SetName {
+        from: String,
+        to: String,
+    },
+    SetColorOrder {
+        from: i32,
+        to: i32,
+    },
+    SetPorts {
+        from: Vec<PortConfig>,
+        to: Vec<PortConfig>,
+    },
+    AddEffect {
+        effect: Effect,
+    },
+    /// `effect` is the saved effect with the new settings applied
+    UpdateEffect {
+        effect: Effect,
+        fields: Vec<FieldChange>,
+    },
+    DeleteEffect {
+        id: i32,
+        name: String,
+    },
+    AddDaily {
+        schedule: DailyConfig,
+    },
+    ModifyDaily {
+        id: i32,
+        from: DailyConfig,
+        to: DailyConfig,
+    },
+    DeleteDaily {
+        id: i32,
+        schedule: DailyConfig,
+    },
+    AddCalendar {
+        schedule: CalendarConfig,
+    },
+    ModifyCalendar {
+        id: i32,
+        from: CalendarConfig,
+        to: CalendarConfig,
+    },
+    DeleteCalendar {
+        id: i32,
+        schedule: CalendarConfig,
+    },
+    SetCombined {
+        from: CombinedConfig,
+        to: CombinedConfig,
+    },
+    SetOverlays {
+        from: Vec<OverlayConfig>,
+        to: Vec<OverlayConfig>,
+    },
+}
+
+impl ConfigChange {
+    /// `+` for additions, `-` for removals and `~` for changes
+    pub fn symbol(&self) -> char {
+        match self {
+            ConfigChange::AddEffect { .. }
+            | ConfigChange::AddDaily { .. }
+            | ConfigChange::AddCalendar { .. } => '+',
+            ConfigChange::DeleteEffect { .. }
+            | ConfigChange::DeleteDaily { .. }
+            | ConfigChange::DeleteCalendar { .. } => '-',
+            _ => '~',
+        }
+    }
+}
+
+impl fmt::Display for ConfigChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |items: &[String]| {
+            if items.is_empty() {
+                "none".to_string()
+            } else {
+                items.join(", ")
+            }
+        };
+        match self {
+            ConfigChange::SetName { from, to } => write!(f, "name: {:?} -> {:?}", from, to),
+            ConfigChange::SetColorOrder { from, to } => {
+                write!(f, "color order: {} -> {}", from, to)
+            }
+            ConfigChange::SetPorts { from, to } => {
+                let ports = |ports: &[PortConfig]| {
+                    list(
+                        &ports
+                            .iter()
+                            .map(|p| format!("{}-{}", p.start, p.end))
+                            .collect::<Vec<_>>(),
+                    )
+                };
+                write!(f, "ports: {} -> {}", ports(from), ports(to))
+            }
+            ConfigChange::AddEffect { effect } => write!(f, "effect {:?}", effect.name),
+            ConfigChange::UpdateEffect { effect, fields } => write!(
+                f,
+                "effect {:?} (id {}): {}",
+                effect.name,
+                effect.id,
+                list(&fields.iter().map(|c| c.to_string()).collect::<Vec<_>>())
+            ),
+            ConfigChange::DeleteEffect { id, name } => write!(f, "effect {:?} (id {})", name, id),
+            ConfigChange::AddDaily { schedule } => write!(f, "daily {}", schedule),
+            ConfigChange::ModifyDaily { id, from, to } => {
+                write!(f, "daily {}: {} -> {}", id, from, to)
+            }
+            ConfigChange::DeleteDaily { id, schedule } => {
+                write!(f, "daily {}: {}", id, schedule)
+            }
+            ConfigChange::AddCalendar { schedule } => write!(f, "calendar {}", schedule),
+            ConfigChange::ModifyCalendar { id, from, to } => {
+                write!(f, "calendar {}: {} -> {}", id, from, to)
+            }
+            ConfigChange::DeleteCalendar { id, schedule } => {
+                write!(f, "calendar {}: {}", id, schedule)
+            }
+            ConfigChange::SetCombined { from, to } => {
+                write!(f, "combined effect: {} -> {}", from, to)
+            }
+            ConfigChange::SetOverlays { from, to } => {
+                let overlays = |overlays: &[OverlayConfig]| {
+                    list(&overlays.iter().map(|o| o.to_string()).collect::<Vec<_>>())
+                };
+                write!(f, "overlays: {} -> {}", overlays(from), overlays(to))
+            }
+        }
+    }
+}
+
+/// Changes planned by [`plan_config`], in the order they are applied
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ConfigPlan {
+    pub changes: Vec<ConfigChange>,
+}
+
+impl ConfigPlan {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// Effect names on a device, for describing references by name
+pub(crate) struct EffectNames {
+    names: HashMap<i32, String>,
+}
+
+impl EffectNames {
+    pub(crate) fn new(details: &DeviceDetails) -> Self {
+        Self {
+            names: details
+                .effects
+                .iter()
+                .map(|e| (e.id, e.name.clone()))
+                .collect(),
+        }
+    }
+
+    /// Name of the effect, or `#ID` when the device has no such effect
+    ///
+    /// Configs accept `#ID` wherever an effect name is expected.
+    pub(crate) fn name(&self, id: i32) -> String {
+        self.names
+            .get(&id)
+            .cloned()
+            .unwrap_or_else(|| format!("#{}", id))
+    }
+
+    pub(crate) fn daily(&self, schedule: &DailySchedule) -> DailyConfig {
+        DailyConfig {
+            effect: self.name(schedule.effect_id),
+            start: format_time(&schedule.start_time),
+            end: format_time(&schedule.end_time),
+            repeat: Repetition::from_value(schedule.repetition).unwrap_or_default(),
+            enabled: schedule.enable,
+        }
+    }
+
+    pub(crate) fn calendar(&self, schedule: &CalendarSchedule) -> CalendarConfig {
+        CalendarConfig {
+            effect: self.name(schedule.effect_id),
+            start_date: format_date(&schedule.start_date),
+            end_date: format_date(&schedule.end_date),
+            start: format_time(&schedule.start_time),
+            end: format_time(&schedule.end_time),
+        }
+    }
+
+    pub(crate) fn combined(&self, details: &DeviceDetails) -> CombinedConfig {
+        details
+            .combined_effect
+            .as_ref()
+            .filter(|c| !c.effect_ids.is_empty())
+            .map(|c| CombinedConfig {
+                effects: c.effect_ids.iter().map(|id| self.name(*id)).collect(),
+                interval: c.interval,
+            })
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn overlays(&self, details: &DeviceDetails) -> Vec<OverlayConfig> {
+        let mut overlays: Vec<OverlayConfig> = details
+            .overlay_effects
+            .iter()
+            .filter_map(|o| {
+                Some(OverlayConfig {
+                    overlay_type: OverlayType::from_value(o.overlay_type)?,
+                    effect: self.name(o.target_effect),
+                })
+            })
+            .collect();
+        overlays.sort();
+        overlays
+    }
+}
+
+fn format_time(time: &ScheduleTime) -> String {
+    format!("{:02}:{:02}", time.hours, time.minutes)
+}
+
+fn format_date(date: &ScheduleDate) -> String {
+    format!("{:02}-{:02}", date.month, date.day)
+}
+
+fn normalize_time(time: &str) -> Result<String, TrimlightError> {
+    let (hours, minutes) = utils::parse_time(time)?;
+    Ok(format_time(&ScheduleTime::new(hours, minutes)?))
+}
+
+fn normalize_date(date: &str) -> Result<String, TrimlightError> {
+    let (month, day) = utils::parse_date(date)?;
+    Ok(format!("{:02}-{:02}", month, day))
+}
+
+/// Work out the changes that bring a device in line with `config`
+///
+/// Effects are matched by name. Effects on the device that are not in the
+/// config are kept unless `prune` is set. Schedules are matched by content;
+/// schedules that differ are modified in place where possible so the daily
+/// slots are reused. Fails when the config is invalid or refers to an effect
+/// that is neither in the config nor on the device.
+pub fn plan_config(
+    config: &DeviceConfig,
+    details: &DeviceDetails,
+    prune: bool,
+) -> Result<ConfigPlan, TrimlightError> {
+    let names = EffectNames::new(details);
+    let mut changes = Vec::new();
+
+    if let Some(name) = &config.name {
+        if *name != details.name {
+            changes.push(ConfigChange::SetName {
+                from: details.name.clone(),
+                to: name.clone(),
+            });
+        }
+    }
+    if let Some(color_order) = config.color_order {
+        if !(0..=5).contains(&color_order) {
+            return Err(invalid(format!(
+                "Invalid color order {}. Must be between 0 (RGB) and 5 (BGR)",
+                color_order
+            )));
+        }
+        if color_order != details.color_order {
+            changes.push(ConfigChange::SetColorOrder {
+                from: details.color_order,
+                to: color_order,
+            });
+        }
+    }
+    if let Some(ports) = &config.ports {
+        let current: Vec<PortConfig> = details
+            .ports
+            .iter()
+            .map(|p| PortConfig {
+                start: p.start,
+                end: p.end,
+            })
+            .collect();
+        if let Some(port) = ports.iter().find(|p| p.start < 0 || p.end < p.start) {
+            return Err(invalid(format!(
+                "Invalid port {}-{}. The end must not be before the start",
+                port.start, port.end
+            )));
+        }
+        if *ports != current {
+            changes.push(ConfigChange::SetPorts {
+                from: current,
+                to: ports.clone(),
+            });
+        }
+    }
+
+    // Effects
+    let mut defined: Vec<&str> = Vec::new();
+    for effect_config in &config.effects {
+        if defined.contains(&effect_config.name.as_str()) {
+            return Err(invalid(format!(
+                "Effect '{}' is defined more than once",
+                effect_config.name
+            )));
+        }
+        defined.push(&effect_config.name);
+
+        let wanted = effect_config.to_effect()?;
+        match details.effects.iter().find(|e| e.name == wanted.name) {
+            None => changes.push(ConfigChange::AddEffect { effect: wanted }),
+            Some(current) if current.has_same_settings(&wanted) => {}
+            Some(current) => {
+                let mut effect = current.clone();
+                effect.speed = wanted.speed;
+                effect.brightness = wanted.brightness;
+                effect.set_kind(wanted.kind()?);
+                changes.push(ConfigChange::UpdateEffect {
+                    fields: effect_field_changes(current, &effect),
+                    effect,
+                });
+            }
+        }
+    }
+    let mut deleted_effects = Vec::new();
+    if prune {
+        for effect in &details.effects {
+            if !defined.contains(&effect.name.as_str()) {
+                deleted_effects.push(ConfigChange::DeleteEffect {
+                    id: effect.id,
+                    name: effect.name.clone(),
+                });
+            }
+        }
+    }
+    let known = |name: &str| -> Result<(), TrimlightError> {
+        let on_device = !prune && details.effects.iter().any(|e| e.name == name);
+        let by_id = name
+            .strip_prefix('#')
+            .is_some_and(|id| id.parse::<i32>().is_ok());
+        if defined.contains(&name) || on_device || by_id {
+            Ok(())
+        } else {
+            Err(invalid(format!(
+                "Effect '{}' is not defined in the config{}",
+                name,
+                if prune { "" } else { " or saved on the device" }
+            )))
+        }
+    };
+
+    // Daily schedules
+    if let Some(daily) = &config.daily {
+        if daily.len() > DAILY_SCHEDULE_SLOTS {
+            return Err(invalid(format!(
+                "A device holds at most {} daily schedules",
+                DAILY_SCHEDULE_SLOTS
+            )));
+        }
+        let mut wanted = Vec::new();
+        for schedule in daily {
+            known(&schedule.effect)?;
+            wanted.push(DailyConfig {
+                start: normalize_time(&schedule.start)?,
+                end: normalize_time(&schedule.end)?,
+                ..schedule.clone()
+            });
+        }
+        let current: Vec<(i32, DailyConfig)> = details
+            .daily
+            .iter()
+            .map(|s| (s.id, names.daily(s)))
+            .collect();
+        for (id, from, to) in match_schedules(current, wanted, |a, b| a.effect == b.effect) {
+            changes.push(match (from, to) {
+                (Some(from), Some(to)) => ConfigChange::ModifyDaily { id, from, to },
+                (Some(schedule), None) => ConfigChange::DeleteDaily { id, schedule },
+                (None, Some(schedule)) => ConfigChange::AddDaily { schedule },
+                (None, None) => continue,
+            });
+        }
+    }
+
+    // Calendar schedules
+    if let Some(calendar) = &config.calendar {
+        let mut wanted = Vec::new();
+        for schedule in calendar {
+            known(&schedule.effect)?;
+            wanted.push(CalendarConfig {
+                effect: schedule.effect.clone(),
+                start_date: normalize_date(&schedule.start_date)?,
+                end_date: normalize_date(&schedule.end_date)?,
+                start: normalize_time(&schedule.start)?,
+                end: normalize_time(&schedule.end)?,
+            });
+        }
+        let current: Vec<(i32, CalendarConfig)> = details
+            .calendar
+            .iter()
+            .map(|s| (s.id, names.calendar(s)))
+            .collect();
+        for (id, from, to) in match_schedules(current, wanted, |a, b| a.effect == b.effect) {
+            changes.push(match (from, to) {
+                (Some(from), Some(to)) => ConfigChange::ModifyCalendar { id, from, to },
+                (Some(schedule), None) => ConfigChange::DeleteCalendar { id, schedule },
+                (None, Some(schedule)) => ConfigChange::AddCalendar { schedule },
+                (None, None) => continue,
+            });
+        }
+    }
+
+    // Combined effect and overlays
+    if let Some(combined) = &config.combined {
+        for name in &combined.effects {
+            known(name)?;
+        }
+        let current = names.combined(details);
+        let unchanged = if combined.effects.is_empty() {
+            current.effects.is_empty()
+        } else {
+            *combined == current
+        };
+        if !unchanged {
+            changes.push(ConfigChange::SetCombined {
+                from: current,
+                to: combined.clone(),
+            });
+        }
+    }
+    if let Some(overlays) = &config.overlays {
+        let mut wanted = overlays.clone();
+        wanted.sort();
+        wanted.dedup();
+        for overlay in &wanted {
+            known(&overlay.effect)?;
+        }
+        let current = names.overlays(details);
+        if wanted != current {
+            changes.push(ConfigChange::SetOverlays {
+                from: current,
+                to: wanted,
+            });
+        }
+    }
+
+    // Effects go last so nothing refers to them when they are deleted
+    changes.extend(deleted_effects);
+    Ok(ConfigPlan { changes })
+}
+
+/// Pair current schedules with wanted ones
+///
+/// Identical schedules are left out. Remaining wanted schedules take over a
+/// current schedule, preferring one that `similar` accepts, and the rest are
+/// added or deleted. Returns `(id, current, wanted)` with an ID of 0 for
+/// additions.
+fn match_schedules<T: PartialEq + Clone>(
+    current: Vec<(i32, T)>,
+    wanted: Vec<T>,
+    similar: impl Fn(&T, &T) -> bool,
+) -> Vec<(i32, Option<T>, Option<T>)> {
+    let mut remaining = current;
+    let mut unmatched = Vec::new();
+    for schedule in wanted {
+        match remaining.iter().position(|(_, s)| *s == schedule) {
+            Some(index) => {
+                remaining.remove(index);
+            }
+            None => unmatched.push(schedule),
+        }
+    }
+
+    let mut result = Vec::new();
+    for schedule in unmatched {
+        if remaining.is_empty() {
+            result.push((0, None, Some(schedule)));
+            continue;
+        }
+        let index = remaining
+            .iter()
+            .position(|(_, s)| similar(s, &schedule))
+            .unwrap_or(0);
+        let (id, from) = remaining.remove(index);
+        result.push((id, Some(from), Some(schedule)));
+    }
+    result.extend(remaining.into_iter().map(|(id, s)| (id, Some(s), None)));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CombinedEffect, OverlayEffect};
+
+    fn details() -> DeviceDetails {
+        serde_json::from_value(serde_json::json!({
+            "name": "Front",
+            "colorOrder": 0,
+            "ports": [{"id": 1, "start": 0, "end": 100}],
+            "effects": [
+                {"id": 1, "name": "Warm White", "category": 1, "mode": 0, "speed": 100,
+                 "brightness": 200, "pixelLen": 30, "reverse": false},
+                {"id": 2, "name": "Candy Cane", "category": 2, "mode": 1, "speed": 120,
+                 "brightness": 255, "pixels": [
+                    {"index": 0, "count": 5, "color": 16711680, "disable": false},
+                    {"index": 1, "count": 5, "color": 16777215, "disable": false}
+                 ]},
+                {"id": 5, "name": "Old Test", "category": 1, "mode": 9, "speed": 10,
+                 "brightness": 10}
+            ],
+            "daily": [
+                {"id": 0, "enable": true, "effectId": 1, "repetition": 1,
+                 "startTime": {"hours": 18, "minutes": 0}, "endTime": {"hours": 23, "minutes": 0}}
+            ],
+            "calendar": [
+                {"id": 3, "effectId": 2, "startDate": {"month": 12, "day": 1},
+                 "endDate": {"month": 12, "day": 31}, "startTime": {"hours": 17, "minutes": 0},
+                 "endTime": {"hours": 23, "minutes": 30}}
+            ],
+            "combinedEffect": {"effectIds": [1, 2], "interval": 60},
+            "overlayEffects": [{"overlayType": 1, "targetEffect": 2}]
+        }))
+        .unwrap()
+    }
+
+    const CONFIG: &str = r##"
+name = "Front"
+color_order = 0
+ports = [{ start = 0, end = 100 }]
+
+[[effects]]
+name = "Warm White"
+built_in = 0
+brightness = 200
+
+[[effects]]
+name = "Candy Cane"
+pattern = 1
+speed = 120
+brightness = 255
+pixels = [{ color = "#ff0000", count = 5 }, { color = "#FFFFFF", count = 5 }]
+
+[[daily]]
+effect = "Warm White"
+start = "18:00"
+end = "23:00"
+
+[[calendar]]
+effect = "Candy Cane"
+start_date = "12-01"
+end_date = "12-31"
+start = "17:00"
+end = "23:30"
+
+[combined]
+effects = ["Warm White", "Candy Cane"]
+interval = 60
+
+[[overlays]]
+type = "snow"
+effect = "Candy Cane"
+"##;
+
+    #[test]
+    fn test_matching_config_has_no_changes() {
+        let config = DeviceConfig::from_toml(CONFIG).unwrap();
+        let plan = plan_config(&config, &details(), false).unwrap();
+        assert!(plan.is_empty(), "{:?}", plan);
+    }
+
+    #[test]
+    fn test_yaml_matches_toml() {
+        let yaml = r##"
+name: Front
+color_order: 0
+ports:
+  - { start: 0, end: 100 }
+effects:
+  - name: Warm White
+    built_in: 0
+    brightness: 200
+  - name: Candy Cane
+    pattern: 1
+    speed: 120
+    brightness: 255
+    pixels:
+      - { color: "#ff0000", count: 5 }
+      - { color: "#ffffff", count: 5 }
+daily:
+  - { effect: Warm White, start: "18:00", end: "23:00" }
+calendar:
+  - { effect: Candy Cane, start_date: "12-01", end_date: "12-31", start: "17:00", end: "23:30" }
+combined:
+  effects: [Warm White, Candy Cane]
+  interval: 60
+overlays:
+  - { type: snow, effect: Candy Cane }
+"##;
+        let yaml = DeviceConfig::from_yaml(yaml).unwrap();
+        let mut toml = DeviceConfig::from_toml(CONFIG).unwrap();
+        toml.effects[1].pixels[1].color = "#ffffff".to_string();
+        assert_eq!(yaml, toml);
+    }
+
+    #[test]
+    fn test_from_details_round_trip() {
+        let details = details();
+        let config = DeviceConfig::from_details(&details);
+        let parsed = DeviceConfig::from_toml(&config.to_toml().unwrap()).unwrap();
+        assert_eq!(parsed, config);
+        assert!(plan_config(&parsed, &details, true).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_plan_changes() {
+        let mut config = DeviceConfig::from_toml(CONFIG).unwrap();
+        config.name = Some("Front House".to_string());
+        config.effects[0].speed = 150;
+        config.effects.push(EffectConfig {
+            name: "Storm".to_string(),
+            built_in: Some(42),
+            pattern: None,
+            speed: 100,
+            brightness: 100,
+            pixel_len: None,
+            reverse: None,
+            pixels: Vec::new(),
+        });
+        let daily = config.daily.as_mut().unwrap();
+        daily[0].start = "17:30".to_string();
+        daily.push(DailyConfig {
+            effect: "Storm".to_string(),
+            start: "5:00".to_string(),
+            end: "07:00".to_string(),
+            repeat: Repetition::Weekdays,
+            enabled: false,
+        });
+        config.calendar = Some(Vec::new());
+        config.overlays = Some(vec![OverlayConfig {
+            overlay_type: OverlayType::Lightning,
+            effect: "Storm".to_string(),
+        }]);
+
+        let plan = plan_config(&config, &details(), true).unwrap();
+        let lines: Vec<String> = plan
+            .changes
+            .iter()
+            .map(|c| format!("{} {}", c.symbol(), c))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "~ name: \"Front\" -> \"Front House\"",
+                "~ effect \"Warm White\" (id 1): speed 100 -> 150",
+                "+ effect \"Storm\"",
+                "~ daily 0: 18:00-23:00 everyday: Warm White -> 17:30-23:00 everyday: Warm White",
+                "+ daily 05:00-07:00 weekdays: Storm (disabled)",
+                "- calendar 3: 12-01 to 12-31 17:00-23:30: Candy Cane",
+                "~ overlays: snow on Candy Cane -> lightning on Storm",
+                "- effect \"Old Test\" (id 5)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_combined_and_overlays() {
+        let mut details = details();
+        details.combined_effect = Some(CombinedEffect::default());
+        details.overlay_effects = vec![OverlayEffect {
+            overlay_type: 1,
+            target_effect: 2,
+            ..Default::default()
+        }];
+        let config = DeviceConfig {
+            combined: Some(CombinedConfig::default()),
+            overlays: Some(Vec::new()),
+            ..Default::default()
+        };
+        let plan = plan_config(&config, &details, false).unwrap();
+        assert_eq!(
+            plan.changes,
+            [ConfigChange::SetOverlays {
+                from: vec![OverlayConfig {
+                    overlay_type: OverlayType::Snow,
+                    effect: "Candy Cane".to_string(),
+                }],
+                to: Vec::new(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_invalid_configs() {
+        let error = |text: &str| match DeviceConfig::from_toml(text)
+            .and_then(|config| plan_config(&config, &details(), false))
+        {
+            Err(TrimlightError::ApiError { code: 400, message }) => message,
+            other => panic!("expected an error, got {:?}", other),
+        };
+
+        assert!(error("colour_order = 1").contains("unknown field"));
+        assert_eq!(
+            error("[[daily]]\neffect = \"Missing\"\nstart = \"18:00\"\nend = \"23:00\""),
+            "Effect 'Missing' is not defined in the config or saved on the device"
+        );
+        assert_eq!(
+            error("[[effects]]\nname = \"Both\"\nbuilt_in = 1\npattern = 1"),
+            "Effect 'Both' must set exactly one of built_in or pattern"
+        );
+        assert_eq!(
+            error("[[effects]]\nname = \"Red\"\npattern = 1\npixels = [{ color = \"red\" }]"),
+            "Invalid color 'red'. Use #RRGGBB format"
+        );
+        let three_daily =
+            "[[daily]]\neffect = \"Warm White\"\nstart = \"18:00\"\nend = \"23:00\"\n".repeat(3);
+        assert_eq!(
+            error(&three_daily),
+            "A device holds at most 2 daily schedules"
+        );
+    }
+
+    #[test]
+    fn test_load_reports_missing_files_as_io_errors() {
+        let path = std::env::temp_dir().join("trimlight-config-missing.toml");
+        match DeviceConfig::load(&path) {
+            Err(TrimlightError::IoError { context, source }) => {
+                assert_eq!(context, format!("Can't read config {}", path.display()));
+                assert_eq!(source.kind(), std::io::ErrorKind::NotFound);
+            }
+            other => panic!("expected an I/O error, got {:?}", other),
+        }
+    }
+}
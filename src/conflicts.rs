@@ -0,0 +1,600 @@
+use chrono::{Datelike, NaiveDate, Weekday};
+use serde::Serialize;
+use std::fmt;
+
+use crate::interval::{minute_of_day, DateRange, TimeWindow};
+use crate::models::{CalendarSchedule, DailySchedule, ScheduleDate, ScheduleTime};
+
+/// The two kinds of schedules a device stores
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduleType {
+    Daily,
+    Calendar,
+}
+
+impl fmt::Display for ScheduleType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScheduleType::Daily => write!(f, "daily"),
+            ScheduleType::Calendar => write!(f, "calendar"),
+        }
+    }
+}
+
+/// One of the schedules involved in a conflict
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConflictingSchedule {
+    pub schedule_type: ScheduleType,
+    pub id: i32,
+    pub effect_id: i32,
+}
+
+impl ConflictingSchedule {
+    fn daily(schedule: &DailySchedule) -> Self {
+        Self {
+            schedule_type: ScheduleType::Daily,
+            id: schedule.id,
+            effect_id: schedule.effect_id,
+        }
+    }
+
+    fn calendar(schedule: &CalendarSchedule) -> Self {
+        Self {
+            schedule_type: ScheduleType::Calendar,
+            id: schedule.id,
+            effect_id: schedule.effect_id,
+        }
+    }
+}
+
+impl fmt::Display for ConflictingSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.schedule_type, self.id)
+    }
+}
+
+/// Days on which both schedules of a conflict run
+///
+/// For overlaps after midnight these are the days the overlap starts on, not
+/// the days the schedules were started.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConflictDays {
+    /// Shared date range, or `None` when both schedules repeat all year
+    pub dates: Option<(ScheduleDate, ScheduleDate)>,
+    /// Days of the week on which both schedules run
+    pub weekdays: Vec<Weekday>,
+}
+
+impl fmt::Display for ConflictDays {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", describe_weekdays(&self.weekdays))?;
+        if let Some((start, end)) = &self.dates {
+            write!(
+                f,
+                ", {:02}-{:02} to {:02}-{:02}",
+                start.month, start.day, end.month, end.day
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Two schedules that run at the same time
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScheduleConflict {
+    pub first: ConflictingSchedule,
+    pub second: ConflictingSchedule,
+    pub days: ConflictDays,
+    /// Start of the time window in which both schedules run
+    pub start_time: ScheduleTime,
+    /// End of the time window in which both schedules run
+    pub end_time: ScheduleTime,
+    /// The schedule the device is assumed to run during the conflict (see [`Precedence`])
+    pub runs: ConflictingSchedule,
+    /// Why `runs` takes precedence over the other schedule
+    pub precedence: Precedence,
+}
+
+/// Rule assumed to decide which of two overlapping schedules the device runs
+///
+/// The API documentation doesn't say how overlaps are resolved. These rules
+/// are an assumption: calendar schedules override daily schedules; between
+/// schedules of the same type, the one that started most recently takes
+/// over; when both start at the same time the one listed first runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Precedence {
+    CalendarOverridesDaily,
+    StartedLater,
+    ListedFirst,
+}
+
+impl ScheduleConflict {
+    /// The schedule that is not run during the conflict
+    pub fn overridden(&self) -> &ConflictingSchedule {
+        if self.runs == self.first {
+            &self.second
+        } else {
+            &self.first
+        }
+    }
+
+    /// Sentence describing which schedule runs and why
+    pub fn explanation(&self) -> String {
+        match self.precedence {
+            Precedence::CalendarOverridesDaily => {
+                format!("{} overrides {}", self.runs, self.overridden())
+            }
+            Precedence::StartedLater => format!(
+                "{} starts later and takes over from {}",
+                self.runs,
+                self.overridden()
+            ),
+            Precedence::ListedFirst => format!(
+                "{} and {} start together, {} is listed first",
+                self.first, self.second, self.runs
+            ),
+        }
+    }
+}
+
+const ALL_WEEKDAYS: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+/// Days of the week a daily schedule repetition runs on
+///
+/// "Today only" (0) and unknown values run on no fixed weekday.
+pub fn repetition_weekdays(repetition: i32) -> Vec<Weekday> {
+    match repetition {
+        1 => ALL_WEEKDAYS.to_vec(),
+        2 => ALL_WEEKDAYS[..5].to_vec(),
+        3 => ALL_WEEKDAYS[5..].to_vec(),
+        _ => Vec::new(),
+    }
+}
+
+fn describe_weekdays(weekdays: &[Weekday]) -> String {
+    if weekdays == ALL_WEEKDAYS {
+        "Every day".to_string()
+    } else if weekdays == &ALL_WEEKDAYS[..5] {
+        "Weekdays".to_string()
+    } else if weekdays == &ALL_WEEKDAYS[5..] {
+        "Weekend".to_string()
+    } else {
+        weekdays
+            .iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Weekdays moved forward by a number of days
+fn shift_weekdays(weekdays: &[Weekday], days: i32) -> Vec<Weekday> {
+    let shifted: Vec<Weekday> = weekdays
+        .iter()
+        .map(|d| {
+            let index = d.num_days_from_monday() as i32 + days;
+            ALL_WEEKDAYS[index.rem_euclid(7) as usize]
+        })
+        .collect();
+    ALL_WEEKDAYS
+        .into_iter()
+        .filter(|d| shifted.contains(d))
+        .collect()
+}
+
+/// Days on which a schedule starts a run
+#[derive(Debug, Clone)]
+struct RunDays {
+    dates: Option<DateRange>,
+    weekdays: Vec<Weekday>,
+}
+
+impl RunDays {
+    /// "Today only" runs on `today`, unknown repetitions never run
+    fn daily(schedule: &DailySchedule, today: NaiveDate) -> Option<Self> {
+        match schedule.repetition {
+            0 => {
+                let date = ScheduleDate::from(today);
+                Some(Self {
+                    dates: DateRange::new(&date, &date),
+                    weekdays: ALL_WEEKDAYS.to_vec(),
+                })
+            }
+            1..=3 => Some(Self {
+                dates: None,
+                weekdays: repetition_weekdays(schedule.repetition),
+            }),
+            _ => None,
+        }
+    }
+
+    fn calendar(schedule: &CalendarSchedule) -> Option<Self> {
+        DateRange::new(&schedule.start_date, &schedule.end_date).map(|dates| Self {
+            dates: Some(dates),
+            weekdays: ALL_WEEKDAYS.to_vec(),
+        })
+    }
+
+    fn shift(&self, days: i32) -> Self {
+        Self {
+            dates: self.dates.map(|dates| dates.shift(days)),
+            weekdays: shift_weekdays(&self.weekdays, days),
+        }
+    }
+
+    /// Days on which both run, one entry per separate date range
+    ///
+    /// Weekdays are matched against the dates of the next run on or after
+    /// `today`.
+    fn shared(&self, other: &RunDays, today: NaiveDate) -> Vec<ConflictDays> {
+        let weekdays: Vec<Weekday> = self
+            .weekdays
+            .iter()
+            .filter(|d| other.weekdays.contains(d))
+            .copied()
+            .collect();
+        if weekdays.is_empty() {
+            return Vec::new();
+        }
+
+        let ranges = match (self.dates, other.dates) {
+            (None, None) => {
+                return vec![ConflictDays {
+                    dates: None,
+                    weekdays,
+                }]
+            }
+            (Some(dates), None) | (None, Some(dates)) => vec![dates],
+            (Some(dates1), Some(dates2)) => dates1.intersect(&dates2),
+        };
+
+        ranges
+            .into_iter()
+            .filter_map(|range| {
+                if range.is_all_year() || weekdays == ALL_WEEKDAYS {
+                    return Some(ConflictDays {
+                        dates: (!range.is_all_year()).then(|| (range.start(), range.end())),
+                        weekdays: weekdays.clone(),
+                    });
+                }
+
+                let dates: Vec<NaiveDate> = range
+                    .next_dates(today)
+                    .into_iter()
+                    .filter(|d| weekdays.contains(&d.weekday()))
+                    .collect();
+                let (first, last) = (dates.first()?, dates.last()?);
+                Some(ConflictDays {
+                    dates: Some(((*first).into(), (*last).into())),
+                    weekdays: ALL_WEEKDAYS
+                        .into_iter()
+                        .filter(|wd| dates.iter().any(|d| d.weekday() == *wd))
+                        .collect(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// A schedule reduced to what matters for conflicts
+struct Run {
+    schedule: ConflictingSchedule,
+    window: TimeWindow,
+    days: RunDays,
+}
+
+/// Decide which of two overlapping runs the device runs from `start_time`
+fn precedence<'a>(
+    first: &'a Run,
+    second: &'a Run,
+    start_time: &ScheduleTime,
+) -> (&'a Run, Precedence) {
+    match (first.schedule.schedule_type, second.schedule.schedule_type) {
+        (ScheduleType::Daily, ScheduleType::Calendar) => {
+            return (second, Precedence::CalendarOverridesDaily)
+        }
+        (ScheduleType::Calendar, ScheduleType::Daily) => {
+            return (first, Precedence::CalendarOverridesDaily)
+        }
+        _ => {}
+    }
+
+    let minute = minute_of_day(start_time);
+    let elapsed1 = first.window.range().offset_of(minute);
+    let elapsed2 = second.window.range().offset_of(minute);
+    if elapsed2 < elapsed1 {
+        (second, Precedence::StartedLater)
+    } else if elapsed1 < elapsed2 {
+        (first, Precedence::StartedLater)
+    } else {
+        (first, Precedence::ListedFirst)
+    }
+}
+
+/// Find every pair of schedules that would run at the same time
+///
+/// Daily schedules are compared with each other and with calendar schedules.
+/// "Today only" daily schedules run on `today`, which should be the date on
+/// the device clock. Schedules ending before they start run past midnight,
+/// and calendar ranges ending before they start run into the next year.
+/// Disabled daily schedules are ignored.
+pub fn find_schedule_conflicts(
+    daily: &[DailySchedule],
+    calendar: &[CalendarSchedule],
+    today: NaiveDate,
+) -> Vec<ScheduleConflict> {
+    let daily_runs = daily.iter().filter(|s| s.enable).filter_map(|s| {
+        Some(Run {
+            schedule: ConflictingSchedule::daily(s),
+            window: TimeWindow::new(&s.start_time, &s.end_time),
+            days: RunDays::daily(s, today)?,
+        })
+    });
+    let calendar_runs = calendar.iter().filter_map(|s| {
+        Some(Run {
+            schedule: ConflictingSchedule::calendar(s),
+            window: TimeWindow::new(&s.start_time, &s.end_time),
+            days: RunDays::calendar(s)?,
+        })
+    });
+    let runs: Vec<Run> = daily_runs.chain(calendar_runs).collect();
+
+    let mut conflicts = Vec::new();
+    for (i, run1) in runs.iter().enumerate() {
+        for run2 in runs.iter().skip(i + 1) {
+            for overlap in run1.window.intersect(&run2.window) {
+                // An overlap after midnight belongs to the previous day's run
+                let start_time = overlap.start();
+                let days1 = run1.days.shift(run1.window.day_offset(&start_time));
+                let days2 = run2.days.shift(run2.window.day_offset(&start_time));
+                let (runs, precedence) = precedence(run1, run2, &start_time);
+
+                for days in days1.shared(&days2, today) {
+                    conflicts.push(ScheduleConflict {
+                        first: run1.schedule.clone(),
+                        second: run2.schedule.clone(),
+                        days,
+                        start_time: start_time.clone(),
+                        end_time: overlap.end(),
+                        runs: runs.schedule.clone(),
+                        precedence,
+                    });
+                }
+            }
+        }
+    }
+
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{calendar, daily, today};
+
+    fn time(hours: i32, minutes: i32) -> ScheduleTime {
+        ScheduleTime::new(hours, minutes).unwrap()
+    }
+
+    fn date(month: i32, day: i32) -> ScheduleDate {
+        ScheduleDate::new(month, day).unwrap()
+    }
+
+    #[test]
+    fn test_daily_conflict() {
+        let daily = vec![daily(0, 1, (8, 0), (12, 0)), daily(1, 2, (10, 0), (14, 0))];
+
+        let conflicts = find_schedule_conflicts(&daily, &[], today());
+        assert_eq!(conflicts.len(), 1);
+        let conflict = &conflicts[0];
+        assert_eq!(conflict.first.schedule_type, ScheduleType::Daily);
+        assert_eq!((conflict.first.id, conflict.second.id), (0, 1));
+        assert_eq!(conflict.second.effect_id, 11);
+        assert_eq!(conflict.days.weekdays, ALL_WEEKDAYS[..5].to_vec());
+        assert_eq!(conflict.days.to_string(), "Weekdays");
+        assert_eq!(conflict.start_time, ScheduleTime::new(10, 0).unwrap());
+        assert_eq!(conflict.end_time, ScheduleTime::new(12, 0).unwrap());
+    }
+
+    #[test]
+    fn test_daily_no_conflict() {
+        // Weekdays and weekend never share a day
+        let schedules = vec![daily(0, 2, (8, 0), (12, 0)), daily(1, 3, (8, 0), (12, 0))];
+        assert!(find_schedule_conflicts(&schedules, &[], today()).is_empty());
+
+        // Disabled schedules are ignored
+        let mut disabled = daily(1, 1, (8, 0), (12, 0));
+        disabled.enable = false;
+        let schedules = vec![daily(0, 1, (8, 0), (12, 0)), disabled];
+        assert!(find_schedule_conflicts(&schedules, &[], today()).is_empty());
+    }
+
+    #[test]
+    fn test_calendar_conflict() {
+        let schedules = vec![
+            calendar(0, (12, 1), (12, 26)),
+            calendar(1, (12, 24), (12, 31)),
+        ];
+
+        let conflicts = find_schedule_conflicts(&[], &schedules, today());
+        assert_eq!(conflicts.len(), 1);
+        let conflict = &conflicts[0];
+        assert_eq!(conflict.first.schedule_type, ScheduleType::Calendar);
+        assert_eq!(
+            conflict.days.dates,
+            Some((
+                ScheduleDate::new(12, 24).unwrap(),
+                ScheduleDate::new(12, 26).unwrap()
+            ))
+        );
+        assert_eq!(conflict.days.to_string(), "Every day, 12-24 to 12-26");
+    }
+
+    #[test]
+    fn test_daily_overnight_conflict() {
+        let schedules = vec![daily(0, 1, (20, 0), (2, 0)), daily(1, 1, (1, 0), (3, 0))];
+
+        let conflicts = find_schedule_conflicts(&schedules, &[], today());
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].days.to_string(), "Every day");
+        assert_eq!(conflicts[0].start_time, time(1, 0));
+        assert_eq!(conflicts[0].end_time, time(2, 0));
+
+        // Both running past midnight share one window
+        let schedules = vec![daily(0, 1, (20, 0), (2, 0)), daily(1, 1, (22, 0), (1, 0))];
+        let conflicts = find_schedule_conflicts(&schedules, &[], today());
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].start_time, time(22, 0));
+        assert_eq!(conflicts[0].end_time, time(1, 0));
+
+        // Overlapping at both ends
+        let schedules = vec![daily(0, 1, (20, 0), (2, 0)), daily(1, 1, (1, 0), (21, 0))];
+        let windows: Vec<(ScheduleTime, ScheduleTime)> =
+            find_schedule_conflicts(&schedules, &[], today())
+                .into_iter()
+                .map(|c| (c.start_time, c.end_time))
+                .collect();
+        assert_eq!(
+            windows,
+            vec![(time(1, 0), time(2, 0)), (time(20, 0), time(21, 0))]
+        );
+
+        // Ending exactly when the other starts is not a conflict
+        let schedules = vec![daily(0, 1, (20, 0), (2, 0)), daily(1, 1, (2, 0), (8, 0))];
+        assert!(find_schedule_conflicts(&schedules, &[], today()).is_empty());
+    }
+
+    #[test]
+    fn test_daily_overnight_spills_into_next_day() {
+        // Friday night's weekday run continues into Saturday morning
+        let schedules = vec![daily(0, 2, (20, 0), (2, 0)), daily(1, 3, (1, 0), (3, 0))];
+
+        let conflicts = find_schedule_conflicts(&schedules, &[], today());
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].days.weekdays, vec![Weekday::Sat]);
+        assert_eq!(conflicts[0].start_time, time(1, 0));
+
+        // Sunday night's weekend run continues into Monday morning
+        let schedules = vec![daily(0, 3, (20, 0), (2, 0)), daily(1, 2, (1, 0), (3, 0))];
+        let conflicts = find_schedule_conflicts(&schedules, &[], today());
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].days.weekdays, vec![Weekday::Mon]);
+    }
+
+    #[test]
+    fn test_calendar_new_year_conflict() {
+        let schedules = vec![calendar(0, (12, 15), (1, 5)), calendar(1, (1, 1), (1, 31))];
+
+        let conflicts = find_schedule_conflicts(&[], &schedules, today());
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].days.dates, Some((date(1, 1), date(1, 5))));
+
+        // Both ranges crossing new year
+        let schedules = vec![calendar(0, (12, 15), (1, 5)), calendar(1, (12, 31), (2, 1))];
+        let conflicts = find_schedule_conflicts(&[], &schedules, today());
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].days.to_string(), "Every day, 12-31 to 01-05");
+
+        let schedules = vec![calendar(0, (12, 15), (1, 5)), calendar(1, (1, 6), (12, 14))];
+        assert!(find_schedule_conflicts(&[], &schedules, today()).is_empty());
+    }
+
+    #[test]
+    fn test_calendar_overnight_conflict() {
+        // The run starting on the last day continues into the next morning
+        let mut night = calendar(0, (12, 24), (12, 26));
+        night.start_time = time(20, 0);
+        night.end_time = time(2, 0);
+        let mut morning = calendar(1, (12, 27), (12, 27));
+        morning.start_time = time(1, 0);
+        morning.end_time = time(6, 0);
+
+        let conflicts = find_schedule_conflicts(&[], &[night, morning], today());
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].days.dates, Some((date(12, 27), date(12, 27))));
+        assert_eq!(conflicts[0].start_time, time(1, 0));
+        assert_eq!(conflicts[0].end_time, time(2, 0));
+    }
+
+    #[test]
+    fn test_daily_calendar_conflict() {
+        let daily = vec![daily(0, 1, (17, 0), (22, 0))];
+        let mut christmas = calendar(1, (12, 24), (12, 26));
+        christmas.start_time = time(18, 0);
+
+        let conflicts = find_schedule_conflicts(&daily, &[christmas], today());
+        assert_eq!(conflicts.len(), 1);
+        let conflict = &conflicts[0];
+        assert_eq!(conflict.first.schedule_type, ScheduleType::Daily);
+        assert_eq!(conflict.second.schedule_type, ScheduleType::Calendar);
+        assert_eq!(conflict.days.to_string(), "Every day, 12-24 to 12-26");
+        assert_eq!(conflict.start_time, time(18, 0));
+        assert_eq!(conflict.end_time, time(22, 0));
+        assert_eq!(conflict.runs, conflict.second);
+        assert_eq!(conflict.precedence, Precedence::CalendarOverridesDaily);
+        assert_eq!(conflict.explanation(), "calendar 1 overrides daily 0");
+    }
+
+    #[test]
+    fn test_daily_calendar_weekdays() {
+        // Weekends of the holiday run that is still to come
+        let daily = vec![daily(0, 3, (18, 0), (22, 0))];
+        let holidays = calendar(1, (12, 15), (1, 5));
+
+        let conflicts = find_schedule_conflicts(&daily, &[holidays], today());
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].days.to_string(), "Weekend, 12-19 to 01-03");
+
+        // No weekend falls within a Monday to Wednesday range
+        let short = calendar(1, (12, 21), (12, 23));
+        assert!(find_schedule_conflicts(&daily, &[short], today()).is_empty());
+    }
+
+    #[test]
+    fn test_today_only_conflict() {
+        let schedules = vec![daily(0, 0, (20, 0), (23, 0)), daily(1, 3, (21, 0), (22, 0))];
+
+        let conflicts = find_schedule_conflicts(&schedules, &[], today());
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].days.dates, Some((date(12, 19), date(12, 19))));
+        assert_eq!(conflicts[0].days.weekdays, vec![Weekday::Sat]);
+
+        // Today is not a weekday
+        let schedules = vec![daily(0, 0, (20, 0), (23, 0)), daily(1, 2, (21, 0), (22, 0))];
+        assert!(find_schedule_conflicts(&schedules, &[], today()).is_empty());
+
+        let christmas = calendar(2, (12, 24), (12, 26));
+        assert!(find_schedule_conflicts(&schedules[..1], &[christmas], today()).is_empty());
+    }
+
+    #[test]
+    fn test_same_type_precedence() {
+        let schedules = vec![daily(0, 1, (8, 0), (12, 0)), daily(1, 1, (10, 0), (14, 0))];
+        let conflict = &find_schedule_conflicts(&schedules, &[], today())[0];
+        assert_eq!(conflict.runs.id, 1);
+        assert_eq!(conflict.precedence, Precedence::StartedLater);
+        assert_eq!(
+            conflict.explanation(),
+            "daily 1 starts later and takes over from daily 0"
+        );
+
+        let schedules = vec![daily(0, 1, (8, 0), (12, 0)), daily(1, 1, (8, 0), (10, 0))];
+        let conflict = &find_schedule_conflicts(&schedules, &[], today())[0];
+        assert_eq!(conflict.runs.id, 0);
+        assert_eq!(conflict.precedence, Precedence::ListedFirst);
+        assert_eq!(
+            conflict.explanation(),
+            "daily 0 and daily 1 start together, daily 0 is listed first"
+        );
+    }
+}
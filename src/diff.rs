@@ -0,0 +1,358 @@
+//! Field-level comparison of two device states.
+//!
+//! [`diff`] compares two [`DeviceDetails`], for example two controllers that
+//! should be configured the same, or a device and an older snapshot of it.
+//! Effects are matched by name and schedules by content, and effect references
+//! are compared by name so devices that numbered their effects or schedules
+//! differently still compare equal.
+
+use serde::Serialize;
+use std::fmt;
+
+use crate::config::{effect_field_changes, EffectNames, FieldChange};
+use crate::models::{DeviceDetails, Effect};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+impl ChangeKind {
+    /// `+` for additions, `-` for removals and `~` for changes
+    pub fn symbol(&self) -> char {
+        match self {
+            ChangeKind::Added => '+',
+            ChangeKind::Removed => '-',
+            ChangeKind::Modified => '~',
+        }
+    }
+}
+
+/// An effect or schedule that differs between the two devices
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ItemChange {
+    pub kind: ChangeKind,
+    /// The effect or schedule, e.g. `effect "Warm White"` or `daily 0`
+    pub item: String,
+    /// Fields that changed, for modified items
+    pub fields: Vec<FieldChange>,
+}
+
+impl fmt::Display for ItemChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.item)?;
+        if !self.fields.is_empty() {
+            let fields: Vec<String> = self.fields.iter().map(|c| c.to_string()).collect();
+            write!(f, ": {}", fields.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Differences between two device states, from the first to the second
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DeviceDiff {
+    /// Name, color order, IC, ports, combined effect and overlays
+    pub settings: Vec<FieldChange>,
+    pub effects: Vec<ItemChange>,
+    pub schedules: Vec<ItemChange>,
+}
+
+impl DeviceDiff {
+    pub fn is_empty(&self) -> bool {
+        self.settings.is_empty() && self.effects.is_empty() && self.schedules.is_empty()
+    }
+}
+
+/// Compare two device states
+///
+/// Changes describe how to get from `old` to `new`. Effects with the same
+/// name are paired in order, so duplicates on both sides still line up.
+/// Identical schedules are paired whatever their IDs; the rest are reported
+/// as modified when the other device has a schedule with the same ID.
+pub fn diff(old: &DeviceDetails, new: &DeviceDetails) -> DeviceDiff {
+    let old_names = EffectNames::new(old);
+    let new_names = EffectNames::new(new);
+    let mut result = DeviceDiff::default();
+
+    // Settings
+    let mut setting = |field: &str, from: String, to: String| {
+        if from != to {
+            result.settings.push(FieldChange {
+                field: field.to_string(),
+                from,
+                to,
+            });
+        }
+    };
+    let ports = |details: &DeviceDetails| {
+        details
+            .ports
+            .iter()
+            .map(|p| format!("{}-{}", p.start, p.end))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let overlays = |names: &EffectNames, details: &DeviceDetails| {
+        names
+            .overlays(details)
+            .iter()
+            .map(|o| o.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    setting("name", old.name.clone(), new.name.clone());
+    setting(
+        "color_order",
+        old.color_order.to_string(),
+        new.color_order.to_string(),
+    );
+    setting("ic", old.ic.to_string(), new.ic.to_string());
+    setting("ports", ports(old), ports(new));
+    setting(
+        "combined",
+        old_names.combined(old).to_string(),
+        new_names.combined(new).to_string(),
+    );
+    setting(
+        "overlays",
+        overlays(&old_names, old),
+        overlays(&new_names, new),
+    );
+
+    // Effects
+    let mut unmatched: Vec<&Effect> = new.effects.iter().collect();
+    for effect in &old.effects {
+        let item = format!("effect {:?}", effect.name);
+        match unmatched.iter().position(|e| e.name == effect.name) {
+            Some(index) => {
+                let fields = effect_field_changes(effect, unmatched.remove(index));
+                if !fields.is_empty() {
+                    result.effects.push(ItemChange {
+                        kind: ChangeKind::Modified,
+                        item,
+                        fields,
+                    });
+                }
+            }
+            None => result.effects.push(ItemChange {
+                kind: ChangeKind::Removed,
+                item,
+                fields: Vec::new(),
+            }),
+        }
+    }
+    result
+        .effects
+        .extend(unmatched.into_iter().map(|effect| ItemChange {
+            kind: ChangeKind::Added,
+            item: format!("effect {:?}", effect.name),
+            fields: Vec::new(),
+        }));
+
+    // Schedules
+    let daily = |names: &EffectNames, details: &DeviceDetails| {
+        details
+            .daily
+            .iter()
+            .map(|s| {
+                let config = names.daily(s);
+                let fields = vec![
+                    ("effect", config.effect.clone()),
+                    ("start", config.start.clone()),
+                    ("end", config.end.clone()),
+                    ("repeat", config.repeat.to_string()),
+                    ("enabled", config.enabled.to_string()),
+                ];
+                (format!("daily {}", s.id), config.to_string(), fields)
+            })
+            .collect::<Vec<_>>()
+    };
+    let calendar = |names: &EffectNames, details: &DeviceDetails| {
+        details
+            .calendar
+            .iter()
+            .map(|s| {
+                let config = names.calendar(s);
+                let fields = vec![
+                    ("effect", config.effect.clone()),
+                    ("start_date", config.start_date.clone()),
+                    ("end_date", config.end_date.clone()),
+                    ("start", config.start.clone()),
+                    ("end", config.end.clone()),
+                ];
+                (format!("calendar {}", s.id), config.to_string(), fields)
+            })
+            .collect::<Vec<_>>()
+    };
+    for (old_schedules, new_schedules) in [
+        (daily(&old_names, old), daily(&new_names, new)),
+        (calendar(&old_names, old), calendar(&new_names, new)),
+    ] {
+        let mut unmatched: Vec<_> = new_schedules.iter().collect();
+        let mut remaining: Vec<_> = old_schedules.iter().collect();
+        remaining.retain(|(_, _, fields)| {
+            match unmatched.iter().position(|(_, _, other)| other == fields) {
+                Some(index) => {
+                    unmatched.remove(index);
+                    false
+                }
+                None => true,
+            }
+        });
+
+        for (item, description, fields) in remaining {
+            match unmatched.iter().position(|(other, _, _)| other == item) {
+                Some(index) => {
+                    let (_, _, new_fields) = unmatched.remove(index);
+                    let fields: Vec<FieldChange> = fields
+                        .iter()
+                        .zip(new_fields)
+                        .filter(|((_, from), (_, to))| from != to)
+                        .map(|((field, from), (_, to))| FieldChange {
+                            field: field.to_string(),
+                            from: from.clone(),
+                            to: to.clone(),
+                        })
+                        .collect();
+                    if !fields.is_empty() {
+                        result.schedules.push(ItemChange {
+                            kind: ChangeKind::Modified,
+                            item: item.clone(),
+                            fields,
+                        });
+                    }
+                }
+                None => result.schedules.push(ItemChange {
+                    kind: ChangeKind::Removed,
+                    item: format!("{}: {}", item, description),
+                    fields: Vec::new(),
+                }),
+            }
+        }
+        result.schedules.extend(
+            unmatched
+                .into_iter()
+                .map(|(item, description, _)| ItemChange {
+                    kind: ChangeKind::Added,
+                    item: format!("{}: {}", item, description),
+                    fields: Vec::new(),
+                }),
+        );
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::device;
+
+    #[test]
+    fn test_same_setup_with_different_ids() {
+        let front = device(serde_json::json!({
+            "name": "Show",
+            "effects": [{"id": 1, "name": "Warm White", "category": 1, "mode": 0}],
+            "daily": [{"id": 0, "enable": true, "effectId": 1, "repetition": 1}],
+            "combinedEffect": {"effectIds": [1], "interval": 30}
+        }));
+        let back = device(serde_json::json!({
+            "name": "Show",
+            "effects": [{"id": 6, "name": "Warm White", "category": 1, "mode": 0}],
+            "daily": [{"id": 0, "enable": true, "effectId": 6, "repetition": 1}],
+            "combinedEffect": {"effectIds": [6], "interval": 30}
+        }));
+        assert!(diff(&front, &back).is_empty());
+    }
+
+    #[test]
+    fn test_schedules_paired_by_content() {
+        let front = device(serde_json::json!({
+            "effects": [{"id": 1, "name": "Warm White", "category": 1, "mode": 0}],
+            "daily": [
+                {"id": 0, "enable": true, "effectId": 1, "repetition": 1,
+                 "startTime": {"hours": 18, "minutes": 0}, "endTime": {"hours": 23, "minutes": 0}},
+                {"id": 1, "enable": true, "effectId": 1, "repetition": 3,
+                 "startTime": {"hours": 6, "minutes": 0}, "endTime": {"hours": 8, "minutes": 0}}
+            ],
+            "calendar": [{"id": 2, "effectId": 1, "startDate": {"month": 12, "day": 1},
+                          "endDate": {"month": 12, "day": 31}}]
+        }));
+        let back = device(serde_json::json!({
+            "effects": [{"id": 1, "name": "Warm White", "category": 1, "mode": 0}],
+            "daily": [
+                {"id": 0, "enable": true, "effectId": 1, "repetition": 3,
+                 "startTime": {"hours": 6, "minutes": 0}, "endTime": {"hours": 8, "minutes": 0}},
+                {"id": 1, "enable": true, "effectId": 1, "repetition": 1,
+                 "startTime": {"hours": 18, "minutes": 0}, "endTime": {"hours": 23, "minutes": 0}}
+            ],
+            "calendar": [{"id": 7, "effectId": 1, "startDate": {"month": 12, "day": 1},
+                          "endDate": {"month": 12, "day": 31}}]
+        }));
+        assert!(diff(&front, &back).is_empty());
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = device(serde_json::json!({
+            "name": "Front",
+            "colorOrder": 0,
+            "effects": [
+                {"id": 1, "name": "Warm White", "category": 1, "mode": 0, "speed": 100,
+                 "brightness": 200},
+                {"id": 2, "name": "Old", "category": 1, "mode": 3}
+            ],
+            "daily": [{"id": 0, "enable": true, "effectId": 1, "repetition": 1,
+                       "startTime": {"hours": 18, "minutes": 0},
+                       "endTime": {"hours": 23, "minutes": 0}}],
+            "calendar": [{"id": 4, "effectId": 2}]
+        }));
+        let new = device(serde_json::json!({
+            "name": "Front",
+            "colorOrder": 2,
+            "effects": [
+                {"id": 1, "name": "Warm White", "category": 1, "mode": 0, "speed": 100,
+                 "brightness": 150},
+                {"id": 3, "name": "New", "category": 1, "mode": 5}
+            ],
+            "daily": [{"id": 0, "enable": false, "effectId": 3, "repetition": 1,
+                       "startTime": {"hours": 17, "minutes": 30},
+                       "endTime": {"hours": 23, "minutes": 0}}]
+        }));
+
+        let changes = diff(&old, &new);
+        let lines = |changes: &[ItemChange]| {
+            changes
+                .iter()
+                .map(|c| format!("{} {}", c.kind.symbol(), c))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            changes.settings,
+            [FieldChange {
+                field: "color_order".to_string(),
+                from: "0".to_string(),
+                to: "2".to_string(),
+            }]
+        );
+        assert_eq!(
+            lines(&changes.effects),
+            [
+                "~ effect \"Warm White\": brightness 200 -> 150",
+                "- effect \"Old\"",
+                "+ effect \"New\"",
+            ]
+        );
+        assert_eq!(
+            lines(&changes.schedules),
+            [
+                "~ daily 0: effect Warm White -> New, start 18:00 -> 17:30, enabled true -> false",
+                "- calendar 4: 00-00 to 00-00 00:00-00:00: Old",
+            ]
+        );
+    }
+}
@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::models::EffectReference;
+
 #[derive(Error, Debug)]
 pub enum TrimlightError {
     #[error("HTTP request failed: {0}")]
@@ -10,4 +12,24 @@ pub enum TrimlightError {
     AuthError(String),
     #[error("API error: {code} - {message}")]
     ApiError { code: i32, message: String },
+    #[error(
+        "Effect {effect_id} is still used by {}",
+        .references.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+    )]
+    EffectInUse {
+        effect_id: i32,
+        references: Vec<EffectReference>,
+    },
+    #[error("Unexpected fields in response: {}", .0.join(", "))]
+    UnexpectedFields(Vec<String>),
+    #[error("{context}: {source}")]
+    IoError {
+        context: String,
+        source: std::io::Error,
+    },
+}
+
+/// A 400 error for input this library rejects before calling the API
+pub(crate) fn invalid(message: String) -> TrimlightError {
+    TrimlightError::ApiError { code: 400, message }
 }
@@ -0,0 +1,452 @@
+//! Work out what a device in timer mode shows at a given time.
+//!
+//! Overlapping schedules are resolved with the same assumed precedence as
+//! [`find_schedule_conflicts`](crate::find_schedule_conflicts): calendar
+//! schedules override daily schedules, otherwise the most recently started
+//! run wins, and on a tie the schedule listed first. The API documentation
+//! doesn't describe how the device resolves overlaps, so see
+//! [`Precedence`](crate::Precedence) before relying on it.
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use serde::Serialize;
+
+use crate::conflicts::{repetition_weekdays, ScheduleType};
+use crate::interval::{minute_of_day, DateRange, TimeWindow};
+use crate::models::{DeviceSchedules, ScheduleDate};
+
+/// Switch state value for lights that are off
+pub const SWITCH_OFF: i32 = 0;
+/// Switch state value for manual mode, which ignores schedules
+pub const SWITCH_MANUAL: i32 = 1;
+/// Switch state value for timer mode, which follows schedules
+pub const SWITCH_TIMER: i32 = 2;
+
+/// How far ahead to look for the next schedule boundary
+const HORIZON_DAYS: i64 = 2 * 366;
+
+/// How far ahead to look for the next change of the lights
+///
+/// Every schedule runs at least once a year, so a search that finds no change
+/// within this time never will, e.g. when schedules run the same effect
+/// around the clock.
+const TRANSITION_HORIZON_DAYS: i64 = 366;
+
+/// What the lights are doing at a given moment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", rename_all = "lowercase")]
+pub enum LightState {
+    Off,
+    /// Manual mode shows the current effect regardless of schedules
+    Manual,
+    Scheduled {
+        schedule_type: ScheduleType,
+        schedule_id: i32,
+        effect_id: i32,
+    },
+}
+
+impl LightState {
+    /// Effect shown by a schedule, if any
+    pub fn effect_id(&self) -> Option<i32> {
+        match self {
+            LightState::Scheduled { effect_id, .. } => Some(*effect_id),
+            _ => None,
+        }
+    }
+}
+
+/// Kind of change between two light states
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransitionKind {
+    On,
+    Off,
+    EffectChange,
+}
+
+/// A moment at which the lights change
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Transition {
+    pub at: NaiveDateTime,
+    pub from: LightState,
+    pub to: LightState,
+}
+
+impl Transition {
+    pub fn kind(&self) -> TransitionKind {
+        match (self.from.effect_id(), self.to.effect_id()) {
+            (None, _) => TransitionKind::On,
+            (_, None) => TransitionKind::Off,
+            _ => TransitionKind::EffectChange,
+        }
+    }
+}
+
+/// A single run of a schedule on a particular day
+struct Run {
+    schedule_type: ScheduleType,
+    schedule_id: i32,
+    effect_id: i32,
+    /// Position in the device's schedule list, used to break ties
+    index: usize,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+}
+
+impl Run {
+    fn new(
+        schedule_type: ScheduleType,
+        schedule_id: i32,
+        effect_id: i32,
+        index: usize,
+        day: NaiveDate,
+        window: &TimeWindow,
+    ) -> Self {
+        let start =
+            day.and_time(NaiveTime::MIN) + Duration::minutes(minute_of_day(&window.start()) as i64);
+        Self {
+            schedule_type,
+            schedule_id,
+            effect_id,
+            index,
+            start,
+            end: start + Duration::minutes(window.range().len() as i64),
+        }
+    }
+
+    fn state(&self) -> LightState {
+        LightState::Scheduled {
+            schedule_type: self.schedule_type,
+            schedule_id: self.schedule_id,
+            effect_id: self.effect_id,
+        }
+    }
+}
+
+/// Evaluates the schedules of a device
+///
+/// `today` is the date on the device clock and places "today only" daily
+/// schedules. All date times are in the device's local time.
+pub struct ScheduleEvaluator<'a> {
+    schedules: &'a DeviceSchedules,
+    switch_state: i32,
+    today: NaiveDate,
+}
+
+impl<'a> ScheduleEvaluator<'a> {
+    pub fn new(schedules: &'a DeviceSchedules, switch_state: i32, today: NaiveDate) -> Self {
+        Self {
+            schedules,
+            switch_state,
+            today,
+        }
+    }
+
+    /// Schedule runs starting on `day`
+    fn runs_on(&self, day: NaiveDate) -> Vec<Run> {
+        let mut runs = Vec::new();
+
+        for (index, schedule) in self.schedules.daily.iter().enumerate() {
+            let window = TimeWindow::new(&schedule.start_time, &schedule.end_time);
+            let runs_today = match schedule.repetition {
+                0 => day == self.today,
+                repetition => repetition_weekdays(repetition).contains(&day.weekday()),
+            };
+            if schedule.enable && runs_today && !window.is_empty() {
+                runs.push(Run::new(
+                    ScheduleType::Daily,
+                    schedule.id,
+                    schedule.effect_id,
+                    index,
+                    day,
+                    &window,
+                ));
+            }
+        }
+
+        for (index, schedule) in self.schedules.calendar.iter().enumerate() {
+            let window = TimeWindow::new(&schedule.start_time, &schedule.end_time);
+            let runs_today = DateRange::new(&schedule.start_date, &schedule.end_date)
+                .is_some_and(|dates| dates.contains(&ScheduleDate::from(day)));
+            if runs_today && !window.is_empty() {
+                runs.push(Run::new(
+                    ScheduleType::Calendar,
+                    schedule.id,
+                    schedule.effect_id,
+                    index,
+                    day,
+                    &window,
+                ));
+            }
+        }
+
+        runs
+    }
+
+    /// What the lights show at `at`
+    pub fn state_at(&self, at: NaiveDateTime) -> LightState {
+        match self.switch_state {
+            SWITCH_TIMER => {}
+            SWITCH_MANUAL => return LightState::Manual,
+            _ => return LightState::Off,
+        }
+
+        // Runs crossing midnight started the day before
+        let day = at.date();
+        let mut runs = self.runs_on(day - Duration::days(1));
+        runs.extend(self.runs_on(day));
+
+        runs.into_iter()
+            .filter(|run| run.start <= at && at < run.end)
+            .max_by(|a, b| {
+                (a.schedule_type == ScheduleType::Calendar)
+                    .cmp(&(b.schedule_type == ScheduleType::Calendar))
+                    .then(a.start.cmp(&b.start))
+                    .then(b.index.cmp(&a.index))
+            })
+            .map(|run| run.state())
+            .unwrap_or(LightState::Off)
+    }
+
+    /// First moment after `after` at which a schedule starts or ends
+    fn next_boundary(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let first = after.date() - Duration::days(1);
+        let mut next: Option<NaiveDateTime> = None;
+        for offset in 0..=HORIZON_DAYS {
+            let day = first + Duration::days(offset);
+            // Runs starting on later days can't have earlier boundaries
+            if next.is_some_and(|next| next < day.and_time(NaiveTime::MIN)) {
+                break;
+            }
+            let boundaries = self
+                .runs_on(day)
+                .into_iter()
+                .flat_map(|run| [run.start, run.end])
+                .filter(|at| *at > after);
+            next = next.into_iter().chain(boundaries).min();
+        }
+        next
+    }
+
+    /// Changes to the lights after `from`, in order
+    ///
+    /// Only switching on, switching off and changing effect are reported, a
+    /// schedule handing over to another one with the same effect is not.
+    /// Devices that are off or in manual mode never change, and neither do
+    /// lights that run the same effect around the clock. The iterator ends
+    /// when no change is found within a year.
+    pub fn transitions(&self, from: NaiveDateTime) -> Transitions<'_> {
+        Transitions {
+            evaluator: self,
+            current: from,
+            state: self.state_at(from),
+            done: false,
+        }
+    }
+}
+
+/// Iterator over upcoming [`Transition`]s, see [`ScheduleEvaluator::transitions`]
+pub struct Transitions<'a> {
+    evaluator: &'a ScheduleEvaluator<'a>,
+    current: NaiveDateTime,
+    state: LightState,
+    done: bool,
+}
+
+impl Iterator for Transitions<'_> {
+    type Item = Transition;
+
+    fn next(&mut self) -> Option<Transition> {
+        if self.done || self.evaluator.switch_state != SWITCH_TIMER {
+            return None;
+        }
+
+        let limit = self.current + Duration::days(TRANSITION_HORIZON_DAYS);
+        loop {
+            self.current = match self.evaluator.next_boundary(self.current) {
+                Some(at) if at <= limit => at,
+                _ => {
+                    self.done = true;
+                    return None;
+                }
+            };
+            let state = self.evaluator.state_at(self.current);
+            let changed = state.effect_id() != self.state.effect_id();
+            let from = std::mem::replace(&mut self.state, state);
+            if changed {
+                return Some(Transition {
+                    at: self.current,
+                    from,
+                    to: self.state.clone(),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::DailySchedule;
+    use crate::test_util::{calendar, daily, today};
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn effect_at(schedules: &DeviceSchedules, time: &str) -> Option<i32> {
+        ScheduleEvaluator::new(schedules, SWITCH_TIMER, today())
+            .state_at(at(time))
+            .effect_id()
+    }
+
+    #[test]
+    fn test_switch_state() {
+        let schedules = DeviceSchedules {
+            daily: vec![daily(0, 1, (0, 0), (23, 59))],
+            ..Default::default()
+        };
+        let time = at("2026-12-24 21:00");
+
+        let evaluator = ScheduleEvaluator::new(&schedules, SWITCH_OFF, today());
+        assert_eq!(evaluator.state_at(time), LightState::Off);
+        assert_eq!(evaluator.transitions(time).next(), None);
+
+        let evaluator = ScheduleEvaluator::new(&schedules, SWITCH_MANUAL, today());
+        assert_eq!(evaluator.state_at(time), LightState::Manual);
+    }
+
+    #[test]
+    fn test_daily_repetition() {
+        let schedules = DeviceSchedules {
+            daily: vec![daily(0, 2, (18, 0), (22, 0)), daily(1, 0, (6, 0), (7, 0))],
+            ..Default::default()
+        };
+
+        // Thursday
+        assert_eq!(effect_at(&schedules, "2026-12-24 21:00"), Some(10));
+        assert_eq!(effect_at(&schedules, "2026-12-24 22:00"), None);
+        assert_eq!(effect_at(&schedules, "2026-12-24 17:59"), None);
+        // Saturday
+        assert_eq!(effect_at(&schedules, "2026-12-26 21:00"), None);
+
+        // "Today only" runs on the device's current date
+        assert_eq!(effect_at(&schedules, "2026-12-19 06:30"), Some(11));
+        assert_eq!(effect_at(&schedules, "2026-12-20 06:30"), None);
+    }
+
+    #[test]
+    fn test_overnight_runs_from_previous_day() {
+        let schedules = DeviceSchedules {
+            daily: vec![daily(0, 2, (20, 0), (2, 0))],
+            ..Default::default()
+        };
+
+        // Friday night continues into Saturday morning
+        assert_eq!(effect_at(&schedules, "2026-12-26 01:00"), Some(10));
+        // Sunday night doesn't run, so Monday morning is dark
+        assert_eq!(effect_at(&schedules, "2026-12-28 01:00"), None);
+    }
+
+    #[test]
+    fn test_precedence() {
+        let schedules = DeviceSchedules {
+            daily: vec![daily(0, 1, (17, 0), (23, 0)), daily(1, 1, (20, 0), (22, 0))],
+            calendar: vec![calendar(2, (12, 24), (12, 26))],
+            ..Default::default()
+        };
+
+        // The later start takes over
+        assert_eq!(effect_at(&schedules, "2026-12-20 21:00"), Some(11));
+        assert_eq!(effect_at(&schedules, "2026-12-20 22:30"), Some(10));
+        // Calendar schedules override daily ones
+        assert_eq!(effect_at(&schedules, "2026-12-24 21:00"), Some(22));
+        assert_eq!(effect_at(&schedules, "2026-12-24 17:30"), Some(10));
+    }
+
+    #[test]
+    fn test_calendar_new_year() {
+        let schedules = DeviceSchedules {
+            calendar: vec![calendar(0, (12, 15), (1, 5))],
+            ..Default::default()
+        };
+        assert_eq!(effect_at(&schedules, "2027-01-01 19:00"), Some(20));
+        assert_eq!(effect_at(&schedules, "2027-01-06 19:00"), None);
+    }
+
+    #[test]
+    fn test_transitions() {
+        let schedules = DeviceSchedules {
+            daily: vec![
+                daily(0, 1, (17, 0), (23, 0)),
+                daily(1, 1, (20, 0), (22, 0)),
+                // Same effect as schedule 0, so handing over isn't a change
+                DailySchedule {
+                    effect_id: 10,
+                    ..daily(2, 1, (23, 0), (1, 0))
+                },
+            ],
+            ..Default::default()
+        };
+
+        let evaluator = ScheduleEvaluator::new(&schedules, SWITCH_TIMER, today());
+        let transitions: Vec<(NaiveDateTime, TransitionKind, Option<i32>)> = evaluator
+            .transitions(at("2026-12-20 12:00"))
+            .take(5)
+            .map(|t| (t.at, t.kind(), t.to.effect_id()))
+            .collect();
+        assert_eq!(
+            transitions,
+            vec![
+                (at("2026-12-20 17:00"), TransitionKind::On, Some(10)),
+                (
+                    at("2026-12-20 20:00"),
+                    TransitionKind::EffectChange,
+                    Some(11)
+                ),
+                (
+                    at("2026-12-20 22:00"),
+                    TransitionKind::EffectChange,
+                    Some(10)
+                ),
+                (at("2026-12-21 01:00"), TransitionKind::Off, None),
+                (at("2026-12-21 17:00"), TransitionKind::On, Some(10)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_transitions_yearly() {
+        let schedules = DeviceSchedules {
+            calendar: vec![calendar(0, (7, 4), (7, 4))],
+            ..Default::default()
+        };
+
+        let evaluator = ScheduleEvaluator::new(&schedules, SWITCH_TIMER, today());
+        let mut transitions = evaluator.transitions(at("2026-12-20 12:00"));
+        assert_eq!(transitions.next().unwrap().at, at("2027-07-04 18:00"));
+        assert_eq!(transitions.next().unwrap().at, at("2027-07-04 23:00"));
+        assert_eq!(transitions.next().unwrap().at, at("2028-07-04 18:00"));
+
+        let empty = DeviceSchedules::default();
+        let evaluator = ScheduleEvaluator::new(&empty, SWITCH_TIMER, today());
+        assert_eq!(evaluator.transitions(at("2026-12-20 12:00")).next(), None);
+    }
+
+    #[test]
+    fn test_transitions_same_effect_all_day() {
+        let mut night = daily(0, 1, (18, 0), (6, 0));
+        let mut day = daily(1, 1, (6, 0), (18, 0));
+        night.effect_id = 7;
+        day.effect_id = 7;
+        let schedules = DeviceSchedules {
+            daily: vec![night, day],
+            ..Default::default()
+        };
+
+        let evaluator = ScheduleEvaluator::new(&schedules, SWITCH_TIMER, today());
+        let mut transitions = evaluator.transitions(at("2026-12-20 12:00"));
+        assert_eq!(transitions.next(), None);
+        assert_eq!(transitions.next(), None);
+    }
+}
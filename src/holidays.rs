@@ -0,0 +1,618 @@
+//! Dates of holidays that move from year to year.
+//!
+//! Calendar schedules repeat every year on the same month and day, so
+//! holidays such as Thanksgiving or Diwali need new schedules each year.
+//! Lunar dates use the new moon algorithm from Meeus, "Astronomical
+//! Algorithms", chapter 49, which is accurate to a few minutes.
+
+use chrono::{Duration, NaiveDate, NaiveDateTime, Weekday};
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::{invalid, TrimlightError};
+use crate::models::{CalendarSchedule, ScheduleDate, ScheduleTime};
+
+/// Mean length of a lunar month in days
+const SYNODIC_MONTH: f64 = 29.530588861;
+/// Julian day of 1970-01-01 00:00 UTC
+const UNIX_EPOCH_JD: f64 = 2440587.5;
+
+/// First year holiday dates are computed for, the first full year of the
+/// Gregorian calendar the rules assume
+pub const MIN_HOLIDAY_YEAR: i32 = 1583;
+/// Last year holiday dates are computed for; the lunar dates drift further
+/// from the present
+pub const MAX_HOLIDAY_YEAR: i32 = 4000;
+
+fn check_holiday_year(year: i32) -> Result<(), TrimlightError> {
+    if !(MIN_HOLIDAY_YEAR..=MAX_HOLIDAY_YEAR).contains(&year) {
+        return Err(invalid(format!(
+            "Invalid year {}. Holidays are computed for {} to {}",
+            year, MIN_HOLIDAY_YEAR, MAX_HOLIDAY_YEAR
+        )));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Holiday {
+    Easter,
+    MothersDay,
+    MemorialDay,
+    FathersDay,
+    LaborDay,
+    Thanksgiving,
+    Hanukkah,
+    Diwali,
+    LunarNewYear,
+}
+
+impl Holiday {
+    pub const ALL: [Holiday; 9] = [
+        Holiday::LunarNewYear,
+        Holiday::Easter,
+        Holiday::MothersDay,
+        Holiday::MemorialDay,
+        Holiday::FathersDay,
+        Holiday::LaborDay,
+        Holiday::Diwali,
+        Holiday::Thanksgiving,
+        Holiday::Hanukkah,
+    ];
+
+    /// Identifier used on the command line, e.g. `lunar-new-year`
+    pub fn key(&self) -> &'static str {
+        match self {
+            Holiday::Easter => "easter",
+            Holiday::MothersDay => "mothers-day",
+            Holiday::MemorialDay => "memorial-day",
+            Holiday::FathersDay => "fathers-day",
+            Holiday::LaborDay => "labor-day",
+            Holiday::Thanksgiving => "thanksgiving",
+            Holiday::Hanukkah => "hanukkah",
+            Holiday::Diwali => "diwali",
+            Holiday::LunarNewYear => "lunar-new-year",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Holiday::Easter => "Easter",
+            Holiday::MothersDay => "Mother's Day",
+            Holiday::MemorialDay => "Memorial Day",
+            Holiday::FathersDay => "Father's Day",
+            Holiday::LaborDay => "Labor Day",
+            Holiday::Thanksgiving => "Thanksgiving",
+            Holiday::Hanukkah => "Hanukkah",
+            Holiday::Diwali => "Diwali",
+            Holiday::LunarNewYear => "Lunar New Year",
+        }
+    }
+
+    /// Days on which the holiday is lit in `year`
+    ///
+    /// Most holidays are a single day. Hanukkah covers its eight nights
+    /// starting on the evening of the first candle, Diwali the five days from
+    /// Dhanteras to Bhai Dooj, and Lunar New Year the fifteen days up to the
+    /// Lantern Festival. Hanukkah and Lunar New Year can run into the next
+    /// year. Fails for years outside [`MIN_HOLIDAY_YEAR`] to
+    /// [`MAX_HOLIDAY_YEAR`].
+    pub fn dates(&self, year: i32) -> Result<HolidayDates, TrimlightError> {
+        check_holiday_year(year)?;
+        let (start, days) = match self {
+            Holiday::Easter => (easter(year), 1),
+            Holiday::MothersDay => (nth_weekday(year, 5, Weekday::Sun, 2), 1),
+            Holiday::MemorialDay => (last_weekday(year, 5, Weekday::Mon), 1),
+            Holiday::FathersDay => (nth_weekday(year, 6, Weekday::Sun, 3), 1),
+            Holiday::LaborDay => (nth_weekday(year, 9, Weekday::Mon, 1), 1),
+            Holiday::Thanksgiving => (nth_weekday(year, 11, Weekday::Thu, 4), 1),
+            Holiday::Hanukkah => (hanukkah(year), 8),
+            Holiday::Diwali => (diwali(year) - Duration::days(2), 5),
+            Holiday::LunarNewYear => (lunar_new_year(year), 15),
+        };
+        Ok(HolidayDates {
+            holiday: *self,
+            start,
+            end: start + Duration::days(days - 1),
+        })
+    }
+}
+
+impl fmt::Display for Holiday {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Holiday {
+    type Err = TrimlightError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let key = s
+            .trim()
+            .to_lowercase()
+            .replace([' ', '_'], "-")
+            .replace('\'', "");
+        Holiday::ALL
+            .into_iter()
+            .find(|holiday| holiday.key() == key)
+            .ok_or_else(|| TrimlightError::ApiError {
+                code: 400,
+                message: format!(
+                    "Unknown holiday '{}'. Supported holidays: {}",
+                    s,
+                    Holiday::ALL.map(|h| h.key()).join(", ")
+                ),
+            })
+    }
+}
+
+/// First and last day of a holiday, both inclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct HolidayDates {
+    pub holiday: Holiday,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+/// Groups of holidays to schedule together
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HolidayPreset {
+    /// US holidays that move: Easter, Mother's Day, Memorial Day, Father's
+    /// Day, Labor Day and Thanksgiving
+    Us,
+    Christian,
+    Jewish,
+    Hindu,
+    Chinese,
+    All,
+}
+
+impl HolidayPreset {
+    pub fn holidays(&self) -> Vec<Holiday> {
+        match self {
+            HolidayPreset::Us => vec![
+                Holiday::Easter,
+                Holiday::MothersDay,
+                Holiday::MemorialDay,
+                Holiday::FathersDay,
+                Holiday::LaborDay,
+                Holiday::Thanksgiving,
+            ],
+            HolidayPreset::Christian => vec![Holiday::Easter],
+            HolidayPreset::Jewish => vec![Holiday::Hanukkah],
+            HolidayPreset::Hindu => vec![Holiday::Diwali],
+            HolidayPreset::Chinese => vec![Holiday::LunarNewYear],
+            HolidayPreset::All => Holiday::ALL.to_vec(),
+        }
+    }
+}
+
+impl FromStr for HolidayPreset {
+    type Err = TrimlightError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "us" => Ok(HolidayPreset::Us),
+            "christian" => Ok(HolidayPreset::Christian),
+            "jewish" => Ok(HolidayPreset::Jewish),
+            "hindu" => Ok(HolidayPreset::Hindu),
+            "chinese" | "lunar" => Ok(HolidayPreset::Chinese),
+            "all" => Ok(HolidayPreset::All),
+            _ => Err(TrimlightError::ApiError {
+                code: 400,
+                message: format!(
+                    "Unknown preset '{}'. Supported presets: us, christian, jewish, hindu, chinese, all",
+                    s
+                ),
+            }),
+        }
+    }
+}
+
+/// Changes needed to bring calendar schedules up to date for a year of holidays
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HolidayPlan {
+    pub add: Vec<CalendarSchedule>,
+    /// Schedules left over from the previous year, or for this year's dates
+    /// at different times
+    pub remove: Vec<CalendarSchedule>,
+    pub unchanged: Vec<CalendarSchedule>,
+}
+
+/// Plan calendar schedules for holidays in `year`
+///
+/// Each holiday is paired with the ID of the effect to run. A calendar
+/// schedule running that effect on the holiday's dates of the previous year
+/// is replaced, so running the plan again every year keeps a single entry
+/// per holiday. Other calendar schedules are left alone. Fails for years
+/// outside [`MIN_HOLIDAY_YEAR`] to [`MAX_HOLIDAY_YEAR`].
+pub fn plan_holiday_schedules(
+    year: i32,
+    holidays: &[(Holiday, i32)],
+    calendar: &[CalendarSchedule],
+    start_time: &ScheduleTime,
+    end_time: &ScheduleTime,
+) -> Result<HolidayPlan, TrimlightError> {
+    let mut plan = HolidayPlan::default();
+    for (holiday, effect_id) in holidays {
+        let dates = holiday.dates(year)?;
+        // Nothing was scheduled before the first supported year
+        let previous = holiday.dates(year - 1).ok();
+        let wanted = CalendarSchedule {
+            effect_id: *effect_id,
+            start_date: ScheduleDate::from(dates.start),
+            end_date: ScheduleDate::from(dates.end),
+            start_time: start_time.clone(),
+            end_time: end_time.clone(),
+            ..Default::default()
+        };
+
+        let mut found = false;
+        for schedule in calendar.iter().filter(|s| s.effect_id == *effect_id) {
+            let on = |dates: &HolidayDates| {
+                schedule.start_date == ScheduleDate::from(dates.start)
+                    && schedule.end_date == ScheduleDate::from(dates.end)
+            };
+            let same_times =
+                schedule.start_time == wanted.start_time && schedule.end_time == wanted.end_time;
+            if on(&dates) && same_times && !found {
+                found = true;
+                plan.unchanged.push(schedule.clone());
+            } else if on(&dates) || previous.as_ref().is_some_and(on) {
+                plan.remove.push(schedule.clone());
+            }
+        }
+        if !found {
+            plan.add.push(wanted);
+        }
+    }
+    Ok(plan)
+}
+
+/// Easter Sunday, using the anonymous Gregorian algorithm
+fn easter(year: i32) -> NaiveDate {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32).expect("valid date")
+}
+
+fn nth_weekday(year: i32, month: u32, weekday: Weekday, n: u8) -> NaiveDate {
+    NaiveDate::from_weekday_of_month_opt(year, month, weekday, n).expect("valid date")
+}
+
+fn last_weekday(year: i32, month: u32, weekday: Weekday) -> NaiveDate {
+    NaiveDate::from_weekday_of_month_opt(year, month, weekday, 5)
+        .unwrap_or_else(|| nth_weekday(year, month, weekday, 4))
+}
+
+/// Days from the Hebrew epoch to Rosh Hashanah of `year`, before the
+/// postponements that depend on the length of the neighbouring years
+fn hebrew_elapsed_days(year: i64) -> i64 {
+    let months = (235 * year - 234).div_euclid(19);
+    let parts = 12084 + 13753 * months;
+    let day = 29 * months + parts.div_euclid(25920);
+    if (3 * (day + 1)).rem_euclid(7) < 3 {
+        day + 1
+    } else {
+        day
+    }
+}
+
+/// Rosh Hashanah of a Hebrew year, in days since 0001-01-01 (day 1)
+fn hebrew_new_year(year: i64) -> i64 {
+    let (previous, current, next) = (
+        hebrew_elapsed_days(year - 1),
+        hebrew_elapsed_days(year),
+        hebrew_elapsed_days(year + 1),
+    );
+    let correction = if next - current == 356 {
+        2
+    } else if current - previous == 382 {
+        1
+    } else {
+        0
+    };
+    -1373427 + current + correction
+}
+
+/// Day of the first Hanukkah candle, the evening before 25 Kislev
+fn hanukkah(year: i32) -> NaiveDate {
+    let hebrew_year = year as i64 + 3761;
+    let new_year = hebrew_new_year(hebrew_year);
+    let year_length = hebrew_new_year(hebrew_year + 1) - new_year;
+    // Cheshvan has 30 days in complete years of 355 or 385 days
+    let cheshvan = if year_length % 10 == 5 { 30 } else { 29 };
+    let first_candle = new_year + 30 + cheshvan + 23;
+    NaiveDate::from_num_days_from_ce_opt(first_candle as i32).expect("valid date")
+}
+
+/// First day of the Chinese new year, the new moon between 01-21 and 02-20
+/// in Beijing
+fn lunar_new_year(year: i32) -> NaiveDate {
+    let from = NaiveDate::from_ymd_opt(year, 1, 21).expect("valid date");
+    new_moon_on_or_after(from, 8 * 60).date()
+}
+
+/// Lakshmi Puja, the evening of the new moon between 10-17 and 11-15 in India
+///
+/// The puja falls on the day whose evening is in the last lunar day before
+/// the new moon, so a new moon before 18:00 moves it to the day before.
+fn diwali(year: i32) -> NaiveDate {
+    let from = NaiveDate::from_ymd_opt(year, 10, 17).expect("valid date");
+    let new_moon = new_moon_on_or_after(from, 5 * 60 + 30);
+    let evening = new_moon.date().and_hms_opt(18, 0, 0).expect("valid time");
+    if new_moon < evening {
+        new_moon.date() - Duration::days(1)
+    } else {
+        new_moon.date()
+    }
+}
+
+/// Local time of the first new moon falling on or after `date`
+fn new_moon_on_or_after(date: NaiveDate, utc_offset_minutes: i32) -> NaiveDateTime {
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1)
+        .expect("valid date")
+        .and_hms_opt(0, 0, 0)
+        .expect("valid time");
+    let local_time = |jde: f64| {
+        let minutes = (jde - UNIX_EPOCH_JD) * 24.0 * 60.0 + utc_offset_minutes as f64;
+        epoch + Duration::minutes(minutes.round() as i64)
+    };
+
+    let jd = UNIX_EPOCH_JD + (date - epoch.date()).num_days() as f64;
+    let mut k = ((jd - 2451550.09766) / SYNODIC_MONTH).floor() - 1.0;
+    loop {
+        let time = local_time(new_moon(k));
+        if time.date() >= date {
+            return time;
+        }
+        k += 1.0;
+    }
+}
+
+/// Julian ephemeris day of new moon number `k`, counted from January 2000
+fn new_moon(k: f64) -> f64 {
+    let t = k / 1236.85;
+    let jde = 2451550.09766 + SYNODIC_MONTH * k + 0.00015437 * t * t - 0.000000150 * t.powi(3)
+        + 0.00000000073 * t.powi(4);
+    let e = 1.0 - 0.002516 * t - 0.0000074 * t * t;
+    let m = (2.5534 + 29.10535670 * k - 0.0000014 * t * t - 0.00000011 * t.powi(3)).to_radians();
+    let mp = (201.5643 + 385.81693528 * k + 0.0107582 * t * t + 0.00001238 * t.powi(3)
+        - 0.000000058 * t.powi(4))
+    .to_radians();
+    let f = (160.7108 + 390.67050284 * k - 0.0016118 * t * t - 0.00000227 * t.powi(3)
+        + 0.000000011 * t.powi(4))
+    .to_radians();
+    let omega =
+        (124.7746 - 1.56375588 * k + 0.0020672 * t * t + 0.00000215 * t.powi(3)).to_radians();
+
+    let correction = -0.40720 * mp.sin()
+        + 0.17241 * e * m.sin()
+        + 0.01608 * (2.0 * mp).sin()
+        + 0.01039 * (2.0 * f).sin()
+        + 0.00739 * e * (mp - m).sin()
+        - 0.00514 * e * (mp + m).sin()
+        + 0.00208 * e * e * (2.0 * m).sin()
+        - 0.00111 * (mp - 2.0 * f).sin()
+        - 0.00057 * (mp + 2.0 * f).sin()
+        + 0.00056 * e * (2.0 * mp + m).sin()
+        - 0.00042 * (3.0 * mp).sin()
+        + 0.00042 * e * (m + 2.0 * f).sin()
+        + 0.00038 * e * (m - 2.0 * f).sin()
+        - 0.00024 * e * (2.0 * mp - m).sin()
+        - 0.00017 * omega.sin()
+        - 0.00007 * (mp + 2.0 * m).sin()
+        + 0.00004 * (2.0 * mp - 2.0 * f).sin()
+        + 0.00004 * (3.0 * m).sin()
+        + 0.00003 * (mp + m - 2.0 * f).sin()
+        + 0.00003 * (2.0 * mp + 2.0 * f).sin()
+        - 0.00003 * (mp + m + 2.0 * f).sin()
+        + 0.00003 * (mp - m + 2.0 * f).sin()
+        - 0.00002 * (mp - m - 2.0 * f).sin()
+        - 0.00002 * (3.0 * mp + m).sin()
+        + 0.00002 * (4.0 * mp).sin();
+    jde + correction
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn starts(holiday: Holiday, expected: &[(i32, u32, u32)]) {
+        for &(year, month, day) in expected {
+            assert_eq!(
+                holiday.dates(year).unwrap().start,
+                date(year, month, day),
+                "{} {}",
+                holiday,
+                year
+            );
+        }
+    }
+
+    #[test]
+    fn test_fixed_rule_holidays() {
+        starts(
+            Holiday::Easter,
+            &[
+                (2024, 3, 31),
+                (2025, 4, 20),
+                (2026, 4, 5),
+                (2027, 3, 28),
+                (2038, 4, 25),
+            ],
+        );
+        starts(
+            Holiday::Thanksgiving,
+            &[(2025, 11, 27), (2026, 11, 26), (2027, 11, 25)],
+        );
+        starts(Holiday::MemorialDay, &[(2026, 5, 25), (2027, 5, 31)]);
+        starts(Holiday::MothersDay, &[(2027, 5, 9)]);
+        starts(Holiday::FathersDay, &[(2027, 6, 20)]);
+        starts(Holiday::LaborDay, &[(2027, 9, 6)]);
+    }
+
+    #[test]
+    fn test_lunar_holidays() {
+        starts(
+            Holiday::Hanukkah,
+            &[
+                (2023, 12, 7),
+                (2024, 12, 25),
+                (2025, 12, 14),
+                (2026, 12, 4),
+                (2027, 12, 24),
+            ],
+        );
+        starts(
+            Holiday::LunarNewYear,
+            &[
+                (2024, 2, 10),
+                (2025, 1, 29),
+                (2026, 2, 17),
+                (2027, 2, 6),
+                (2028, 1, 26),
+                (2033, 1, 31),
+                (2034, 2, 19),
+            ],
+        );
+        // Diwali spans two days before and after Lakshmi Puja
+        starts(
+            Holiday::Diwali,
+            &[
+                (2022, 10, 22),
+                (2023, 11, 10),
+                (2025, 10, 18),
+                (2026, 11, 6),
+                (2027, 10, 27),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_holiday_ranges() {
+        let hanukkah = Holiday::Hanukkah.dates(2024).unwrap();
+        assert_eq!(hanukkah.end, date(2025, 1, 1));
+        let new_year = Holiday::LunarNewYear.dates(2027).unwrap();
+        assert_eq!(new_year.end, date(2027, 2, 20));
+        let thanksgiving = Holiday::Thanksgiving.dates(2027).unwrap();
+        assert_eq!(thanksgiving.start, thanksgiving.end);
+    }
+
+    #[test]
+    fn test_unsupported_years() {
+        for year in [
+            i32::MIN,
+            MIN_HOLIDAY_YEAR - 1,
+            MAX_HOLIDAY_YEAR + 1,
+            300000,
+            i32::MAX,
+        ] {
+            for holiday in Holiday::ALL {
+                assert!(holiday.dates(year).is_err(), "{} {}", holiday, year);
+            }
+        }
+        for year in [MIN_HOLIDAY_YEAR, MAX_HOLIDAY_YEAR] {
+            for holiday in Holiday::ALL {
+                assert!(holiday.dates(year).is_ok(), "{} {}", holiday, year);
+            }
+        }
+
+        let evening = ScheduleTime::new(18, 0).unwrap();
+        let night = ScheduleTime::new(23, 0).unwrap();
+        let all: Vec<(Holiday, i32)> = Holiday::ALL.iter().map(|h| (*h, 1)).collect();
+        let plan = plan_holiday_schedules(MIN_HOLIDAY_YEAR, &all, &[], &evening, &night).unwrap();
+        assert_eq!(plan.add.len(), all.len());
+        assert!(matches!(
+            plan_holiday_schedules(i32::MIN, &all, &[], &evening, &night),
+            Err(TrimlightError::ApiError { code: 400, message })
+                if message == "Invalid year -2147483648. Holidays are computed for 1583 to 4000"
+        ));
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "Lunar New Year".parse::<Holiday>().unwrap(),
+            Holiday::LunarNewYear
+        );
+        assert_eq!(
+            "mothers-day".parse::<Holiday>().unwrap(),
+            Holiday::MothersDay
+        );
+        assert_eq!(
+            "Mother's Day".parse::<Holiday>().unwrap(),
+            Holiday::MothersDay
+        );
+        assert!("halloween".parse::<Holiday>().is_err());
+        assert_eq!("US".parse::<HolidayPreset>().unwrap(), HolidayPreset::Us);
+        assert!("eu".parse::<HolidayPreset>().is_err());
+    }
+
+    #[test]
+    fn test_plan_replaces_last_year() {
+        let evening = ScheduleTime::new(18, 0).unwrap();
+        let night = ScheduleTime::new(23, 0).unwrap();
+        let schedule = |id, effect_id, dates: HolidayDates| CalendarSchedule {
+            id,
+            effect_id,
+            start_date: ScheduleDate::from(dates.start),
+            end_date: ScheduleDate::from(dates.end),
+            start_time: evening.clone(),
+            end_time: night.clone(),
+            ..Default::default()
+        };
+        let calendar = vec![
+            // Last year's Thanksgiving
+            schedule(1, 7, Holiday::Thanksgiving.dates(2026).unwrap()),
+            // This year's Hanukkah, already in place
+            schedule(2, 8, Holiday::Hanukkah.dates(2027).unwrap()),
+            // Same effect on unrelated dates is kept
+            schedule(3, 7, Holiday::Easter.dates(2026).unwrap()),
+        ];
+
+        let plan = plan_holiday_schedules(
+            2027,
+            &[(Holiday::Thanksgiving, 7), (Holiday::Hanukkah, 8)],
+            &calendar,
+            &evening,
+            &night,
+        )
+        .unwrap();
+        assert_eq!(plan.remove.iter().map(|s| s.id).collect::<Vec<_>>(), [1]);
+        assert_eq!(plan.unchanged.iter().map(|s| s.id).collect::<Vec<_>>(), [2]);
+        assert_eq!(plan.add.len(), 1);
+        assert_eq!(plan.add[0].effect_id, 7);
+        assert_eq!(
+            plan.add[0].start_date,
+            ScheduleDate::from(date(2027, 11, 25))
+        );
+
+        // Different times on this year's dates are replaced too
+        let later = ScheduleTime::new(19, 0).unwrap();
+        let plan =
+            plan_holiday_schedules(2027, &[(Holiday::Hanukkah, 8)], &calendar, &later, &night)
+                .unwrap();
+        assert_eq!(plan.remove.iter().map(|s| s.id).collect::<Vec<_>>(), [2]);
+        assert_eq!(plan.add.len(), 1);
+    }
+}
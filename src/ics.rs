@@ -0,0 +1,1146 @@
+//! iCalendar (RFC 5545) export and import of device schedules.
+//!
+//! Times are written as floating local times, since the device runs its
+//! schedules on its own clock without a time zone. Imported times are taken
+//! as device local time for the same reason.
+
+use chrono::{
+    DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Utc, Weekday,
+};
+use serde::Serialize;
+
+use crate::conflicts::{repetition_weekdays, ScheduleType};
+use crate::error::TrimlightError;
+use crate::interval::{
+    date_of_year, day_of_year, minute_of_day, DateRange, TimeWindow, DAYS_PER_YEAR,
+};
+use crate::models::{DeviceSchedules, Effect, ScheduleDate, ScheduleTime};
+
+const PRODUCT_ID: &str = "-//trimlight//trimlight-cli//EN";
+/// Lines longer than this many octets are folded
+const MAX_LINE_LEN: usize = 75;
+
+/// Escape a value for use in a TEXT property
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Write a content line, folding it so no line exceeds 75 octets
+fn push_line(out: &mut String, line: &str) {
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > MAX_LINE_LEN {
+            out.push_str("\r\n ");
+            len = 1;
+        }
+        out.push(c);
+        len += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+fn format_datetime(datetime: NaiveDateTime) -> String {
+    datetime.format("%Y%m%dT%H%M%S").to_string()
+}
+
+fn weekday_code(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+/// One VEVENT before it is written out
+struct Event {
+    uid: String,
+    schedule_type: ScheduleType,
+    schedule_id: i32,
+    effect_id: i32,
+    summary: String,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    rrule: Option<String>,
+    enabled: bool,
+}
+
+impl Event {
+    fn write(&self, out: &mut String, stamp: &str) {
+        push_line(out, "BEGIN:VEVENT");
+        push_line(out, &format!("UID:{}", self.uid));
+        push_line(out, &format!("DTSTAMP:{}", stamp));
+        push_line(out, &format!("DTSTART:{}", format_datetime(self.start)));
+        push_line(out, &format!("DTEND:{}", format_datetime(self.end)));
+        if let Some(rrule) = &self.rrule {
+            push_line(out, &format!("RRULE:{}", rrule));
+        }
+        push_line(out, &format!("SUMMARY:{}", escape_text(&self.summary)));
+        push_line(
+            out,
+            &format!(
+                "DESCRIPTION:{}",
+                escape_text(&format!(
+                    "Trimlight {} schedule {}, effect {}",
+                    self.schedule_type, self.schedule_id, self.effect_id
+                ))
+            ),
+        );
+        if !self.enabled {
+            push_line(out, "STATUS:CANCELLED");
+        }
+        push_line(
+            out,
+            &format!("X-TRIMLIGHT-SCHEDULE-TYPE:{}", self.schedule_type),
+        );
+        push_line(
+            out,
+            &format!("X-TRIMLIGHT-SCHEDULE-ID:{}", self.schedule_id),
+        );
+        push_line(out, &format!("X-TRIMLIGHT-EFFECT-ID:{}", self.effect_id));
+        push_line(out, "END:VEVENT");
+    }
+}
+
+/// Start and end of a run starting on `date`
+fn run_times(
+    date: NaiveDate,
+    start: &ScheduleTime,
+    end: &ScheduleTime,
+) -> (NaiveDateTime, NaiveDateTime) {
+    let window = TimeWindow::new(start, end);
+    let start = date.and_time(NaiveTime::MIN) + Duration::minutes(minute_of_day(start) as i64);
+    (
+        start,
+        start + Duration::minutes(window.range().len() as i64),
+    )
+}
+
+/// Render schedules as an iCalendar document
+///
+/// Daily schedules repeat with a daily or weekly RRULE, "today only"
+/// schedules become a single event on `today`. Calendar schedules become
+/// yearly events, one per month they cover, since a single RRULE cannot
+/// describe an arbitrary date range. Events are named after their effect and
+/// disabled schedules are marked as cancelled. Schedules with an empty time
+/// window or invalid dates are left out.
+///
+/// Event times are floating, i.e. in the device's local time. `created` is
+/// written as the DTSTAMP of every event and is usually `Utc::now()`.
+pub fn export_ics(
+    schedules: &DeviceSchedules,
+    effects: &[Effect],
+    today: NaiveDate,
+    created: DateTime<Utc>,
+) -> String {
+    let effect_name = |effect_id: i32| {
+        effects
+            .iter()
+            .find(|e| e.id == effect_id)
+            .map(|e| e.name.clone())
+            .unwrap_or_else(|| format!("Effect {}", effect_id))
+    };
+    let mut events = Vec::new();
+
+    for schedule in &schedules.daily {
+        if TimeWindow::new(&schedule.start_time, &schedule.end_time).is_empty() {
+            continue;
+        }
+        let (first_date, rrule) = match schedule.repetition {
+            0 => (today, None),
+            1 => (today, Some("FREQ=DAILY".to_string())),
+            2 | 3 => {
+                let weekdays = repetition_weekdays(schedule.repetition);
+                let first_date = today
+                    .iter_days()
+                    .find(|d| weekdays.contains(&d.weekday()))
+                    .expect("a matching weekday within a week");
+                let days: Vec<&str> = weekdays.into_iter().map(weekday_code).collect();
+                (
+                    first_date,
+                    Some(format!("FREQ=WEEKLY;BYDAY={}", days.join(","))),
+                )
+            }
+            _ => continue,
+        };
+        let (start, end) = run_times(first_date, &schedule.start_time, &schedule.end_time);
+        events.push(Event {
+            uid: format!("daily-{}@trimlight", schedule.id),
+            schedule_type: ScheduleType::Daily,
+            schedule_id: schedule.id,
+            effect_id: schedule.effect_id,
+            summary: effect_name(schedule.effect_id),
+            start,
+            end,
+            rrule,
+            enabled: schedule.enable,
+        });
+    }
+
+    for schedule in &schedules.calendar {
+        let Some(range) = DateRange::new(&schedule.start_date, &schedule.end_date) else {
+            continue;
+        };
+        if TimeWindow::new(&schedule.start_time, &schedule.end_time).is_empty() {
+            continue;
+        }
+        let event = |uid: String, first_date: NaiveDate, rrule: String| {
+            let (start, end) = run_times(first_date, &schedule.start_time, &schedule.end_time);
+            Event {
+                uid,
+                schedule_type: ScheduleType::Calendar,
+                schedule_id: schedule.id,
+                effect_id: schedule.effect_id,
+                summary: effect_name(schedule.effect_id),
+                start,
+                end,
+                rrule: Some(rrule),
+                enabled: true,
+            }
+        };
+
+        if range.is_all_year() {
+            events.push(event(
+                format!("calendar-{}@trimlight", schedule.id),
+                today,
+                "FREQ=DAILY".to_string(),
+            ));
+            continue;
+        }
+
+        // Month days covered by the range, grouped by month in order
+        let mut months: Vec<(u32, Vec<u32>)> = Vec::new();
+        for ordinal in range.range().start()..range.range().start() + range.range().len() {
+            let date = date_of_year(ordinal);
+            let (month, day) = (date.month as u32, date.day as u32);
+            match months.last_mut() {
+                Some((last, days)) if *last == month => days.push(day),
+                _ => months.push((month, vec![day])),
+            }
+        }
+
+        let next_dates = range.next_dates(today);
+        for (month, days) in months {
+            // Only 02-29 is missing from the next run when it isn't a leap year
+            let first_date = next_dates
+                .iter()
+                .find(|d| d.month() == month)
+                .copied()
+                .or_else(|| {
+                    (today.year()..).find_map(|year| NaiveDate::from_ymd_opt(year, month, days[0]))
+                })
+                .expect("every month day exists in some year");
+            let days: Vec<String> = days.iter().map(|d| d.to_string()).collect();
+            events.push(event(
+                format!("calendar-{}-{:02}@trimlight", schedule.id, month),
+                first_date,
+                format!(
+                    "FREQ=YEARLY;BYMONTH={};BYMONTHDAY={}",
+                    month,
+                    days.join(",")
+                ),
+            ));
+        }
+    }
+
+    let stamp = format!("{}Z", format_datetime(created.naive_utc()));
+    let mut out = String::new();
+    push_line(&mut out, "BEGIN:VCALENDAR");
+    push_line(&mut out, "VERSION:2.0");
+    push_line(&mut out, &format!("PRODID:{}", PRODUCT_ID));
+    push_line(&mut out, "CALSCALE:GREGORIAN");
+    for event in &events {
+        event.write(&mut out, &stamp);
+    }
+    push_line(&mut out, "END:VCALENDAR");
+    out
+}
+
+/// A schedule read from an iCalendar event
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ImportedSchedule {
+    Daily {
+        effect_id: i32,
+        start_time: ScheduleTime,
+        end_time: ScheduleTime,
+        repetition: i32,
+    },
+    Calendar {
+        effect_id: i32,
+        start_date: ScheduleDate,
+        end_date: ScheduleDate,
+        start_time: ScheduleTime,
+        end_time: ScheduleTime,
+    },
+}
+
+impl ImportedSchedule {
+    pub fn effect_id(&self) -> i32 {
+        match self {
+            ImportedSchedule::Daily { effect_id, .. }
+            | ImportedSchedule::Calendar { effect_id, .. } => *effect_id,
+        }
+    }
+
+    /// Whether the device already has a schedule with the same settings
+    pub fn exists_in(&self, schedules: &DeviceSchedules) -> bool {
+        match self {
+            ImportedSchedule::Daily {
+                effect_id,
+                start_time,
+                end_time,
+                repetition,
+            } => schedules.daily.iter().any(|s| {
+                s.effect_id == *effect_id
+                    && s.start_time == *start_time
+                    && s.end_time == *end_time
+                    && s.repetition == *repetition
+            }),
+            ImportedSchedule::Calendar {
+                effect_id,
+                start_date,
+                end_date,
+                start_time,
+                end_time,
+            } => schedules.calendar.iter().any(|s| {
+                s.effect_id == *effect_id
+                    && s.start_date == *start_date
+                    && s.end_date == *end_date
+                    && s.start_time == *start_time
+                    && s.end_time == *end_time
+            }),
+        }
+    }
+}
+
+/// An event that maps onto a device schedule
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ImportedEvent {
+    pub summary: String,
+    pub schedule: ImportedSchedule,
+    /// Differences between the event and the schedule the device will run
+    pub notes: Vec<String>,
+}
+
+/// An event the device cannot represent
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SkippedEvent {
+    pub summary: String,
+    pub reason: String,
+}
+
+/// Result of reading an iCalendar document
+#[derive(Debug, Default, Serialize)]
+pub struct IcsImport {
+    pub events: Vec<ImportedEvent>,
+    pub skipped: Vec<SkippedEvent>,
+}
+
+/// A content line split into its parts
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+fn unescape_text(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') | Some('N') => out.push('\n'),
+                Some(other) => out.push(other),
+                None => {}
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn parse_properties(ics: &str) -> Vec<Property> {
+    let unfolded = ics
+        .replace("\r\n ", "")
+        .replace("\r\n\t", "")
+        .replace("\n ", "")
+        .replace("\n\t", "");
+    unfolded
+        .lines()
+        .filter_map(|line| {
+            // The value starts at the first colon outside a quoted parameter
+            let mut quoted = false;
+            let colon = line.char_indices().find_map(|(i, c)| match c {
+                '"' => {
+                    quoted = !quoted;
+                    None
+                }
+                ':' if !quoted => Some(i),
+                _ => None,
+            })?;
+            let mut parts = line[..colon].split(';');
+            let name = parts.next()?.trim().to_ascii_uppercase();
+            let params = parts
+                .filter_map(|param| {
+                    let (key, value) = param.split_once('=')?;
+                    Some((key.to_string(), value.trim_matches('"').to_string()))
+                })
+                .collect();
+            Some(Property {
+                name,
+                params,
+                value: line[colon + 1..].to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Read a DTSTART or DTEND value as device local time
+fn parse_event_time(property: &Property) -> Result<NaiveDateTime, String> {
+    let value = property.value.trim();
+    if property.param("VALUE") == Some("DATE") || value.len() == 8 {
+        return Err("all-day events have no start and end time".to_string());
+    }
+    if value.ends_with('Z') {
+        return Err("UTC times are not supported, the device has no time zone".to_string());
+    }
+    let datetime = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .map_err(|_| format!("invalid {} '{}'", property.name, value))?;
+    if datetime.second() != 0 {
+        return Err("the device only supports whole minutes".to_string());
+    }
+    Ok(datetime)
+}
+
+/// Parse a DURATION value such as `PT5H30M` or `P1D`
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid DURATION '{}'", value);
+    let rest = value.trim().strip_prefix('P').ok_or_else(invalid)?;
+    let mut duration = Duration::zero();
+    let mut number = String::new();
+    for c in rest.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => {}
+            unit => {
+                let amount: i64 = number.parse().map_err(|_| invalid())?;
+                number.clear();
+                duration += match unit {
+                    'W' => Duration::weeks(amount),
+                    'D' => Duration::days(amount),
+                    'H' => Duration::hours(amount),
+                    'M' => Duration::minutes(amount),
+                    'S' => Duration::seconds(amount),
+                    _ => return Err(invalid()),
+                };
+            }
+        }
+    }
+    Ok(duration)
+}
+
+fn parse_weekday(code: &str) -> Option<Weekday> {
+    [
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+        Weekday::Sat,
+        Weekday::Sun,
+    ]
+    .into_iter()
+    .find(|d| weekday_code(*d) == code)
+}
+
+/// Map an event onto a device schedule, or explain why it can't be
+fn read_event(
+    properties: &[Property],
+    effects: &[Effect],
+    today: NaiveDate,
+) -> Result<ImportedEvent, String> {
+    let property = |name: &str| properties.iter().find(|p| p.name == name);
+    let summary = property("SUMMARY")
+        .map(|p| unescape_text(&p.value))
+        .unwrap_or_default();
+    let mut notes = Vec::new();
+
+    if property("STATUS").is_some_and(|p| p.value.eq_ignore_ascii_case("CANCELLED")) {
+        return Err("event is cancelled".to_string());
+    }
+    if property("RDATE").is_some() || property("EXDATE").is_some() {
+        return Err("extra or excluded dates (RDATE, EXDATE) can't be represented".to_string());
+    }
+    if properties.iter().filter(|p| p.name == "RRULE").count() > 1 {
+        return Err("events with more than one RRULE can't be represented".to_string());
+    }
+
+    let start = parse_event_time(property("DTSTART").ok_or("event has no start time")?)?;
+    let end = match (property("DTEND"), property("DURATION")) {
+        (Some(end), _) => parse_event_time(end)?,
+        (None, Some(duration)) => start + parse_duration(&duration.value)?,
+        (None, None) => return Err("event has no end time".to_string()),
+    };
+    if end <= start {
+        return Err("event ends before it starts".to_string());
+    }
+    if end - start >= Duration::days(1) {
+        return Err("events lasting 24 hours or more can't be scheduled".to_string());
+    }
+    if end.second() != 0 {
+        return Err("the device only supports whole minutes".to_string());
+    }
+    let start_time = ScheduleTime::from(start.time());
+    let end_time = ScheduleTime::from(end.time());
+
+    let effect_id = effects
+        .iter()
+        .find(|e| e.name.trim().eq_ignore_ascii_case(summary.trim()))
+        .map(|e| e.id)
+        .or_else(|| {
+            // Events exported by this library carry the effect ID
+            property("X-TRIMLIGHT-EFFECT-ID")
+                .and_then(|p| p.value.trim().parse().ok())
+                .filter(|id| effects.iter().any(|e| e.id == *id))
+        })
+        .ok_or_else(|| format!("no saved effect named '{}'", summary))?;
+
+    let date = ScheduleDate::from(start.date());
+    let rrule: Vec<(String, String)> = property("RRULE")
+        .map(|p| {
+            p.value
+                .split(';')
+                .filter_map(|part| part.split_once('='))
+                .map(|(key, value)| (key.to_ascii_uppercase(), value.to_ascii_uppercase()))
+                .collect()
+        })
+        .unwrap_or_default();
+    let rule = |key: &str| {
+        rrule
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    };
+    for (key, value) in &rrule {
+        match key.as_str() {
+            "FREQ" | "BYDAY" | "BYMONTH" | "BYMONTHDAY" | "WKST" => {}
+            "INTERVAL" if value == "1" => {}
+            "UNTIL" | "COUNT" => {
+                return Err(
+                    "repeating events that end (UNTIL, COUNT) can't be represented".to_string(),
+                )
+            }
+            _ => return Err(format!("RRULE part {}={} can't be represented", key, value)),
+        }
+    }
+    let is_calendar = property("X-TRIMLIGHT-SCHEDULE-TYPE")
+        .is_some_and(|p| p.value.trim().eq_ignore_ascii_case("calendar"));
+    if rrule.is_empty() || rule("FREQ") == Some("DAILY") || rule("FREQ") == Some("WEEKLY") {
+        if let Some(by) = ["BYMONTH", "BYMONTHDAY"]
+            .into_iter()
+            .find(|by| rule(by).is_some())
+        {
+            return Err(format!("{} can't be represented for this repetition", by));
+        }
+    }
+
+    let daily = |repetition: i32| ImportedSchedule::Daily {
+        effect_id,
+        start_time: start_time.clone(),
+        end_time: end_time.clone(),
+        repetition,
+    };
+    let calendar = |start_date: ScheduleDate, end_date: ScheduleDate| ImportedSchedule::Calendar {
+        effect_id,
+        start_date,
+        end_date,
+        start_time: start_time.clone(),
+        end_time: end_time.clone(),
+    };
+
+    let schedule = match rule("FREQ") {
+        None if start.date() == today => daily(0),
+        None => {
+            notes.push(format!(
+                "runs on {:02}-{:02} every year, not only in {}",
+                date.month,
+                date.day,
+                start.year()
+            ));
+            calendar(date.clone(), date)
+        }
+        Some("DAILY") if is_calendar => calendar(
+            ScheduleDate::new(1, 1).expect("valid date"),
+            ScheduleDate::new(12, 31).expect("valid date"),
+        ),
+        Some("DAILY") if rule("BYDAY").is_none() => daily(1),
+        Some("DAILY") | Some("WEEKLY") => {
+            let mut weekdays: Vec<Weekday> = match rule("BYDAY") {
+                Some(days) => days
+                    .split(',')
+                    .map(|code| {
+                        parse_weekday(code.trim())
+                            .ok_or_else(|| format!("BYDAY={} can't be represented", days))
+                    })
+                    .collect::<Result<_, _>>()?,
+                None => vec![start.weekday()],
+            };
+            weekdays.sort_by_key(|d| d.num_days_from_monday());
+            weekdays.dedup();
+            let repetition = (1..=3)
+                .find(|repetition| repetition_weekdays(*repetition) == weekdays)
+                .ok_or_else(|| {
+                    let days: Vec<&str> = weekdays.iter().map(|d| weekday_code(*d)).collect();
+                    format!(
+                        "repeating on {} doesn't match every day, weekdays or weekends",
+                        days.join(",")
+                    )
+                })?;
+            daily(repetition)
+        }
+        Some("YEARLY") => {
+            if rule("BYDAY").is_some() {
+                return Err("yearly events on weekdays (BYDAY) can't be represented".to_string());
+            }
+            let month: i32 = match rule("BYMONTH") {
+                Some(month) => month
+                    .parse()
+                    .map_err(|_| format!("BYMONTH={} can't be represented", month))?,
+                None => date.month,
+            };
+            let mut days: Vec<i32> = match rule("BYMONTHDAY") {
+                Some(days) => days
+                    .split(',')
+                    .map(|d| d.trim().parse::<i32>().ok().filter(|d| *d > 0))
+                    .collect::<Option<_>>()
+                    .ok_or_else(|| format!("BYMONTHDAY={} can't be represented", days))?,
+                None => vec![date.day],
+            };
+            days.sort();
+            days.dedup();
+            if days.windows(2).any(|pair| pair[1] != pair[0] + 1) {
+                return Err(
+                    "yearly events on days that aren't consecutive can't be represented"
+                        .to_string(),
+                );
+            }
+            let start_date =
+                ScheduleDate::new(month, days[0]).map_err(|_| "invalid yearly date".to_string())?;
+            let end_date = ScheduleDate::new(month, days[days.len() - 1])
+                .map_err(|_| "invalid yearly date".to_string())?;
+            calendar(start_date, end_date)
+        }
+        Some(freq) => return Err(format!("{} repetition can't be represented", freq)),
+    };
+
+    // The device starts repeating schedules right away
+    if let ImportedSchedule::Daily {
+        repetition: repetition @ 1..=3,
+        ..
+    } = schedule
+    {
+        let weekdays = repetition_weekdays(repetition);
+        let first_run = today
+            .iter_days()
+            .find(|d| weekdays.contains(&d.weekday()))
+            .expect("a matching weekday within a week");
+        if start.date() > first_run {
+            notes.push(format!(
+                "starts right away instead of on {}",
+                start.date().format("%Y-%m-%d")
+            ));
+        }
+    }
+
+    Ok(ImportedEvent {
+        summary,
+        schedule,
+        notes,
+    })
+}
+
+/// Join calendar schedules whose date ranges continue one another, such as
+/// the per-month events written by [`export_ics`]
+fn merge_calendar_events(events: &mut Vec<ImportedEvent>) {
+    let mut i = 0;
+    while i < events.len() {
+        let next = (0..events.len()).find(|j| match (&events[i].schedule, &events[*j].schedule) {
+            (
+                ImportedSchedule::Calendar {
+                    effect_id,
+                    end_date,
+                    start_time,
+                    end_time,
+                    ..
+                },
+                ImportedSchedule::Calendar {
+                    effect_id: next_effect_id,
+                    start_date: next_start_date,
+                    start_time: next_start_time,
+                    end_time: next_end_time,
+                    ..
+                },
+            ) => {
+                *j != i
+                    && effect_id == next_effect_id
+                    && start_time == next_start_time
+                    && end_time == next_end_time
+                    && day_of_year(end_date)
+                        .zip(day_of_year(next_start_date))
+                        .is_some_and(|(end, start)| (end + 1) % DAYS_PER_YEAR == start)
+            }
+            _ => false,
+        });
+
+        match next {
+            Some(j) => {
+                // Stay on the merged event, it may continue further
+                let next = events.remove(j);
+                if j < i {
+                    i -= 1;
+                }
+                if let (
+                    ImportedSchedule::Calendar { end_date, .. },
+                    ImportedSchedule::Calendar {
+                        end_date: next_end_date,
+                        ..
+                    },
+                ) = (&mut events[i].schedule, next.schedule)
+                {
+                    *end_date = next_end_date;
+                }
+                for note in next.notes {
+                    if !events[i].notes.contains(&note) {
+                        events[i].notes.push(note);
+                    }
+                }
+            }
+            None => i += 1,
+        }
+    }
+}
+
+/// Read schedules from an iCalendar document
+///
+/// Event summaries are matched to saved effect names. Events that repeat
+/// daily, on weekdays or on weekends become daily schedules, a single event
+/// on `today` becomes a "today only" daily schedule, and yearly events on
+/// consecutive days of a month become calendar schedules. Other events are
+/// returned in [`IcsImport::skipped`] with the reason they can't be
+/// represented. Times are taken as device local time.
+pub fn import_ics(
+    ics: &str,
+    effects: &[Effect],
+    today: NaiveDate,
+) -> Result<IcsImport, TrimlightError> {
+    let properties = parse_properties(ics);
+    if !properties
+        .iter()
+        .any(|p| p.name == "BEGIN" && p.value.trim().eq_ignore_ascii_case("VCALENDAR"))
+    {
+        return Err(TrimlightError::ApiError {
+            code: 400,
+            message: "Invalid iCalendar file: missing BEGIN:VCALENDAR".to_string(),
+        });
+    }
+
+    let mut import = IcsImport::default();
+    let mut event: Option<Vec<Property>> = None;
+    // Nested components such as alarms inside an event
+    let mut nested = 0;
+    for property in properties {
+        let component = property.value.trim().to_ascii_uppercase();
+        match (property.name.as_str(), event.as_mut()) {
+            ("BEGIN", None) if component == "VEVENT" => event = Some(Vec::new()),
+            ("BEGIN", Some(_)) => nested += 1,
+            ("END", Some(_)) if nested > 0 => nested -= 1,
+            ("END", Some(_)) if component == "VEVENT" => {
+                let properties = event.take().unwrap_or_default();
+                match read_event(&properties, effects, today) {
+                    Ok(imported) => import.events.push(imported),
+                    Err(reason) => import.skipped.push(SkippedEvent {
+                        summary: properties
+                            .iter()
+                            .find(|p| p.name == "SUMMARY")
+                            .map(|p| unescape_text(&p.value))
+                            .unwrap_or_default(),
+                        reason,
+                    }),
+                }
+            }
+            (_, Some(properties)) if nested == 0 => properties.push(property),
+            _ => {}
+        }
+    }
+
+    merge_calendar_events(&mut import.events);
+    Ok(import)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{calendar, daily, today};
+
+    fn created() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2026-12-19T14:30:05Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn effects() -> Vec<Effect> {
+        vec![Effect {
+            id: 10,
+            name: "Warm White, Soft".to_string(),
+            ..Default::default()
+        }]
+    }
+
+    /// Property lines of each event, without the fixed ones
+    fn events(ics: &str) -> Vec<Vec<String>> {
+        ics.replace("\r\n ", "")
+            .split("BEGIN:VEVENT\r\n")
+            .skip(1)
+            .map(|event| {
+                event
+                    .lines()
+                    .filter(|line| {
+                        ["DTSTART", "DTEND", "RRULE", "SUMMARY", "STATUS", "UID"]
+                            .iter()
+                            .any(|name| line.starts_with(name))
+                    })
+                    .map(str::to_string)
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_export_daily() {
+        let mut disabled = daily(3, 0, (6, 0), (7, 0));
+        disabled.enable = false;
+        let schedules = DeviceSchedules {
+            daily: vec![
+                daily(0, 1, (17, 0), (23, 0)),
+                daily(1, 2, (20, 0), (2, 0)),
+                daily(2, 3, (9, 30), (10, 0)),
+                disabled,
+            ],
+            ..Default::default()
+        };
+
+        let ics = export_ics(&schedules, &effects(), today(), created());
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert!(ics.contains("\r\nDTSTAMP:20261219T143005Z\r\n"));
+        assert_eq!(
+            events(&ics),
+            vec![
+                vec![
+                    "UID:daily-0@trimlight",
+                    "DTSTART:20261219T170000",
+                    "DTEND:20261219T230000",
+                    "RRULE:FREQ=DAILY",
+                    "SUMMARY:Warm White\\, Soft",
+                ],
+                vec![
+                    "UID:daily-1@trimlight",
+                    "DTSTART:20261221T200000",
+                    "DTEND:20261222T020000",
+                    "RRULE:FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR",
+                    "SUMMARY:Effect 11",
+                ],
+                vec![
+                    "UID:daily-2@trimlight",
+                    "DTSTART:20261219T093000",
+                    "DTEND:20261219T100000",
+                    "RRULE:FREQ=WEEKLY;BYDAY=SA,SU",
+                    "SUMMARY:Effect 12",
+                ],
+                vec![
+                    "UID:daily-3@trimlight",
+                    "DTSTART:20261219T060000",
+                    "DTEND:20261219T070000",
+                    "SUMMARY:Effect 13",
+                    "STATUS:CANCELLED",
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_export_calendar() {
+        let schedules = DeviceSchedules {
+            calendar: vec![
+                calendar(0, (12, 24), (12, 26)),
+                calendar(1, (12, 15), (1, 5)),
+            ],
+            ..Default::default()
+        };
+
+        let ics = export_ics(&schedules, &effects(), today(), created());
+        assert_eq!(
+            events(&ics),
+            vec![
+                vec![
+                    "UID:calendar-0-12@trimlight",
+                    "DTSTART:20261224T180000",
+                    "DTEND:20261224T230000",
+                    "RRULE:FREQ=YEARLY;BYMONTH=12;BYMONTHDAY=24,25,26",
+                    "SUMMARY:Effect 20",
+                ],
+                vec![
+                    "UID:calendar-1-12@trimlight",
+                    "DTSTART:20261215T180000",
+                    "DTEND:20261215T230000",
+                    "RRULE:FREQ=YEARLY;BYMONTH=12;BYMONTHDAY=15,16,17,18,19,20,21,22,23,24,25,26,27,28,29,30,31",
+                    "SUMMARY:Effect 21",
+                ],
+                vec![
+                    "UID:calendar-1-01@trimlight",
+                    "DTSTART:20270101T180000",
+                    "DTEND:20270101T230000",
+                    "RRULE:FREQ=YEARLY;BYMONTH=1;BYMONTHDAY=1,2,3,4,5",
+                    "SUMMARY:Effect 21",
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_export_leap_day() {
+        // 2027 has no 02-29, but the schedule still covers it in leap years
+        let schedules = DeviceSchedules {
+            calendar: vec![calendar(0, (2, 20), (3, 2))],
+            ..Default::default()
+        };
+        let ics = export_ics(&schedules, &[], today(), created());
+        assert!(ics.contains("DTSTART:20270220T180000\r\n"));
+        assert!(ics.contains("BYMONTH=2;BYMONTHDAY=20,21,22,23,24,25,26,27,28,29\r\n"));
+        assert!(ics.contains("BYMONTH=3;BYMONTHDAY=1,2\r\n"));
+
+        let schedules = DeviceSchedules {
+            calendar: vec![calendar(0, (2, 29), (2, 29))],
+            ..Default::default()
+        };
+        let ics = export_ics(&schedules, &[], today(), created());
+        assert!(ics.contains("DTSTART:20280229T180000\r\n"));
+    }
+
+    #[test]
+    fn test_fold_long_lines() {
+        let mut out = String::new();
+        push_line(&mut out, &"x".repeat(160));
+        let lines: Vec<&str> = out.split("\r\n").collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0].len(), 75);
+        assert_eq!(lines[1].len(), 75);
+        assert!(lines[1].starts_with(' '));
+        assert_eq!(lines[2], format!(" {}", "x".repeat(11)));
+    }
+
+    fn plan_effects() -> Vec<Effect> {
+        vec![
+            Effect {
+                id: 1,
+                name: "Warm White".to_string(),
+                ..Default::default()
+            },
+            Effect {
+                id: 2,
+                name: "Christmas".to_string(),
+                ..Default::default()
+            },
+        ]
+    }
+
+    fn calendar_file(events: &[&str]) -> String {
+        let events: Vec<String> = events
+            .iter()
+            .map(|e| {
+                format!(
+                    "BEGIN:VEVENT\r\n{}\r\nEND:VEVENT\r\n",
+                    e.replace('\n', "\r\n")
+                )
+            })
+            .collect();
+        format!(
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\n{}END:VCALENDAR\r\n",
+            events.concat()
+        )
+    }
+
+    #[test]
+    fn test_import_round_trip() {
+        let schedules = DeviceSchedules {
+            daily: vec![daily(0, 2, (20, 0), (2, 0)), daily(1, 0, (6, 0), (7, 0))],
+            calendar: vec![calendar(0, (12, 15), (1, 5)), calendar(1, (2, 20), (3, 2))],
+            ..Default::default()
+        };
+        let effects: Vec<Effect> = [
+            (10, "Warm White"),
+            (11, "Sunrise"),
+            (20, "Christmas"),
+            (21, "Valentine"),
+        ]
+        .into_iter()
+        .map(|(id, name)| Effect {
+            id,
+            name: name.to_string(),
+            ..Default::default()
+        })
+        .collect();
+        let ics = export_ics(&schedules, &effects, today(), created());
+
+        let import = import_ics(&ics, &effects, today()).unwrap();
+        assert!(import.skipped.is_empty(), "{:?}", import.skipped);
+        assert_eq!(import.events.len(), 4);
+        assert!(import
+            .events
+            .iter()
+            .all(|e| e.schedule.exists_in(&schedules)));
+        assert_eq!(import.events[0].summary, "Warm White");
+        assert!(import.events.iter().all(|e| e.notes.is_empty()));
+    }
+
+    #[test]
+    fn test_exists_in_ignores_unknown_fields() {
+        let schedules: DeviceSchedules = serde_json::from_value(serde_json::json!({
+            "calendar": [{
+                "id": 0,
+                "effectId": 20,
+                "startDate": {"month": 12, "day": 24, "year": 26},
+                "endDate": {"month": 12, "day": 26},
+                "startTime": {"hours": 18, "minutes": 0, "seconds": 0},
+                "endTime": {"hours": 23, "minutes": 0}
+            }]
+        }))
+        .unwrap();
+
+        let schedule = ImportedSchedule::Calendar {
+            effect_id: 20,
+            start_date: ScheduleDate::new(12, 24).unwrap(),
+            end_date: ScheduleDate::new(12, 26).unwrap(),
+            start_time: ScheduleTime::new(18, 0).unwrap(),
+            end_time: ScheduleTime::new(23, 0).unwrap(),
+        };
+        assert!(schedule.exists_in(&schedules));
+    }
+
+    #[test]
+    fn test_import_events() {
+        let ics = calendar_file(&[
+            "SUMMARY:warm white\nDTSTART:20261219T170000\nDTEND:20261219T230000\nRRULE:FREQ=DAILY",
+            "SUMMARY:Christmas\nDTSTART;TZID=America/Chicago:20261224T180000\nDURATION:PT7H\n\
+             RRULE:FREQ=YEARLY;BYMONTH=12;BYMONTHDAY=24,25,26",
+            "SUMMARY:Christmas\nDTSTART:20261231T180000\nDTEND:20261231T230000",
+            "SUMMARY:Warm White\nDTSTART:20261228T060000\nDTEND:20261228T070000\n\
+             RRULE:FREQ=WEEKLY;BYDAY=FR,TH,WE,TU,MO",
+            "SUMMARY:Warm White\nDTSTART:20261219T060000\nDTEND:20261219T070000\n\
+             BEGIN:VALARM\nTRIGGER:-PT15M\nSUMMARY:Reminder\nEND:VALARM",
+        ]);
+
+        let import = import_ics(&ics, &plan_effects(), today()).unwrap();
+        assert!(import.skipped.is_empty(), "{:?}", import.skipped);
+        let schedules: Vec<&ImportedSchedule> = import.events.iter().map(|e| &e.schedule).collect();
+        assert_eq!(
+            schedules,
+            vec![
+                &ImportedSchedule::Daily {
+                    effect_id: 1,
+                    start_time: ScheduleTime::new(17, 0).unwrap(),
+                    end_time: ScheduleTime::new(23, 0).unwrap(),
+                    repetition: 1,
+                },
+                &ImportedSchedule::Calendar {
+                    effect_id: 2,
+                    start_date: ScheduleDate::new(12, 24).unwrap(),
+                    end_date: ScheduleDate::new(12, 26).unwrap(),
+                    start_time: ScheduleTime::new(18, 0).unwrap(),
+                    end_time: ScheduleTime::new(1, 0).unwrap(),
+                },
+                &ImportedSchedule::Calendar {
+                    effect_id: 2,
+                    start_date: ScheduleDate::new(12, 31).unwrap(),
+                    end_date: ScheduleDate::new(12, 31).unwrap(),
+                    start_time: ScheduleTime::new(18, 0).unwrap(),
+                    end_time: ScheduleTime::new(23, 0).unwrap(),
+                },
+                &ImportedSchedule::Daily {
+                    effect_id: 1,
+                    start_time: ScheduleTime::new(6, 0).unwrap(),
+                    end_time: ScheduleTime::new(7, 0).unwrap(),
+                    repetition: 2,
+                },
+                &ImportedSchedule::Daily {
+                    effect_id: 1,
+                    start_time: ScheduleTime::new(6, 0).unwrap(),
+                    end_time: ScheduleTime::new(7, 0).unwrap(),
+                    repetition: 0,
+                },
+            ]
+        );
+        assert_eq!(
+            import.events[2].notes,
+            vec!["runs on 12-31 every year, not only in 2026"]
+        );
+        assert_eq!(
+            import.events[3].notes,
+            vec!["starts right away instead of on 2026-12-28"]
+        );
+    }
+
+    #[test]
+    fn test_import_unrepresentable() {
+        let ics = calendar_file(&[
+            "SUMMARY:Party\nDTSTART:20261219T170000\nDTEND:20261219T230000",
+            "SUMMARY:Warm White\nDTSTART:20261219T170000\nDTEND:20261219T230000\n\
+             RRULE:FREQ=WEEKLY;BYDAY=MO,WE",
+            "SUMMARY:Warm White\nDTSTART:20261219T170000\nDTEND:20261219T230000\n\
+             RRULE:FREQ=DAILY;COUNT=5",
+            "SUMMARY:Warm White\nDTSTART;VALUE=DATE:20261219\nDTEND;VALUE=DATE:20261220",
+            "SUMMARY:Warm White\nDTSTART:20261219T170000Z\nDTEND:20261219T230000Z",
+            "SUMMARY:Christmas\nDTSTART:20261224T180000\nDTEND:20261224T230000\n\
+             RRULE:FREQ=YEARLY;BYMONTH=12;BYMONTHDAY=24,26",
+            "SUMMARY:Warm White\nDTSTART:20261219T170000\nDTEND:20261221T170000",
+            "SUMMARY:Warm White\nDTSTART:20261219T170000\nDTEND:20261219T230000\nSTATUS:CANCELLED",
+        ]);
+
+        let import = import_ics(&ics, &plan_effects(), today()).unwrap();
+        assert!(import.events.is_empty());
+        let reasons: Vec<&str> = import.skipped.iter().map(|s| s.reason.as_str()).collect();
+        assert_eq!(
+            reasons,
+            vec![
+                "no saved effect named 'Party'",
+                "repeating on MO,WE doesn't match every day, weekdays or weekends",
+                "repeating events that end (UNTIL, COUNT) can't be represented",
+                "all-day events have no start and end time",
+                "UTC times are not supported, the device has no time zone",
+                "yearly events on days that aren't consecutive can't be represented",
+                "events lasting 24 hours or more can't be scheduled",
+                "event is cancelled",
+            ]
+        );
+        assert_eq!(import.skipped[0].summary, "Party");
+
+        assert!(import_ics("not a calendar", &[], today()).is_err());
+    }
+}
@@ -0,0 +1,493 @@
+//! Ranges on repeating scales, used to reason about schedules.
+//!
+//! Schedule times repeat every day and calendar dates repeat every year, so a
+//! schedule from 20:00 to 02:00 or from 12-15 to 01-05 wraps around the end
+//! of its scale. [`CyclicRange`] models such ranges; [`TimeWindow`] and
+//! [`DateRange`] apply it to schedule times and dates.
+
+use chrono::{Datelike, NaiveDate};
+
+use crate::models::{ScheduleDate, ScheduleTime};
+
+pub const MINUTES_PER_DAY: u32 = 24 * 60;
+/// Calendar dates are placed on a leap year so that 02-29 has a position
+pub const DAYS_PER_YEAR: u32 = 366;
+
+/// A half-open range `[start, start + len)` on a scale that wraps at `period`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CyclicRange {
+    start: u32,
+    len: u32,
+    period: u32,
+}
+
+impl CyclicRange {
+    pub fn new(start: u32, len: u32, period: u32) -> Self {
+        Self {
+            start: start % period,
+            len: len.min(period),
+            period,
+        }
+    }
+
+    pub fn start(&self) -> u32 {
+        self.start
+    }
+
+    /// Exclusive end, wrapped onto the scale
+    pub fn end(&self) -> u32 {
+        (self.start + self.len) % self.period
+    }
+
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    pub fn period(&self) -> u32 {
+        self.period
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == self.period
+    }
+
+    /// Whether the range runs past the end of the scale and continues at 0
+    pub fn wraps(&self) -> bool {
+        self.start + self.len > self.period
+    }
+
+    pub fn contains(&self, point: u32) -> bool {
+        (point % self.period + self.period - self.start) % self.period < self.len
+    }
+
+    /// Distance from the start of the range to `point`, moving forward
+    pub fn offset_of(&self, point: u32) -> u32 {
+        (point % self.period + self.period - self.start) % self.period
+    }
+
+    /// Move the range forward (or backward, for negative values)
+    pub fn shift(&self, by: i32) -> Self {
+        let start = (self.start as i64 + by as i64).rem_euclid(self.period as i64) as u32;
+        Self { start, ..*self }
+    }
+
+    /// Linear pieces of the range within `[0, period)`
+    fn segments(&self) -> Vec<(u32, u32)> {
+        if self.is_empty() {
+            Vec::new()
+        } else if self.wraps() {
+            vec![
+                (self.start, self.period),
+                (0, self.start + self.len - self.period),
+            ]
+        } else {
+            vec![(self.start, self.start + self.len)]
+        }
+    }
+
+    /// Points contained in both ranges
+    ///
+    /// Two wrapping ranges can share two separate pieces, e.g. 20:00-02:00
+    /// and 01:00-21:00 share 01:00-02:00 and 20:00-21:00.
+    pub fn intersect(&self, other: &CyclicRange) -> Vec<CyclicRange> {
+        assert_eq!(self.period, other.period, "ranges must share a scale");
+        if self.is_full() {
+            return if other.is_empty() {
+                Vec::new()
+            } else {
+                vec![*other]
+            };
+        }
+        if other.is_full() {
+            return if self.is_empty() {
+                Vec::new()
+            } else {
+                vec![*self]
+            };
+        }
+
+        let mut pieces: Vec<(u32, u32)> = Vec::new();
+        for (a_start, a_end) in self.segments() {
+            for (b_start, b_end) in other.segments() {
+                let start = a_start.max(b_start);
+                let end = a_end.min(b_end);
+                if start < end {
+                    pieces.push((start, end));
+                }
+            }
+        }
+        pieces.sort();
+
+        // A piece ending at the end of the scale continues in a piece starting at 0
+        if pieces.len() > 1 && pieces[0].0 == 0 && pieces[pieces.len() - 1].1 == self.period {
+            let (_, head_end) = pieces.remove(0);
+            let last = pieces.len() - 1;
+            pieces[last].1 = self.period + head_end;
+        }
+
+        pieces
+            .into_iter()
+            .map(|(start, end)| CyclicRange::new(start, end - start, self.period))
+            .collect()
+    }
+}
+
+/// Minutes since midnight
+pub fn minute_of_day(time: &ScheduleTime) -> u32 {
+    (time.hours * 60 + time.minutes).rem_euclid(MINUTES_PER_DAY as i32) as u32
+}
+
+/// Schedule time at the given number of minutes since midnight
+pub fn time_of_day(minute: u32) -> ScheduleTime {
+    let minute = minute % MINUTES_PER_DAY;
+    ScheduleTime {
+        hours: (minute / 60) as i32,
+        minutes: (minute % 60) as i32,
+        ..Default::default()
+    }
+}
+
+fn leap_year_date(ordinal: u32) -> NaiveDate {
+    NaiveDate::from_yo_opt(2000, ordinal % DAYS_PER_YEAR + 1).expect("2000 is a leap year")
+}
+
+/// Position of a calendar date in the year, starting at 0 for 01-01
+///
+/// Dates are placed on a leap year, so 03-01 is day 60 in every year.
+pub fn day_of_year(date: &ScheduleDate) -> Option<u32> {
+    NaiveDate::from_ymd_opt(2000, date.month as u32, date.day as u32).map(|d| d.ordinal0())
+}
+
+/// Calendar date at the given position in the year
+pub fn date_of_year(ordinal: u32) -> ScheduleDate {
+    let date = leap_year_date(ordinal);
+    ScheduleDate {
+        month: date.month() as i32,
+        day: date.day() as i32,
+        ..Default::default()
+    }
+}
+
+/// The time of day a schedule runs, possibly crossing midnight
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeWindow(CyclicRange);
+
+impl TimeWindow {
+    /// Window from `start` up to (not including) `end`
+    ///
+    /// An end before the start means the window continues past midnight. An
+    /// end equal to the start gives an empty window.
+    pub fn new(start: &ScheduleTime, end: &ScheduleTime) -> Self {
+        let start = minute_of_day(start);
+        let len = (minute_of_day(end) + MINUTES_PER_DAY - start) % MINUTES_PER_DAY;
+        Self(CyclicRange::new(start, len, MINUTES_PER_DAY))
+    }
+
+    pub fn range(&self) -> &CyclicRange {
+        &self.0
+    }
+
+    pub fn start(&self) -> ScheduleTime {
+        time_of_day(self.0.start())
+    }
+
+    pub fn end(&self) -> ScheduleTime {
+        time_of_day(self.0.end())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn crosses_midnight(&self) -> bool {
+        self.0.wraps()
+    }
+
+    pub fn contains(&self, time: &ScheduleTime) -> bool {
+        self.0.contains(minute_of_day(time))
+    }
+
+    /// Days between the start of the window and `time`: 1 when `time` falls
+    /// in the part after midnight, 0 otherwise
+    pub fn day_offset(&self, time: &ScheduleTime) -> i32 {
+        let minute = minute_of_day(time);
+        if self.0.start() + self.0.offset_of(minute) >= MINUTES_PER_DAY {
+            1
+        } else {
+            0
+        }
+    }
+
+    pub fn intersect(&self, other: &TimeWindow) -> Vec<TimeWindow> {
+        self.0
+            .intersect(&other.0)
+            .into_iter()
+            .map(TimeWindow)
+            .collect()
+    }
+}
+
+/// The days of the year a calendar schedule runs, possibly crossing new year
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateRange(CyclicRange);
+
+impl DateRange {
+    /// Range from `start` through `end`, both inclusive
+    ///
+    /// An end before the start means the range continues into the next year.
+    /// Returns `None` for dates that don't exist.
+    pub fn new(start: &ScheduleDate, end: &ScheduleDate) -> Option<Self> {
+        let start = day_of_year(start)?;
+        let end = day_of_year(end)?;
+        let len = (end + DAYS_PER_YEAR - start) % DAYS_PER_YEAR + 1;
+        Some(Self(CyclicRange::new(start, len, DAYS_PER_YEAR)))
+    }
+
+    /// Every day of the year
+    pub fn all_year() -> Self {
+        Self(CyclicRange::new(0, DAYS_PER_YEAR, DAYS_PER_YEAR))
+    }
+
+    pub fn range(&self) -> &CyclicRange {
+        &self.0
+    }
+
+    pub fn start(&self) -> ScheduleDate {
+        date_of_year(self.0.start())
+    }
+
+    /// Last day of the range
+    pub fn end(&self) -> ScheduleDate {
+        date_of_year(self.0.start() + self.0.len() - 1)
+    }
+
+    pub fn is_all_year(&self) -> bool {
+        self.0.is_full()
+    }
+
+    pub fn crosses_new_year(&self) -> bool {
+        self.0.wraps()
+    }
+
+    pub fn contains(&self, date: &ScheduleDate) -> bool {
+        day_of_year(date).is_some_and(|d| self.0.contains(d))
+    }
+
+    /// Move the range by a number of days
+    pub fn shift(&self, days: i32) -> Self {
+        Self(self.0.shift(days))
+    }
+
+    pub fn intersect(&self, other: &DateRange) -> Vec<DateRange> {
+        self.0
+            .intersect(&other.0)
+            .into_iter()
+            .map(DateRange)
+            .collect()
+    }
+
+    /// Dates of the first run of the range that hasn't ended before `today`
+    ///
+    /// 02-29 is left out in years that don't have it.
+    pub fn next_dates(&self, today: NaiveDate) -> Vec<NaiveDate> {
+        let dates_from = |year: i32| -> Vec<NaiveDate> {
+            (self.0.start()..self.0.start() + self.0.len())
+                .filter_map(|ordinal| {
+                    let year = year + (ordinal / DAYS_PER_YEAR) as i32;
+                    let date = leap_year_date(ordinal);
+                    NaiveDate::from_ymd_opt(year, date.month(), date.day())
+                })
+                .collect()
+        };
+        (today.year() - 1..=today.year())
+            .map(dates_from)
+            .find(|dates| dates.last().is_some_and(|last| *last >= today))
+            .unwrap_or_else(|| dates_from(today.year() + 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(hours: i32, minutes: i32) -> ScheduleTime {
+        ScheduleTime::new(hours, minutes).unwrap()
+    }
+
+    fn date(month: i32, day: i32) -> ScheduleDate {
+        ScheduleDate::new(month, day).unwrap()
+    }
+
+    #[test]
+    fn test_intersect_exhaustive() {
+        // Compare against point-by-point membership for every pair of ranges
+        // on a small scale
+        let period = 8;
+        let ranges: Vec<CyclicRange> = (0..period)
+            .flat_map(|start| (0..=period).map(move |len| CyclicRange::new(start, len, period)))
+            .collect();
+
+        for a in &ranges {
+            for b in &ranges {
+                let pieces = a.intersect(b);
+                for point in 0..period {
+                    let expected = a.contains(point) && b.contains(point);
+                    let covered = pieces.iter().filter(|p| p.contains(point)).count();
+                    assert_eq!(
+                        covered, expected as usize,
+                        "{:?} & {:?} at {}: {:?}",
+                        a, b, point, pieces
+                    );
+                }
+                assert!(pieces.len() <= 2);
+                assert!(pieces.iter().all(|p| !p.is_empty()));
+                // Pieces never touch, otherwise they would have been merged
+                for p in &pieces {
+                    for q in &pieces {
+                        if p != q && !p.is_full() {
+                            assert_ne!(p.end(), q.start(), "{:?} & {:?}: {:?}", a, b, pieces);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_contains_and_shift() {
+        let range = CyclicRange::new(6, 4, 8);
+        assert!(range.wraps());
+        assert_eq!(range.end(), 2);
+        let contained: Vec<u32> = (0..8).filter(|p| range.contains(*p)).collect();
+        assert_eq!(contained, vec![0, 1, 6, 7]);
+
+        assert_eq!(range.shift(3), CyclicRange::new(1, 4, 8));
+        assert_eq!(range.shift(-7), CyclicRange::new(7, 4, 8));
+    }
+
+    #[test]
+    fn test_time_window_midnight() {
+        let window = TimeWindow::new(&time(20, 0), &time(2, 0));
+        assert!(window.crosses_midnight());
+        assert_eq!(window.range().len(), 6 * 60);
+        assert!(window.contains(&time(23, 59)));
+        assert!(window.contains(&time(0, 0)));
+        assert!(window.contains(&time(1, 59)));
+        assert!(!window.contains(&time(2, 0)));
+        assert!(!window.contains(&time(19, 59)));
+        assert_eq!(window.day_offset(&time(21, 0)), 0);
+        assert_eq!(window.day_offset(&time(1, 0)), 1);
+        assert_eq!(window.end(), time(2, 0));
+
+        let window = TimeWindow::new(&time(8, 0), &time(12, 0));
+        assert!(!window.crosses_midnight());
+        assert_eq!(window.day_offset(&time(11, 0)), 0);
+
+        assert!(TimeWindow::new(&time(8, 0), &time(8, 0)).is_empty());
+    }
+
+    #[test]
+    fn test_time_window_intersect() {
+        let night = TimeWindow::new(&time(20, 0), &time(2, 0));
+
+        let late = TimeWindow::new(&time(22, 0), &time(1, 0));
+        assert_eq!(night.intersect(&late), vec![late]);
+
+        let morning = TimeWindow::new(&time(1, 0), &time(3, 0));
+        let shared = night.intersect(&morning);
+        assert_eq!(shared.len(), 1);
+        assert_eq!(
+            (shared[0].start(), shared[0].end()),
+            (time(1, 0), time(2, 0))
+        );
+
+        let day = TimeWindow::new(&time(1, 0), &time(21, 0));
+        let shared: Vec<(ScheduleTime, ScheduleTime)> = night
+            .intersect(&day)
+            .iter()
+            .map(|w| (w.start(), w.end()))
+            .collect();
+        assert_eq!(
+            shared,
+            vec![(time(1, 0), time(2, 0)), (time(20, 0), time(21, 0))]
+        );
+
+        // Touching windows don't overlap
+        let evening = TimeWindow::new(&time(18, 0), &time(20, 0));
+        assert!(night.intersect(&evening).is_empty());
+    }
+
+    #[test]
+    fn test_day_of_year() {
+        assert_eq!(day_of_year(&date(1, 1)), Some(0));
+        assert_eq!(day_of_year(&date(2, 29)), Some(59));
+        assert_eq!(day_of_year(&date(3, 1)), Some(60));
+        assert_eq!(day_of_year(&date(12, 31)), Some(365));
+        assert_eq!(
+            day_of_year(&ScheduleDate {
+                month: 2,
+                day: 30,
+                ..Default::default()
+            }),
+            None
+        );
+
+        for ordinal in 0..DAYS_PER_YEAR {
+            assert_eq!(day_of_year(&date_of_year(ordinal)), Some(ordinal));
+        }
+    }
+
+    #[test]
+    fn test_date_range_new_year() {
+        let holidays = DateRange::new(&date(12, 15), &date(1, 5)).unwrap();
+        assert!(holidays.crosses_new_year());
+        assert_eq!(holidays.range().len(), 22);
+        assert!(holidays.contains(&date(12, 31)));
+        assert!(holidays.contains(&date(1, 1)));
+        assert!(holidays.contains(&date(1, 5)));
+        assert!(!holidays.contains(&date(1, 6)));
+        assert!(!holidays.contains(&date(12, 14)));
+        assert_eq!(holidays.start(), date(12, 15));
+        assert_eq!(holidays.end(), date(1, 5));
+
+        let january = DateRange::new(&date(1, 1), &date(1, 31)).unwrap();
+        let shared = holidays.intersect(&january);
+        assert_eq!(shared.len(), 1);
+        assert_eq!(
+            (shared[0].start(), shared[0].end()),
+            (date(1, 1), date(1, 5))
+        );
+
+        let single = DateRange::new(&date(7, 4), &date(7, 4)).unwrap();
+        assert_eq!(single.range().len(), 1);
+
+        let year = DateRange::new(&date(1, 1), &date(12, 31)).unwrap();
+        assert!(year.is_all_year());
+        assert_eq!(year, DateRange::all_year());
+
+        let today = NaiveDate::from_ymd_opt(2027, 1, 3).unwrap();
+        let dates = holidays.next_dates(today);
+        assert_eq!(dates.len(), 22);
+        assert_eq!(dates[0], NaiveDate::from_ymd_opt(2026, 12, 15).unwrap());
+        assert_eq!(dates[21], NaiveDate::from_ymd_opt(2027, 1, 5).unwrap());
+        let today = NaiveDate::from_ymd_opt(2027, 1, 6).unwrap();
+        assert_eq!(
+            holidays.next_dates(today)[0],
+            NaiveDate::from_ymd_opt(2027, 12, 15).unwrap()
+        );
+
+        // 02-29 only exists in leap years
+        let february = DateRange::new(&date(2, 28), &date(3, 1)).unwrap();
+        let today = NaiveDate::from_ymd_opt(2027, 1, 1).unwrap();
+        assert_eq!(february.next_dates(today).len(), 2);
+        let today = NaiveDate::from_ymd_opt(2028, 1, 1).unwrap();
+        assert_eq!(february.next_dates(today).len(), 3);
+
+        assert_eq!(holidays.shift(1).start(), date(12, 16));
+        assert_eq!(holidays.shift(1).end(), date(1, 6));
+    }
+}
@@ -1,9 +1,33 @@
 mod client;
+mod config;
+mod conflicts;
+mod diff;
 mod error;
+mod evaluator;
+mod holidays;
+mod ics;
+mod interval;
+mod library;
 mod models;
+mod snapshot;
+mod solar;
+#[cfg(test)]
+mod test_util;
 mod utils;
+mod validate;
 
 pub use client::TrimlightClient;
+pub use config::*;
+pub use conflicts::*;
+pub use diff::*;
 pub use error::TrimlightError;
+pub use evaluator::*;
+pub use holidays::*;
+pub use ics::*;
+pub use interval::*;
+pub use library::*;
 pub use models::*;
+pub use snapshot::*;
+pub use solar::*;
 pub use utils::*;
+pub use validate::*;
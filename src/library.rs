@@ -0,0 +1,167 @@
+//! Local effect library.
+//!
+//! An [`EffectLibrary`] keeps effects in a TOML file, in the same format as
+//! the `effects` section of a [`DeviceConfig`](crate::DeviceConfig), so they
+//! can be designed once and pushed to any device by name.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::config::EffectConfig;
+use crate::error::{invalid, TrimlightError};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LibraryFile {
+    #[serde(default)]
+    effects: Vec<EffectConfig>,
+}
+
+/// Effects stored in a TOML file, keyed by name
+#[derive(Debug)]
+pub struct EffectLibrary {
+    path: PathBuf,
+    effects: Vec<EffectConfig>,
+}
+
+impl EffectLibrary {
+    /// Open the library at `path`, which is empty when the file doesn't exist yet
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, TrimlightError> {
+        let path = path.into();
+        let effects = match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                let file: LibraryFile = toml::from_str(&text)
+                    .map_err(|e| invalid(format!("Invalid library {}: {}", path.display(), e)))?;
+                file.effects
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(source) => {
+                return Err(TrimlightError::IoError {
+                    context: format!("Can't read library {}", path.display()),
+                    source,
+                })
+            }
+        };
+        Ok(Self { path, effects })
+    }
+
+    /// Write the library back to its file, creating the directory if needed
+    pub fn save(&self) -> Result<(), TrimlightError> {
+        let text = toml::to_string(&LibraryFile {
+            effects: self.effects.clone(),
+        })
+        .map_err(|e| invalid(format!("Can't write library: {}", e)))?;
+        let write = || -> std::io::Result<()> {
+            if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+                std::fs::create_dir_all(dir)?;
+            }
+            std::fs::write(&self.path, text)
+        };
+        write().map_err(|source| TrimlightError::IoError {
+            context: format!("Can't write library {}", self.path.display()),
+            source,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Effects in the order they were added
+    pub fn effects(&self) -> &[EffectConfig] {
+        &self.effects
+    }
+
+    pub fn get(&self, name: &str) -> Option<&EffectConfig> {
+        self.effects.iter().find(|e| e.name == name)
+    }
+
+    /// Add an effect, replacing one with the same name
+    ///
+    /// Returns the effect that was replaced. The effect is checked first so
+    /// the library only holds effects that can be saved to a device.
+    pub fn insert(&mut self, effect: EffectConfig) -> Result<Option<EffectConfig>, TrimlightError> {
+        effect.to_effect()?;
+        match self.effects.iter_mut().find(|e| e.name == effect.name) {
+            Some(existing) => Ok(Some(std::mem::replace(existing, effect))),
+            None => {
+                self.effects.push(effect);
+                Ok(None)
+            }
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<EffectConfig> {
+        let index = self.effects.iter().position(|e| e.name == name)?;
+        Some(self.effects.remove(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn effect(name: &str, built_in: i32) -> EffectConfig {
+        EffectConfig {
+            name: name.to_string(),
+            built_in: Some(built_in),
+            pattern: None,
+            speed: 100,
+            brightness: 100,
+            pixel_len: None,
+            reverse: None,
+            pixels: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_library_round_trip() {
+        let path = std::env::temp_dir()
+            .join(format!("trimlight-library-{}", std::process::id()))
+            .join("effects.toml");
+        let _ = std::fs::remove_file(&path);
+
+        let mut library = EffectLibrary::open(&path).unwrap();
+        assert!(library.effects().is_empty());
+        assert_eq!(library.insert(effect("Warm White", 0)).unwrap(), None);
+        assert_eq!(library.insert(effect("Storm", 42)).unwrap(), None);
+        let replaced = library.insert(effect("Storm", 43)).unwrap();
+        assert_eq!(replaced.map(|e| e.built_in), Some(Some(42)));
+        library.save().unwrap();
+
+        let mut reopened = EffectLibrary::open(&path).unwrap();
+        assert_eq!(reopened.effects(), library.effects());
+        assert_eq!(reopened.get("Storm").unwrap().built_in, Some(43));
+        assert!(reopened.remove("Warm White").is_some());
+        assert!(reopened.remove("Warm White").is_none());
+        assert_eq!(reopened.effects().len(), 1);
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_file_errors_are_io_errors() {
+        let dir = std::env::temp_dir().join(format!("trimlight-library-io-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let result = EffectLibrary::open(&dir);
+        assert!(matches!(result, Err(TrimlightError::IoError { .. })));
+        let library = EffectLibrary {
+            path: dir.clone(),
+            effects: Vec::new(),
+        };
+        assert!(matches!(
+            library.save(),
+            Err(TrimlightError::IoError { .. })
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_insert_rejects_invalid_effects() {
+        let mut library = EffectLibrary::open("unused.toml").unwrap();
+        assert!(library.insert(effect("Too Far", 180)).is_err());
+        assert!(library.effects().is_empty());
+    }
+}
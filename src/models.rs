@@ -2,6 +2,8 @@
 pub mod device;
 #[path = "models/effect.rs"]
 pub mod effect;
+#[path = "models/extra.rs"]
+pub mod extra;
 #[path = "models/response.rs"]
 pub mod response;
 #[path = "models/schedule.rs"]
@@ -9,5 +11,6 @@ pub mod schedule;
 
 pub use device::*;
 pub use effect::*;
+pub use extra::{Extra, UnknownFields};
 pub use response::*;
 pub use schedule::*;
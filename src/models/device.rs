@@ -1,5 +1,8 @@
-use super::effect::Effect;
-use super::schedule::{CalendarSchedule, DailySchedule};
+use super::effect::{Effect, OverlayEffect};
+use super::extra::{collect_extra, join_path, Extra, UnknownFields};
+use super::schedule::{CalendarSchedule, DailySchedule, DeviceSchedules};
+use crate::error::TrimlightError;
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -19,6 +22,14 @@ pub struct Device {
     #[serde(default)]
     #[serde(rename = "fwVersionName")]
     pub fw_version_name: String,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl UnknownFields for Device {
+    fn collect_unknown_fields(&self, path: &str, out: &mut Vec<String>) {
+        collect_extra(&self.extra, path, out);
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -29,6 +40,16 @@ pub struct DeviceListResponse {
     pub current: i32,
     #[serde(default)]
     pub data: Vec<Device>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl UnknownFields for DeviceListResponse {
+    fn collect_unknown_fields(&self, path: &str, out: &mut Vec<String>) {
+        collect_extra(&self.extra, path, out);
+        self.data
+            .collect_unknown_fields(&join_path(path, "data"), out);
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -39,9 +60,17 @@ pub struct Port {
     pub start: i32,
     #[serde(default)]
     pub end: i32,
+    #[serde(flatten)]
+    pub extra: Extra,
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+impl UnknownFields for Port {
+    fn collect_unknown_fields(&self, path: &str, out: &mut Vec<String>) {
+        collect_extra(&self.extra, path, out);
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq, Eq)]
 pub struct DeviceDateTime {
     #[serde(default)]
     pub year: i32,
@@ -57,6 +86,84 @@ pub struct DeviceDateTime {
     pub minutes: i32,
     #[serde(default)]
     pub seconds: i32,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl UnknownFields for DeviceDateTime {
+    fn collect_unknown_fields(&self, path: &str, out: &mut Vec<String>) {
+        collect_extra(&self.extra, path, out);
+    }
+}
+
+impl From<NaiveDateTime> for DeviceDateTime {
+    fn from(datetime: NaiveDateTime) -> Self {
+        Self {
+            year: datetime.year() % 100,
+            month: datetime.month() as i32,
+            day: datetime.day() as i32,
+            // The device numbers weekdays from 1 (Sunday) to 7 (Saturday)
+            weekday: datetime.weekday().num_days_from_sunday() as i32 + 1,
+            hours: datetime.hour() as i32,
+            minutes: datetime.minute() as i32,
+            seconds: datetime.second() as i32,
+            ..Default::default()
+        }
+    }
+}
+
+impl TryFrom<DeviceDateTime> for NaiveDateTime {
+    type Error = TrimlightError;
+
+    /// The device reports the year relative to 2000. The weekday is derived
+    /// from the date and is not checked.
+    fn try_from(datetime: DeviceDateTime) -> Result<Self, Self::Error> {
+        let year = if (0..100).contains(&datetime.year) {
+            2000 + datetime.year
+        } else {
+            datetime.year
+        };
+        u32::try_from(datetime.month)
+            .ok()
+            .zip(u32::try_from(datetime.day).ok())
+            .and_then(|(month, day)| NaiveDate::from_ymd_opt(year, month, day))
+            .and_then(|date| {
+                date.and_hms_opt(
+                    u32::try_from(datetime.hours).ok()?,
+                    u32::try_from(datetime.minutes).ok()?,
+                    u32::try_from(datetime.seconds).ok()?,
+                )
+            })
+            .ok_or_else(|| TrimlightError::ApiError {
+                code: 400,
+                message: "Invalid device date and time".to_string(),
+            })
+    }
+}
+
+/// Something on a device that refers to a saved effect by ID
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EffectReference {
+    DailySchedule { id: i32 },
+    CalendarSchedule { id: i32 },
+    CombinedEffect,
+    Overlay { overlay_type: i32 },
+}
+
+impl std::fmt::Display for EffectReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EffectReference::DailySchedule { id } => write!(f, "daily schedule {}", id),
+            EffectReference::CalendarSchedule { id } => write!(f, "calendar schedule {}", id),
+            EffectReference::CombinedEffect => write!(f, "combined effect"),
+            EffectReference::Overlay { overlay_type: 0 } => write!(f, "lightning overlay"),
+            EffectReference::Overlay { overlay_type: 1 } => write!(f, "snow overlay"),
+            EffectReference::Overlay { overlay_type } => {
+                write!(f, "overlay type {}", overlay_type)
+            }
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -98,4 +205,383 @@ pub struct DeviceDetails {
     #[serde(default)]
     #[serde(rename = "currentDatetime")]
     pub current_datetime: DeviceDateTime,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl UnknownFields for DeviceDetails {
+    fn collect_unknown_fields(&self, path: &str, out: &mut Vec<String>) {
+        collect_extra(&self.extra, path, out);
+        self.ports
+            .collect_unknown_fields(&join_path(path, "ports"), out);
+        self.effects
+            .collect_unknown_fields(&join_path(path, "effects"), out);
+        self.combined_effect
+            .collect_unknown_fields(&join_path(path, "combinedEffect"), out);
+        self.daily
+            .collect_unknown_fields(&join_path(path, "daily"), out);
+        self.calendar
+            .collect_unknown_fields(&join_path(path, "calendar"), out);
+        self.current_effect
+            .collect_unknown_fields(&join_path(path, "currentEffect"), out);
+        self.overlay_effects
+            .collect_unknown_fields(&join_path(path, "overlayEffects"), out);
+        self.current_datetime
+            .collect_unknown_fields(&join_path(path, "currentDatetime"), out);
+    }
+}
+
+impl DeviceDetails {
+    /// Time on the device clock, or the local time when the device did not
+    /// report a valid one
+    pub fn current_time(&self) -> NaiveDateTime {
+        NaiveDateTime::try_from(self.current_datetime.clone())
+            .unwrap_or_else(|_| chrono::Local::now().naive_local())
+    }
+
+    /// Date on the device clock, see [`DeviceDetails::current_time`]
+    pub fn current_date(&self) -> NaiveDate {
+        self.current_time().date()
+    }
+
+    /// Copy of the daily and calendar schedules
+    pub fn schedules(&self) -> DeviceSchedules {
+        DeviceSchedules {
+            daily: self.daily.clone(),
+            calendar: self.calendar.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Saved effect with the given ID
+    pub fn effect_by_id(&self, id: i32) -> Option<&Effect> {
+        self.effects.iter().find(|e| e.id == id)
+    }
+
+    /// Saved effect with the given name
+    pub fn effect_by_name(&self, name: &str) -> Option<&Effect> {
+        self.effects.iter().find(|e| e.name == name)
+    }
+
+    /// Name of the saved effect with the given ID
+    pub fn effect_name(&self, id: i32) -> Option<&str> {
+        self.effect_by_id(id).map(|e| e.name.as_str())
+    }
+
+    /// Daily schedules that run the given effect
+    pub fn daily_schedules_for_effect(&self, effect_id: i32) -> Vec<&DailySchedule> {
+        self.daily
+            .iter()
+            .filter(|s| s.effect_id == effect_id)
+            .collect()
+    }
+
+    /// Calendar schedules that run the given effect
+    pub fn calendar_schedules_for_effect(&self, effect_id: i32) -> Vec<&CalendarSchedule> {
+        self.calendar
+            .iter()
+            .filter(|s| s.effect_id == effect_id)
+            .collect()
+    }
+
+    /// The combined effect sequence, with each ID resolved to its saved effect
+    ///
+    /// IDs that don't match a saved effect resolve to `None`.
+    pub fn combined_effects(&self) -> Vec<(i32, Option<&Effect>)> {
+        self.combined_effect
+            .iter()
+            .flat_map(|c| c.effect_ids.iter())
+            .map(|&id| (id, self.effect_by_id(id)))
+            .collect()
+    }
+
+    /// Overlay effects that target the given effect
+    pub fn overlays_for_effect(&self, effect_id: i32) -> Vec<&OverlayEffect> {
+        self.overlay_effects
+            .iter()
+            .filter(|o| o.target_effect == effect_id)
+            .collect()
+    }
+
+    /// Everything on the device that refers to the given effect
+    pub fn effect_references(&self, effect_id: i32) -> Vec<EffectReference> {
+        let mut references: Vec<EffectReference> = self
+            .daily_schedules_for_effect(effect_id)
+            .iter()
+            .map(|s| EffectReference::DailySchedule { id: s.id })
+            .collect();
+        references.extend(
+            self.calendar_schedules_for_effect(effect_id)
+                .iter()
+                .map(|s| EffectReference::CalendarSchedule { id: s.id }),
+        );
+        if self
+            .combined_effects()
+            .iter()
+            .any(|(id, _)| *id == effect_id)
+        {
+            references.push(EffectReference::CombinedEffect);
+        }
+        references.extend(self.overlays_for_effect(effect_id).iter().map(|o| {
+            EffectReference::Overlay {
+                overlay_type: o.overlay_type,
+            }
+        }));
+        references
+    }
+
+    /// Saved effects nothing on the device uses
+    ///
+    /// An effect is unused when no schedule, combined effect or overlay refers
+    /// to it and it isn't the effect the device is currently running.
+    pub fn unused_effects(&self) -> Vec<&Effect> {
+        let current = self.current_saved_effect().map(|e| e.id);
+        self.effects
+            .iter()
+            .filter(|e| Some(e.id) != current && self.effect_references(e.id).is_empty())
+            .collect()
+    }
+
+    /// Overlay effects paired with the saved effect they target
+    pub fn overlay_targets(&self) -> Vec<(&OverlayEffect, Option<&Effect>)> {
+        self.overlay_effects
+            .iter()
+            .map(|o| (o, self.effect_by_id(o.target_effect)))
+            .collect()
+    }
+
+    /// The saved effect the device is currently running, if any
+    ///
+    /// Matches on ID when the device reports one, otherwise on settings. A
+    /// preview that hasn't been saved (ID -1) only matches a saved effect with
+    /// identical settings.
+    pub fn current_saved_effect(&self) -> Option<&Effect> {
+        let current = self.current_effect.as_ref()?;
+        if current.id >= 0 {
+            if let Some(effect) = self
+                .effect_by_id(current.id)
+                .filter(|e| e.has_same_settings(current))
+            {
+                return Some(effect);
+            }
+        }
+        self.effects.iter().find(|e| e.has_same_settings(current))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_datetime_from_chrono() {
+        let datetime = NaiveDate::from_ymd_opt(2024, 9, 26)
+            .unwrap()
+            .and_hms_opt(16, 20, 2)
+            .unwrap();
+        assert_eq!(
+            DeviceDateTime::from(datetime),
+            DeviceDateTime {
+                year: 24,
+                month: 9,
+                day: 26,
+                weekday: 5, // Thursday
+                hours: 16,
+                minutes: 20,
+                seconds: 2,
+                ..Default::default()
+            }
+        );
+
+        let sunday = NaiveDate::from_ymd_opt(2026, 10, 18)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        assert_eq!(DeviceDateTime::from(sunday).weekday, 1);
+    }
+
+    #[test]
+    fn test_device_datetime_to_chrono() {
+        let datetime = DeviceDateTime {
+            year: 24,
+            month: 2,
+            day: 29,
+            weekday: 5,
+            hours: 23,
+            minutes: 59,
+            seconds: 59,
+            ..Default::default()
+        };
+        let naive = NaiveDateTime::try_from(datetime.clone()).unwrap();
+        assert_eq!(naive.to_string(), "2024-02-29 23:59:59");
+        assert_eq!(DeviceDateTime::from(naive), datetime);
+
+        let invalid = DeviceDateTime {
+            year: 25,
+            month: 2,
+            day: 29,
+            ..Default::default()
+        };
+        assert!(NaiveDateTime::try_from(invalid).is_err());
+    }
+
+    #[test]
+    fn test_unknown_fields_round_trip() {
+        let json = serde_json::json!({
+            "name": "Front",
+            "switchState": 1,
+            "connectivity": 1,
+            "state": 0,
+            "colorOrder": 0,
+            "ic": 0,
+            "ports": [{ "id": 0, "start": 1, "end": 100, "voltage": 12 }],
+            "fwVersionName": "1.1.1",
+            "effects": [{
+                "id": 0,
+                "name": "New Year",
+                "category": 1,
+                "mode": 0,
+                "speed": 100,
+                "brightness": 100,
+                "pixelLen": 30,
+                "reverse": false,
+                "transition": { "fade": true }
+            }],
+            "combinedEffect": null,
+            "daily": [],
+            "calendar": [],
+            "currentEffect": null,
+            "overlayEffects": [],
+            "currentDatetime": {
+                "year": 24,
+                "month": 9,
+                "day": 26,
+                "weekday": 5,
+                "hours": 16,
+                "minutes": 20,
+                "seconds": 2
+            },
+            "timezone": "America/Chicago"
+        });
+
+        let details: DeviceDetails = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(details.extra["timezone"], "America/Chicago");
+        assert_eq!(serde_json::to_value(&details).unwrap(), json);
+        assert_eq!(
+            details.unknown_fields(),
+            vec![
+                "timezone".to_string(),
+                "ports[0].voltage".to_string(),
+                "effects[0].transition".to_string(),
+            ]
+        );
+    }
+
+    fn sample_details() -> DeviceDetails {
+        serde_json::from_value(serde_json::json!({
+            "effects": [
+                { "id": 1, "name": "Candy Cane", "category": 2, "mode": 1, "speed": 100, "brightness": 200,
+                  "pixels": [{ "index": 0, "count": 5, "color": 16711680, "disable": false }] },
+                { "id": 2, "name": "Rainbow", "category": 1, "mode": 0, "speed": 150, "brightness": 255,
+                  "pixelLen": 30, "reverse": false }
+            ],
+            "combinedEffect": { "effectIds": [2, 7, 1], "interval": 5 },
+            "daily": [
+                { "id": 0, "enable": true, "effectId": 2, "repetition": 1,
+                  "startTime": { "hours": 18, "minutes": 0 }, "endTime": { "hours": 23, "minutes": 0 } },
+                { "id": 1, "enable": false, "effectId": 1, "repetition": 3,
+                  "startTime": { "hours": 17, "minutes": 0 }, "endTime": { "hours": 22, "minutes": 0 } }
+            ],
+            "calendar": [
+                { "id": 0, "effectId": 1, "startDate": { "month": 12, "day": 1 }, "endDate": { "month": 12, "day": 31 },
+                  "startTime": { "hours": 17, "minutes": 0 }, "endTime": { "hours": 23, "minutes": 0 } }
+            ],
+            "currentEffect": { "id": -1, "category": 1, "mode": 0, "speed": 150, "brightness": 255,
+                               "pixelLen": 30, "reverse": false },
+            "overlayEffects": [{ "overlayType": 1, "targetEffect": 1 }, { "overlayType": 0, "targetEffect": 9 }]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_effect_lookups() {
+        let details = sample_details();
+
+        assert_eq!(
+            details.effect_by_id(2).map(|e| e.name.as_str()),
+            Some("Rainbow")
+        );
+        assert!(details.effect_by_id(3).is_none());
+        assert_eq!(details.effect_by_name("Candy Cane").map(|e| e.id), Some(1));
+        assert_eq!(details.effect_name(1), Some("Candy Cane"));
+    }
+
+    #[test]
+    fn test_schedule_references() {
+        let details = sample_details();
+
+        let daily: Vec<i32> = details
+            .daily_schedules_for_effect(1)
+            .iter()
+            .map(|s| s.id)
+            .collect();
+        assert_eq!(daily, vec![1]);
+        assert_eq!(details.calendar_schedules_for_effect(1).len(), 1);
+        assert!(details.calendar_schedules_for_effect(2).is_empty());
+    }
+
+    #[test]
+    fn test_combined_and_overlays() {
+        let details = sample_details();
+
+        let combined: Vec<(i32, Option<&str>)> = details
+            .combined_effects()
+            .into_iter()
+            .map(|(id, e)| (id, e.map(|e| e.name.as_str())))
+            .collect();
+        assert_eq!(
+            combined,
+            vec![(2, Some("Rainbow")), (7, None), (1, Some("Candy Cane"))]
+        );
+
+        assert_eq!(details.overlays_for_effect(1).len(), 1);
+        let targets = details.overlay_targets();
+        assert_eq!(targets[0].1.map(|e| e.id), Some(1));
+        assert!(targets[1].1.is_none());
+    }
+
+    #[test]
+    fn test_current_saved_effect() {
+        let mut details = sample_details();
+        assert_eq!(details.current_saved_effect().map(|e| e.id), Some(2));
+
+        details.current_effect.as_mut().unwrap().brightness = 10;
+        assert!(details.current_saved_effect().is_none());
+
+        details.current_effect = None;
+        assert!(details.current_saved_effect().is_none());
+    }
+
+    #[test]
+    fn test_unused_effects() {
+        let mut details = sample_details();
+        details.effects.push(Effect {
+            id: 3,
+            name: "Old Test".to_string(),
+            ..Default::default()
+        });
+        let unused = |details: &DeviceDetails| -> Vec<i32> {
+            details.unused_effects().iter().map(|e| e.id).collect()
+        };
+        assert_eq!(unused(&details), vec![3]);
+
+        // Rainbow is only used by a schedule and the combined effect, but it's
+        // also running right now
+        details.daily.retain(|s| s.effect_id != 2);
+        details.combined_effect = None;
+        assert_eq!(unused(&details), vec![3]);
+
+        details.current_effect = None;
+        assert_eq!(unused(&details), vec![2, 3]);
+    }
 }
@@ -1,6 +1,19 @@
+use super::extra::{collect_extra, join_path, Extra, UnknownFields};
+use crate::error::TrimlightError;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+/// Effect category value used by the API for built-in effects
+pub const BUILTIN_CATEGORY: i32 = 1;
+/// Other category value the API uses for built-in effects
+///
+/// The device details and preview examples in the API documentation use 0
+/// for built-in effects, while its field tables say 1. Both are read as
+/// built-in; this library always writes [`BUILTIN_CATEGORY`].
+pub const ALT_BUILTIN_CATEGORY: i32 = 0;
+/// Effect category value used by the API for custom effects
+pub const CUSTOM_CATEGORY: i32 = 2;
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
 pub struct Effect {
     #[serde(default)]
     pub id: i32,
@@ -15,14 +28,182 @@ pub struct Effect {
     #[serde(default)]
     pub brightness: i32,
     #[serde(default)]
+    #[serde(rename = "pixelLen", alias = "pixel_len")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub pixel_len: Option<i32>,
     #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub reverse: Option<bool>,
     #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub pixels: Option<Vec<Pixel>>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl UnknownFields for Effect {
+    fn collect_unknown_fields(&self, path: &str, out: &mut Vec<String>) {
+        collect_extra(&self.extra, path, out);
+        self.pixels
+            .collect_unknown_fields(&join_path(path, "pixels"), out);
+    }
+}
+
+/// What [`TrimlightClient::delete_effect`](crate::TrimlightClient::delete_effect)
+/// does about schedules, the combined effect and overlays that use the effect
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeleteMode {
+    /// Fail with [`TrimlightError::EffectInUse`] when anything uses the effect
+    #[default]
+    Safe,
+    /// Delete the effect anyway, leaving references to a missing effect
+    Force,
+    /// Delete the schedules that run the effect and remove it from the
+    /// combined effect and overlays first
+    Cascade,
+}
+
+/// A built-in effect mode (0-179)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BuiltinMode(i32);
+
+impl BuiltinMode {
+    pub const MAX: i32 = 179;
+
+    pub fn value(self) -> i32 {
+        self.0
+    }
+}
+
+impl TryFrom<i32> for BuiltinMode {
+    type Error = TrimlightError;
+
+    fn try_from(mode: i32) -> Result<Self, Self::Error> {
+        if !(0..=Self::MAX).contains(&mode) {
+            return Err(TrimlightError::ApiError {
+                code: 400,
+                message: format!("Invalid built-in mode {}. Must be between 0 and 179", mode),
+            });
+        }
+        Ok(Self(mode))
+    }
+}
+
+/// A custom effect pattern mode (0-16)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CustomMode(i32);
+
+impl CustomMode {
+    pub const MAX: i32 = 16;
+
+    pub fn value(self) -> i32 {
+        self.0
+    }
+}
+
+impl TryFrom<i32> for CustomMode {
+    type Error = TrimlightError;
+
+    fn try_from(mode: i32) -> Result<Self, Self::Error> {
+        if !(0..=Self::MAX).contains(&mode) {
+            return Err(TrimlightError::ApiError {
+                code: 400,
+                message: format!("Invalid custom mode {}. Must be between 0 and 16", mode),
+            });
+        }
+        Ok(Self(mode))
+    }
+}
+
+/// Category-specific settings of an effect
+///
+/// Built-in effects carry a pixel length and direction, custom effects carry
+/// their pixel segments. Use [`Effect::kind`] to read and [`Effect::set_kind`]
+/// to write them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EffectKind {
+    Builtin {
+        mode: BuiltinMode,
+        pixel_len: i32,
+        reverse: bool,
+    },
+    Custom {
+        mode: CustomMode,
+        pixels: Vec<Pixel>,
+    },
+}
+
+impl Effect {
+    /// Default pixel length used when a built-in effect does not specify one
+    pub const DEFAULT_PIXEL_LEN: i32 = 30;
+
+    /// Interpret the category-specific fields of this effect
+    ///
+    /// Fields belonging to the other category are ignored. Category 0 is
+    /// read as built-in (see [`ALT_BUILTIN_CATEGORY`]). Fails when the
+    /// category is unknown or the mode is out of range for the category.
+    pub fn kind(&self) -> Result<EffectKind, TrimlightError> {
+        match self.category {
+            BUILTIN_CATEGORY | ALT_BUILTIN_CATEGORY => Ok(EffectKind::Builtin {
+                mode: BuiltinMode::try_from(self.mode)?,
+                pixel_len: self.pixel_len.unwrap_or(Self::DEFAULT_PIXEL_LEN),
+                reverse: self.reverse.unwrap_or(false),
+            }),
+            CUSTOM_CATEGORY => Ok(EffectKind::Custom {
+                mode: CustomMode::try_from(self.mode)?,
+                pixels: self.pixels.clone().unwrap_or_default(),
+            }),
+            category => Err(TrimlightError::ApiError {
+                code: 400,
+                message: format!("Unknown effect category {}", category),
+            }),
+        }
+    }
+
+    /// Whether both effects produce the same animation
+    ///
+    /// Compares everything except the ID, the name and unknown fields.
+    pub fn has_same_settings(&self, other: &Effect) -> bool {
+        let same_kind = match (self.kind(), other.kind()) {
+            (Ok(kind), Ok(other_kind)) => kind == other_kind,
+            _ => self.category == other.category && self.mode == other.mode,
+        };
+        same_kind && self.speed == other.speed && self.brightness == other.brightness
+    }
+
+    /// Whether the category is one of the values used for built-in effects
+    pub fn is_builtin(&self) -> bool {
+        matches!(self.category, BUILTIN_CATEGORY | ALT_BUILTIN_CATEGORY)
+    }
+
+    /// Replace the category-specific fields of this effect
+    ///
+    /// Fields that do not belong to the new category are cleared.
+    pub fn set_kind(&mut self, kind: EffectKind) {
+        match kind {
+            EffectKind::Builtin {
+                mode,
+                pixel_len,
+                reverse,
+            } => {
+                self.category = BUILTIN_CATEGORY;
+                self.mode = mode.value();
+                self.pixel_len = Some(pixel_len);
+                self.reverse = Some(reverse);
+                self.pixels = None;
+            }
+            EffectKind::Custom { mode, pixels } => {
+                self.category = CUSTOM_CATEGORY;
+                self.mode = mode.value();
+                self.pixel_len = None;
+                self.reverse = None;
+                self.pixels = Some(pixels);
+            }
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
 pub struct Pixel {
     #[serde(default)]
     pub index: i32,
@@ -32,6 +213,14 @@ pub struct Pixel {
     pub color: i32,
     #[serde(default)]
     pub disable: bool,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl UnknownFields for Pixel {
+    fn collect_unknown_fields(&self, path: &str, out: &mut Vec<String>) {
+        collect_extra(&self.extra, path, out);
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -41,6 +230,14 @@ pub struct CombinedEffect {
     pub effect_ids: Vec<i32>,
     #[serde(default)]
     pub interval: i32,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl UnknownFields for CombinedEffect {
+    fn collect_unknown_fields(&self, path: &str, out: &mut Vec<String>) {
+        collect_extra(&self.extra, path, out);
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -51,4 +248,152 @@ pub struct OverlayEffect {
     #[serde(default)]
     #[serde(rename = "targetEffect")]
     pub target_effect: i32,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl UnknownFields for OverlayEffect {
+    fn collect_unknown_fields(&self, path: &str, out: &mut Vec<String>) {
+        collect_extra(&self.extra, path, out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_kind() {
+        let effect: Effect = serde_json::from_value(serde_json::json!({
+            "id": 3,
+            "name": "Rainbow",
+            "category": 1,
+            "mode": 4,
+            "speed": 100,
+            "brightness": 200,
+            "pixelLen": 45,
+            "reverse": true
+        }))
+        .unwrap();
+
+        assert_eq!(
+            effect.kind().unwrap(),
+            EffectKind::Builtin {
+                mode: BuiltinMode::try_from(4).unwrap(),
+                pixel_len: 45,
+                reverse: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_category_zero_is_builtin() {
+        // As in the device details example of the API documentation
+        let effect: Effect = serde_json::from_value(serde_json::json!({
+            "id": 0,
+            "name": "New Year",
+            "category": 0,
+            "mode": 0,
+            "speed": 100,
+            "brightness": 100,
+            "pixelLen": 30,
+            "reverse": false
+        }))
+        .unwrap();
+        assert!(effect.is_builtin());
+        assert!(matches!(effect.kind(), Ok(EffectKind::Builtin { .. })));
+
+        let mut saved = effect.clone();
+        saved.set_kind(effect.kind().unwrap());
+        assert_eq!(saved.category, BUILTIN_CATEGORY);
+        assert!(saved.has_same_settings(&effect));
+    }
+
+    #[test]
+    fn test_custom_kind() {
+        let effect: Effect = serde_json::from_value(serde_json::json!({
+            "id": 4,
+            "name": "Candy Cane",
+            "category": 2,
+            "mode": 1,
+            "speed": 100,
+            "brightness": 200,
+            "pixels": [{ "index": 0, "count": 5, "color": 16711680, "disable": false }]
+        }))
+        .unwrap();
+
+        match effect.kind().unwrap() {
+            EffectKind::Custom { mode, pixels } => {
+                assert_eq!(mode.value(), 1);
+                assert_eq!(pixels.len(), 1);
+                assert_eq!(pixels[0].count, 5);
+            }
+            kind => panic!("unexpected kind {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn test_kind_invalid() {
+        let effect = Effect {
+            category: 2,
+            mode: 17,
+            ..Default::default()
+        };
+        assert!(effect.kind().is_err());
+
+        let effect = Effect {
+            category: 7,
+            ..Default::default()
+        };
+        assert!(matches!(
+            effect.kind(),
+            Err(TrimlightError::ApiError { code: 400, message }) if message == "Unknown effect category 7"
+        ));
+    }
+
+    #[test]
+    fn test_set_kind_clears_other_category() {
+        let mut effect = Effect {
+            category: 1,
+            mode: 10,
+            pixel_len: Some(30),
+            reverse: Some(false),
+            ..Default::default()
+        };
+        effect.set_kind(EffectKind::Custom {
+            mode: CustomMode::try_from(0).unwrap(),
+            pixels: vec![Pixel::default()],
+        });
+
+        assert_eq!(effect.category, CUSTOM_CATEGORY);
+        assert_eq!(effect.pixel_len, None);
+        assert_eq!(effect.reverse, None);
+        assert_eq!(effect.pixels.as_ref().map(Vec::len), Some(1));
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let json = serde_json::json!({
+            "id": 3,
+            "name": "Rainbow",
+            "category": 1,
+            "mode": 4,
+            "speed": 100,
+            "brightness": 200,
+            "pixelLen": 45,
+            "reverse": true
+        });
+        let effect: Effect = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&effect).unwrap(), json);
+
+        let mut rebuilt = Effect {
+            id: 3,
+            name: "Rainbow".to_string(),
+            speed: 100,
+            brightness: 200,
+            ..Default::default()
+        };
+        rebuilt.set_kind(effect.kind().unwrap());
+        assert_eq!(rebuilt, effect);
+    }
 }
@@ -0,0 +1,53 @@
+//! Support for fields the library does not know about yet.
+//!
+//! Firmware updates regularly add fields to the device shadow. Every model
+//! captures keys it does not declare in an `extra` map and writes them back
+//! when serialized, so read-modify-write flows don't drop them.
+//! [`UnknownFields`] lists those keys, which is how the client's strict mode
+//! reports API changes.
+
+use serde_json::{Map, Value};
+
+/// Keys that were present in the JSON but are not declared by the model
+pub type Extra = Map<String, Value>;
+
+pub trait UnknownFields {
+    /// Append the paths of unknown fields under `path` to `out`
+    fn collect_unknown_fields(&self, path: &str, out: &mut Vec<String>);
+
+    /// Paths of all unknown fields in this value and its children,
+    /// e.g. `effects[0].newField`
+    fn unknown_fields(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        self.collect_unknown_fields("", &mut out);
+        out
+    }
+}
+
+pub(crate) fn join_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+pub(crate) fn collect_extra(extra: &Extra, path: &str, out: &mut Vec<String>) {
+    out.extend(extra.keys().map(|key| join_path(path, key)));
+}
+
+impl<T: UnknownFields> UnknownFields for Vec<T> {
+    fn collect_unknown_fields(&self, path: &str, out: &mut Vec<String>) {
+        for (i, item) in self.iter().enumerate() {
+            item.collect_unknown_fields(&format!("{}[{}]", path, i), out);
+        }
+    }
+}
+
+impl<T: UnknownFields> UnknownFields for Option<T> {
+    fn collect_unknown_fields(&self, path: &str, out: &mut Vec<String>) {
+        if let Some(value) = self {
+            value.collect_unknown_fields(path, out);
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use super::extra::{collect_extra, Extra, UnknownFields};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -6,6 +7,8 @@ pub struct ApiResponse<T> {
     pub desc: String,
     #[serde(default)]
     pub payload: Option<T>,
+    #[serde(flatten)]
+    pub extra: Extra,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -14,4 +17,33 @@ pub struct BasicResponse {
     pub code: i32,
     #[serde(default)]
     pub desc: String,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// What an `ensure_*` method did to bring the device in line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum EnsureAction {
+    /// Nothing matched so it was created, with the ID when it could be found
+    Created { id: Option<i32> },
+    /// A match existed with different settings and was changed
+    Updated { id: i32 },
+    /// A match already had these settings
+    Unchanged { id: i32 },
+}
+
+impl EnsureAction {
+    pub fn id(&self) -> Option<i32> {
+        match self {
+            EnsureAction::Created { id } => *id,
+            EnsureAction::Updated { id } | EnsureAction::Unchanged { id } => Some(*id),
+        }
+    }
+}
+
+impl UnknownFields for BasicResponse {
+    fn collect_unknown_fields(&self, path: &str, out: &mut Vec<String>) {
+        collect_extra(&self.extra, path, out);
+    }
 }
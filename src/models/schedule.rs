@@ -1,22 +1,147 @@
+use super::extra::{collect_extra, join_path, Extra, UnknownFields};
+use crate::error::TrimlightError;
+use chrono::{Datelike, NaiveDate, NaiveTime, Timelike};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+/// Compared on hours and minutes only; unknown fields are ignored
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
 pub struct ScheduleTime {
     #[serde(default)]
     pub hours: i32,
     #[serde(default)]
     pub minutes: i32,
+    #[serde(flatten)]
+    pub extra: Extra,
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+impl PartialEq for ScheduleTime {
+    fn eq(&self, other: &Self) -> bool {
+        self.hours == other.hours && self.minutes == other.minutes
+    }
+}
+
+impl Eq for ScheduleTime {}
+
+impl UnknownFields for ScheduleTime {
+    fn collect_unknown_fields(&self, path: &str, out: &mut Vec<String>) {
+        collect_extra(&self.extra, path, out);
+    }
+}
+
+impl ScheduleTime {
+    /// Create a schedule time, validating the hour (0-23) and minute (0-59)
+    pub fn new(hours: i32, minutes: i32) -> Result<Self, TrimlightError> {
+        if !(0..=23).contains(&hours) || !(0..=59).contains(&minutes) {
+            return Err(TrimlightError::ApiError {
+                code: 400,
+                message: "Invalid time values".to_string(),
+            });
+        }
+        Ok(Self {
+            hours,
+            minutes,
+            ..Default::default()
+        })
+    }
+}
+
+impl From<NaiveTime> for ScheduleTime {
+    fn from(time: NaiveTime) -> Self {
+        Self {
+            hours: time.hour() as i32,
+            minutes: time.minute() as i32,
+            ..Default::default()
+        }
+    }
+}
+
+impl TryFrom<ScheduleTime> for NaiveTime {
+    type Error = TrimlightError;
+
+    fn try_from(time: ScheduleTime) -> Result<Self, Self::Error> {
+        let time = ScheduleTime::new(time.hours, time.minutes)?;
+        Ok(
+            NaiveTime::from_hms_opt(time.hours as u32, time.minutes as u32, 0)
+                .expect("validated schedule time"),
+        )
+    }
+}
+
+/// Compared on month and day only; unknown fields are ignored
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
 pub struct ScheduleDate {
     #[serde(default)]
     pub month: i32,
     #[serde(default)]
     pub day: i32,
+    #[serde(flatten)]
+    pub extra: Extra,
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+impl PartialEq for ScheduleDate {
+    fn eq(&self, other: &Self) -> bool {
+        self.month == other.month && self.day == other.day
+    }
+}
+
+impl Eq for ScheduleDate {}
+
+impl UnknownFields for ScheduleDate {
+    fn collect_unknown_fields(&self, path: &str, out: &mut Vec<String>) {
+        collect_extra(&self.extra, path, out);
+    }
+}
+
+impl ScheduleDate {
+    /// Create a schedule date, validating the day against the length of the month
+    ///
+    /// Calendar schedules repeat every year, so February 29 is accepted.
+    pub fn new(month: i32, day: i32) -> Result<Self, TrimlightError> {
+        let date = Self {
+            month,
+            day,
+            ..Default::default()
+        };
+        date.validate()?;
+        Ok(date)
+    }
+
+    /// Number of days in `month`, counting February as 29 days
+    pub fn days_in_month(month: i32) -> Option<i32> {
+        match month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => Some(31),
+            4 | 6 | 9 | 11 => Some(30),
+            2 => Some(29),
+            _ => None,
+        }
+    }
+
+    /// Check that the month exists and the day falls within it
+    pub fn validate(&self) -> Result<(), TrimlightError> {
+        match Self::days_in_month(self.month) {
+            Some(days) if (1..=days).contains(&self.day) => Ok(()),
+            _ => Err(TrimlightError::ApiError {
+                code: 400,
+                message: "Invalid date values".to_string(),
+            }),
+        }
+    }
+}
+
+impl From<NaiveDate> for ScheduleDate {
+    fn from(date: NaiveDate) -> Self {
+        Self {
+            month: date.month() as i32,
+            day: date.day() as i32,
+            ..Default::default()
+        }
+    }
+}
+
+/// Number of daily schedules a device can hold
+pub const DAILY_SCHEDULE_SLOTS: usize = 2;
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
 pub struct DailySchedule {
     #[serde(default)]
     pub id: i32,
@@ -33,9 +158,21 @@ pub struct DailySchedule {
     #[serde(default)]
     #[serde(rename = "endTime")]
     pub end_time: ScheduleTime,
+    #[serde(flatten)]
+    pub extra: Extra,
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+impl UnknownFields for DailySchedule {
+    fn collect_unknown_fields(&self, path: &str, out: &mut Vec<String>) {
+        collect_extra(&self.extra, path, out);
+        self.start_time
+            .collect_unknown_fields(&join_path(path, "startTime"), out);
+        self.end_time
+            .collect_unknown_fields(&join_path(path, "endTime"), out);
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
 pub struct CalendarSchedule {
     #[serde(default)]
     pub id: i32,
@@ -54,6 +191,22 @@ pub struct CalendarSchedule {
     #[serde(default)]
     #[serde(rename = "endTime")]
     pub end_time: ScheduleTime,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl UnknownFields for CalendarSchedule {
+    fn collect_unknown_fields(&self, path: &str, out: &mut Vec<String>) {
+        collect_extra(&self.extra, path, out);
+        self.start_date
+            .collect_unknown_fields(&join_path(path, "startDate"), out);
+        self.end_date
+            .collect_unknown_fields(&join_path(path, "endDate"), out);
+        self.start_time
+            .collect_unknown_fields(&join_path(path, "startTime"), out);
+        self.end_time
+            .collect_unknown_fields(&join_path(path, "endTime"), out);
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -62,4 +215,76 @@ pub struct DeviceSchedules {
     pub daily: Vec<DailySchedule>,
     #[serde(default)]
     pub calendar: Vec<CalendarSchedule>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl UnknownFields for DeviceSchedules {
+    fn collect_unknown_fields(&self, path: &str, out: &mut Vec<String>) {
+        collect_extra(&self.extra, path, out);
+        self.daily
+            .collect_unknown_fields(&join_path(path, "daily"), out);
+        self.calendar
+            .collect_unknown_fields(&join_path(path, "calendar"), out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schedule_time_chrono() {
+        let time = NaiveTime::from_hms_opt(21, 45, 30).unwrap();
+        assert_eq!(
+            ScheduleTime::from(time),
+            ScheduleTime {
+                hours: 21,
+                minutes: 45,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            NaiveTime::try_from(ScheduleTime::new(7, 5).unwrap()).unwrap(),
+            NaiveTime::from_hms_opt(7, 5, 0).unwrap()
+        );
+        assert!(NaiveTime::try_from(ScheduleTime {
+            hours: 24,
+            minutes: 0,
+            ..Default::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_schedule_date_days_per_month() {
+        assert!(ScheduleDate::new(1, 31).is_ok());
+        assert!(ScheduleDate::new(2, 29).is_ok());
+        assert!(ScheduleDate::new(4, 30).is_ok());
+        assert!(ScheduleDate::new(12, 31).is_ok());
+
+        assert!(ScheduleDate::new(2, 30).is_err());
+        assert!(ScheduleDate::new(2, 31).is_err());
+        assert!(ScheduleDate::new(4, 31).is_err());
+        assert!(ScheduleDate::new(6, 31).is_err());
+        assert!(ScheduleDate::new(9, 31).is_err());
+        assert!(ScheduleDate::new(11, 31).is_err());
+        assert!(ScheduleDate::new(0, 1).is_err());
+        assert!(ScheduleDate::new(13, 1).is_err());
+        assert!(ScheduleDate::new(5, 0).is_err());
+    }
+
+    #[test]
+    fn test_equality_ignores_unknown_fields() {
+        let time: ScheduleTime =
+            serde_json::from_value(serde_json::json!({"hours": 18, "minutes": 0, "offset": 5}))
+                .unwrap();
+        assert_eq!(time, ScheduleTime::new(18, 0).unwrap());
+        assert_ne!(time, ScheduleTime::new(18, 1).unwrap());
+
+        let date: ScheduleDate =
+            serde_json::from_value(serde_json::json!({"month": 12, "day": 24, "year": 26}))
+                .unwrap();
+        assert_eq!(date, ScheduleDate::new(12, 24).unwrap());
+    }
 }
@@ -0,0 +1,164 @@
+//! Full-state device snapshots.
+//!
+//! A [`Snapshot`] wraps the complete [`DeviceDetails`] of a device in a
+//! versioned JSON document so it can be kept locally and restored later with
+//! [`TrimlightClient::restore_snapshot`](crate::TrimlightClient::restore_snapshot),
+//! or copied straight to another device with
+//! [`TrimlightClient::clone_device`](crate::TrimlightClient::clone_device).
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::error::{invalid, TrimlightError};
+use crate::models::DeviceDetails;
+
+/// Snapshot format version written by this library
+///
+/// Bump this when the layout of [`Snapshot`] changes in a way older readers
+/// can't handle, and teach [`Snapshot::from_json`] to read the old versions.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    /// When the snapshot was taken, in RFC 3339 format
+    pub created_at: String,
+    /// ID of the device the snapshot was taken from
+    pub device_id: String,
+    pub device: DeviceDetails,
+}
+
+/// How to treat what is already on the target device when copying to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloneMode {
+    /// Delete everything on the target first
+    Replace,
+    /// Keep the target's effects and schedules, updating effects with the same name
+    Merge,
+}
+
+/// What a restore or clone did on the target device
+#[derive(Debug, Default, Serialize)]
+pub struct RestoreReport {
+    /// Source effect IDs mapped to the IDs on the target device
+    pub effect_ids: BTreeMap<i32, i32>,
+    /// Number of effects saved as new effects
+    pub added_effects: usize,
+    /// Number of existing effects with the same name that were changed
+    pub updated_effects: usize,
+    /// Number of daily schedules added
+    pub daily: usize,
+    /// Number of calendar schedules added
+    pub calendar: usize,
+    /// Items that weren't copied, with the reason
+    pub skipped: Vec<String>,
+}
+
+/// Read just the version so unsupported snapshots fail with a clear message
+#[derive(Deserialize)]
+struct SnapshotHeader {
+    version: Option<u32>,
+}
+
+impl Snapshot {
+    pub fn new(device_id: impl Into<String>, device: DeviceDetails) -> Self {
+        Self {
+            version: SNAPSHOT_VERSION,
+            created_at: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            device_id: device_id.into(),
+            device,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, TrimlightError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Parse a snapshot, rejecting versions newer than [`SNAPSHOT_VERSION`]
+    pub fn from_json(text: &str) -> Result<Self, TrimlightError> {
+        let header: SnapshotHeader =
+            serde_json::from_str(text).map_err(|e| invalid(format!("Invalid snapshot: {}", e)))?;
+        match header.version {
+            Some(SNAPSHOT_VERSION) => {}
+            Some(version) => {
+                return Err(invalid(format!(
+                    "Unsupported snapshot version {}. This version of trimlight reads version {}",
+                    version, SNAPSHOT_VERSION
+                )))
+            }
+            None => return Err(invalid("Invalid snapshot: missing version".to_string())),
+        }
+        serde_json::from_str(text).map_err(|e| invalid(format!("Invalid snapshot: {}", e)))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), TrimlightError> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_json()? + "\n").map_err(|source| TrimlightError::IoError {
+            context: format!("Can't write snapshot {}", path.display()),
+            source,
+        })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TrimlightError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|source| TrimlightError::IoError {
+            context: format!("Can't read snapshot {}", path.display()),
+            source,
+        })?;
+        Self::from_json(&text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let device: DeviceDetails = serde_json::from_value(serde_json::json!({
+            "name": "Front",
+            "ports": [{"id": 0, "start": 1, "end": 300}],
+            "effects": [{"id": 4, "name": "Candy Cane", "category": 2, "mode": 1,
+                         "pixels": [{"index": 0, "count": 5, "color": 16711680}]}],
+            "combinedEffect": {"effectIds": [4], "interval": 60},
+            "overlayEffects": [{"overlayType": 1, "targetEffect": 4}],
+            "futureField": true
+        }))
+        .unwrap();
+        let snapshot = Snapshot::new("abc123", device);
+
+        let restored = Snapshot::from_json(&snapshot.to_json().unwrap()).unwrap();
+        assert_eq!(restored.version, SNAPSHOT_VERSION);
+        assert_eq!(restored.device_id, "abc123");
+        assert_eq!(
+            serde_json::to_value(&restored.device).unwrap(),
+            serde_json::to_value(&snapshot.device).unwrap()
+        );
+        assert_eq!(restored.device.extra["futureField"], true);
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let error = Snapshot::from_json(r#"{"version": 2, "device": {}}"#).unwrap_err();
+        assert!(matches!(
+            error,
+            TrimlightError::ApiError { code: 400, message }
+                if message == "Unsupported snapshot version 2. This version of trimlight reads version 1"
+        ));
+        assert!(Snapshot::from_json(r#"{"device": {}}"#).is_err());
+    }
+    #[test]
+    fn test_file_errors_are_io_errors() {
+        let dir = std::env::temp_dir();
+        let snapshot = Snapshot::new("abc123", DeviceDetails::default());
+        assert!(matches!(
+            snapshot.save(&dir),
+            Err(TrimlightError::IoError { .. })
+        ));
+        assert!(matches!(
+            Snapshot::load(dir.join("trimlight-snapshot-missing.json")),
+            Err(TrimlightError::IoError { .. })
+        ));
+    }
+}
@@ -0,0 +1,262 @@
+//! Offline sunrise and sunset times using the NOAA solar calculator equations.
+//!
+//! See <https://gml.noaa.gov/grad/solcalc/calcdetails.html>. Results are
+//! accurate to about a minute between latitudes 72°N and 72°S.
+
+use chrono::{NaiveDate, NaiveTime};
+use std::str::FromStr;
+
+use crate::error::TrimlightError;
+use crate::interval::{minute_of_day, time_of_day, MINUTES_PER_DAY};
+use crate::models::ScheduleTime;
+
+/// Sun altitude at sunrise and sunset, allowing for refraction and the size of the sun
+const SUNRISE_ZENITH: f64 = 90.833;
+
+/// Sunrise and sunset on a day, in local time rounded to the minute
+///
+/// Either is `None` when the sun doesn't rise or set that day, as in polar
+/// summer and winter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SolarTimes {
+    pub sunrise: Option<NaiveTime>,
+    pub sunset: Option<NaiveTime>,
+}
+
+/// Compute sunrise and sunset for `date` at the given position
+///
+/// Latitude is positive to the north, longitude positive to the east, both in
+/// degrees. `utc_offset_minutes` is the local time zone offset on that date,
+/// e.g. -300 for UTC-5.
+pub fn solar_times(
+    date: NaiveDate,
+    latitude: f64,
+    longitude: f64,
+    utc_offset_minutes: i32,
+) -> SolarTimes {
+    let utc_offset_hours = utc_offset_minutes as f64 / 60.0;
+
+    // Julian day and century at local noon
+    let days_since_epoch =
+        (date - NaiveDate::from_ymd_opt(1970, 1, 1).expect("valid date")).num_days() as f64;
+    let julian_day = 2440587.5 + days_since_epoch + (12.0 - utc_offset_hours) / 24.0;
+    let century = (julian_day - 2451545.0) / 36525.0;
+
+    let mean_longitude = (280.46646 + century * (36000.76983 + century * 0.0003032)) % 360.0;
+    let mean_anomaly = 357.52911 + century * (35999.05029 - 0.0001537 * century);
+    let eccentricity = 0.016708634 - century * (0.000042037 + 0.0000001267 * century);
+    let equation_of_center = mean_anomaly.to_radians().sin()
+        * (1.914602 - century * (0.004817 + 0.000014 * century))
+        + (2.0 * mean_anomaly).to_radians().sin() * (0.019993 - 0.000101 * century)
+        + (3.0 * mean_anomaly).to_radians().sin() * 0.000289;
+    let true_longitude = mean_longitude + equation_of_center;
+    let omega = (125.04 - 1934.136 * century).to_radians();
+    let apparent_longitude = true_longitude - 0.00569 - 0.00478 * omega.sin();
+
+    let mean_obliquity = 23.0
+        + (26.0 + (21.448 - century * (46.815 + century * (0.00059 - century * 0.001813))) / 60.0)
+            / 60.0;
+    let obliquity = mean_obliquity + 0.00256 * omega.cos();
+    let declination = (obliquity.to_radians().sin() * apparent_longitude.to_radians().sin()).asin();
+
+    let y = (obliquity.to_radians() / 2.0).tan().powi(2);
+    let (l0, m) = (mean_longitude.to_radians(), mean_anomaly.to_radians());
+    let equation_of_time = 4.0
+        * (y * (2.0 * l0).sin() - 2.0 * eccentricity * m.sin()
+            + 4.0 * eccentricity * y * m.sin() * (2.0 * l0).cos()
+            - 0.5 * y * y * (4.0 * l0).sin()
+            - 1.25 * eccentricity * eccentricity * (2.0 * m).sin())
+        .to_degrees();
+
+    let solar_noon = 720.0 - 4.0 * longitude - equation_of_time + utc_offset_hours * 60.0;
+
+    let latitude = latitude.to_radians();
+    let cos_hour_angle = SUNRISE_ZENITH.to_radians().cos() / (latitude.cos() * declination.cos())
+        - latitude.tan() * declination.tan();
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        return SolarTimes {
+            sunrise: None,
+            sunset: None,
+        };
+    }
+    let hour_angle = cos_hour_angle.acos().to_degrees();
+
+    let at_minute = |minutes: f64| {
+        let minute = (minutes.round() as i64).rem_euclid(MINUTES_PER_DAY as i64) as u32;
+        NaiveTime::from_hms_opt(minute / 60, minute % 60, 0).expect("valid time")
+    };
+    SolarTimes {
+        sunrise: Some(at_minute(solar_noon - hour_angle * 4.0)),
+        sunset: Some(at_minute(solar_noon + hour_angle * 4.0)),
+    }
+}
+
+/// A schedule time that is either fixed or relative to the sun
+///
+/// Parsed from `HH:MM`, `sunrise`, `sunset`, or either of the latter with an
+/// offset in minutes such as `sunset-15` or `sunrise+30m`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolarTime {
+    Fixed(ScheduleTime),
+    Sunrise { offset_minutes: i32 },
+    Sunset { offset_minutes: i32 },
+}
+
+impl SolarTime {
+    /// The schedule time on a day, or `None` when the sun doesn't rise or set
+    pub fn resolve(&self, times: &SolarTimes) -> Option<ScheduleTime> {
+        let (base, offset) = match self {
+            SolarTime::Fixed(time) => return Some(time.clone()),
+            SolarTime::Sunrise { offset_minutes } => (times.sunrise?, *offset_minutes),
+            SolarTime::Sunset { offset_minutes } => (times.sunset?, *offset_minutes),
+        };
+        let minute = minute_of_day(&ScheduleTime::from(base)) as i32 + offset;
+        Some(time_of_day(minute.rem_euclid(MINUTES_PER_DAY as i32) as u32))
+    }
+}
+
+impl FromStr for SolarTime {
+    type Err = TrimlightError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || TrimlightError::ApiError {
+            code: 400,
+            message: format!(
+                "Invalid time '{}'. Use HH:MM, sunrise or sunset with an offset like sunset-15",
+                s
+            ),
+        };
+        let spec: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+        let spec = spec.to_lowercase();
+
+        let (event, rest) = if let Some(rest) = spec.strip_prefix("sunrise") {
+            ("sunrise", rest)
+        } else if let Some(rest) = spec.strip_prefix("sunset") {
+            ("sunset", rest)
+        } else {
+            let (hours, minutes) = crate::utils::parse_time(&spec).map_err(|_| invalid())?;
+            return Ok(SolarTime::Fixed(ScheduleTime::new(hours, minutes)?));
+        };
+
+        let offset_minutes = if rest.is_empty() {
+            0
+        } else {
+            let amount = rest
+                .trim_end_matches("min")
+                .trim_end_matches('m')
+                .parse::<i32>()
+                .map_err(|_| invalid())?;
+            if !rest.starts_with(['+', '-']) {
+                return Err(invalid());
+            }
+            amount
+        };
+
+        Ok(match event {
+            "sunrise" => SolarTime::Sunrise { offset_minutes },
+            _ => SolarTime::Sunset { offset_minutes },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn assert_near(actual: Option<NaiveTime>, expected: &str) {
+        let expected = NaiveTime::parse_from_str(expected, "%H:%M").unwrap();
+        let actual = actual.expect("sun rises and sets");
+        let difference = (actual - expected).num_minutes().abs();
+        assert!(difference <= 2, "expected {} but got {}", expected, actual);
+    }
+
+    #[test]
+    fn test_solar_times() {
+        // New York, values from the NOAA solar calculator
+        let summer = solar_times(date(2026, 6, 21), 40.7128, -74.0060, -240);
+        assert_near(summer.sunrise, "05:25");
+        assert_near(summer.sunset, "20:31");
+        let winter = solar_times(date(2026, 12, 21), 40.7128, -74.0060, -300);
+        assert_near(winter.sunrise, "07:17");
+        assert_near(winter.sunset, "16:32");
+
+        // Greenwich around the March equinox
+        let equinox = solar_times(date(2026, 3, 20), 51.4769, 0.0, 0);
+        assert_near(equinox.sunrise, "06:02");
+        assert_near(equinox.sunset, "18:14");
+
+        // Sydney in its summer
+        let sydney = solar_times(date(2026, 12, 21), -33.8688, 151.2093, 660);
+        assert_near(sydney.sunrise, "05:41");
+        assert_near(sydney.sunset, "20:05");
+    }
+
+    #[test]
+    fn test_polar_day_and_night() {
+        let night = solar_times(date(2026, 12, 21), 69.6496, 18.9560, 60);
+        assert_eq!(night.sunrise, None);
+        assert_eq!(night.sunset, None);
+
+        let day = solar_times(date(2026, 6, 21), 69.6496, 18.9560, 120);
+        assert_eq!(day.sunset, None);
+    }
+
+    #[test]
+    fn test_parse_solar_time() {
+        assert_eq!(
+            "sunset-15".parse::<SolarTime>().unwrap(),
+            SolarTime::Sunset {
+                offset_minutes: -15
+            }
+        );
+        assert_eq!(
+            "Sunset - 15min".parse::<SolarTime>().unwrap(),
+            SolarTime::Sunset {
+                offset_minutes: -15
+            }
+        );
+        assert_eq!(
+            "sunrise+30m".parse::<SolarTime>().unwrap(),
+            SolarTime::Sunrise { offset_minutes: 30 }
+        );
+        assert_eq!(
+            "sunrise".parse::<SolarTime>().unwrap(),
+            SolarTime::Sunrise { offset_minutes: 0 }
+        );
+        assert_eq!(
+            "23:00".parse::<SolarTime>().unwrap(),
+            SolarTime::Fixed(ScheduleTime::new(23, 0).unwrap())
+        );
+        assert!("sunset15".parse::<SolarTime>().is_err());
+        assert!("dusk".parse::<SolarTime>().is_err());
+        assert!("25:00".parse::<SolarTime>().is_err());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let times = SolarTimes {
+            sunrise: NaiveTime::from_hms_opt(7, 17, 0),
+            sunset: NaiveTime::from_hms_opt(16, 32, 0),
+        };
+        let resolve = |spec: &str| spec.parse::<SolarTime>().unwrap().resolve(&times);
+        assert_eq!(
+            resolve("sunset-15"),
+            Some(ScheduleTime::new(16, 17).unwrap())
+        );
+        assert_eq!(
+            resolve("sunrise+50"),
+            Some(ScheduleTime::new(8, 7).unwrap())
+        );
+        assert_eq!(resolve("23:00"), Some(ScheduleTime::new(23, 0).unwrap()));
+
+        let polar = SolarTimes {
+            sunrise: None,
+            sunset: None,
+        };
+        assert_eq!("sunset".parse::<SolarTime>().unwrap().resolve(&polar), None);
+    }
+}
@@ -0,0 +1,40 @@
+//! Fixtures shared by the unit tests
+
+use crate::models::{CalendarSchedule, DailySchedule, DeviceDetails, ScheduleDate, ScheduleTime};
+use chrono::NaiveDate;
+
+/// An enabled daily schedule running effect `id + 10`
+pub(crate) fn daily(id: i32, repetition: i32, start: (i32, i32), end: (i32, i32)) -> DailySchedule {
+    DailySchedule {
+        id,
+        enable: true,
+        effect_id: id + 10,
+        repetition,
+        start_time: ScheduleTime::new(start.0, start.1).unwrap(),
+        end_time: ScheduleTime::new(end.0, end.1).unwrap(),
+        ..Default::default()
+    }
+}
+
+/// A calendar schedule running effect `id + 20` from 18:00 to 23:00
+pub(crate) fn calendar(id: i32, start: (i32, i32), end: (i32, i32)) -> CalendarSchedule {
+    CalendarSchedule {
+        id,
+        effect_id: id + 20,
+        start_date: ScheduleDate::new(start.0, start.1).unwrap(),
+        end_date: ScheduleDate::new(end.0, end.1).unwrap(),
+        start_time: ScheduleTime::new(18, 0).unwrap(),
+        end_time: ScheduleTime::new(23, 0).unwrap(),
+        ..Default::default()
+    }
+}
+
+/// A Saturday
+pub(crate) fn today() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2026, 12, 19).unwrap()
+}
+
+/// Device details from the JSON the API returns
+pub(crate) fn device(value: serde_json::Value) -> DeviceDetails {
+    serde_json::from_value(value).unwrap()
+}
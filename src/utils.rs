@@ -1,4 +1,5 @@
 use crate::error::TrimlightError;
+use crate::models::{ScheduleDate, ScheduleTime};
 
 /// Parse time string in HH:MM format
 pub fn parse_time(time: &str) -> Result<(i32, i32), TrimlightError> {
@@ -26,14 +27,8 @@ pub fn parse_time(time: &str) -> Result<(i32, i32), TrimlightError> {
             message: "Invalid minutes".to_string(),
         })?;
 
-    if hours < 0 || hours > 23 || minutes < 0 || minutes > 59 {
-        return Err(TrimlightError::ApiError {
-            code: 400,
-            message: "Invalid time values".to_string(),
-        });
-    }
-
-    Ok((hours, minutes))
+    let time = ScheduleTime::new(hours, minutes)?;
+    Ok((time.hours, time.minutes))
 }
 
 /// Parse date string in MM-DD format
@@ -60,14 +55,8 @@ pub fn parse_date(date: &str) -> Result<(i32, i32), TrimlightError> {
             message: "Invalid day".to_string(),
         })?;
 
-    if month < 1 || month > 12 || day < 1 || day > 31 {
-        return Err(TrimlightError::ApiError {
-            code: 400,
-            message: "Invalid date values".to_string(),
-        });
-    }
-
-    Ok((month, day))
+    let date = ScheduleDate::new(month, day)?;
+    Ok((date.month, date.day))
 }
 
 #[cfg(test)]
@@ -157,6 +146,7 @@ mod tests {
         assert_eq!(parse_date("01-01").unwrap(), (1, 1));
         assert_eq!(parse_date("12-31").unwrap(), (12, 31));
         assert_eq!(parse_date("02-28").unwrap(), (2, 28));
+        assert_eq!(parse_date("02-29").unwrap(), (2, 29));
         assert_eq!(parse_date("09-05").unwrap(), (9, 5));
     }
 
@@ -226,5 +216,21 @@ mod tests {
                 message
             }) if message == "Invalid date values"
         ));
+
+        // Test days that don't exist in the month
+        assert!(matches!(
+            parse_date("02-31"),
+            Err(TrimlightError::ApiError {
+                code: 400,
+                message
+            }) if message == "Invalid date values"
+        ));
+        assert!(matches!(
+            parse_date("04-31"),
+            Err(TrimlightError::ApiError {
+                code: 400,
+                message
+            }) if message == "Invalid date values"
+        ));
     }
 }
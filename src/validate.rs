@@ -0,0 +1,519 @@
+//! Consistency checks on a device's saved state.
+//!
+//! [`validate`] looks for things the device accepts but that don't work as
+//! intended: schedules, combined effects and overlays that refer to deleted
+//! effects, effect settings outside the ranges the API documents, custom
+//! patterns longer than the LEDs on the ports, and calendar dates that don't
+//! exist. Each [`Finding`] carries a `trimlight-cli` command that fixes it.
+
+use serde::Serialize;
+use std::fmt;
+
+use crate::models::{
+    DeviceDetails, Effect, EffectReference, ScheduleDate, ScheduleTime, ALT_BUILTIN_CATEGORY,
+    BUILTIN_CATEGORY, CUSTOM_CATEGORY,
+};
+
+/// Highest pixel length the API accepts for built-in effects
+const MAX_PIXEL_LEN: i32 = 90;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Something the device can't run as saved
+    Error,
+    /// Something that runs, but probably not as intended
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A problem found by [`validate`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    pub severity: Severity,
+    /// What the problem is on, e.g. `daily schedule 0` or `effect "Storm" (id 3)`
+    pub item: String,
+    pub message: String,
+    /// Command that fixes the problem
+    pub fix: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.item, self.message)
+    }
+}
+
+/// Check a device for broken references, out-of-range settings and invalid dates
+///
+/// `device_id` is only used to build the suggested fix commands. Errors are
+/// listed before warnings.
+pub fn validate(device_id: &str, details: &DeviceDetails) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut add = |severity: Severity, item: String, message: String, fix: String| {
+        findings.push(Finding {
+            severity,
+            item,
+            message,
+            fix,
+        })
+    };
+    let cli = |command: String| format!("trimlight-cli {} --device {}", command, device_id);
+    let missing = |effect_id: i32| details.effect_by_id(effect_id).is_none();
+
+    // Schedules
+    for schedule in &details.daily {
+        let item = EffectReference::DailySchedule { id: schedule.id }.to_string();
+        if missing(schedule.effect_id) {
+            add(
+                Severity::Error,
+                item.clone(),
+                format!("runs effect {}, which doesn't exist", schedule.effect_id),
+                cli(format!(
+                    "schedule delete --id {} --schedule-type daily",
+                    schedule.id
+                )),
+            );
+        } else if !schedule.enable {
+            add(
+                Severity::Warning,
+                item.clone(),
+                format!(
+                    "is disabled, so it never runs effect {}",
+                    schedule.effect_id
+                ),
+                cli(format!(
+                    "schedule toggle --id {} --schedule-type daily --enable",
+                    schedule.id
+                )),
+            );
+        }
+        for (field, time) in [("start", &schedule.start_time), ("end", &schedule.end_time)] {
+            if !valid_time(time) {
+                add(
+                    Severity::Error,
+                    item.clone(),
+                    format!("has an invalid {} time {}", field, format_time(time)),
+                    cli(format!(
+                        "schedule modify --id {} --schedule-type daily --{} 00:00",
+                        schedule.id, field
+                    )),
+                );
+            }
+        }
+    }
+    for schedule in &details.calendar {
+        let item = EffectReference::CalendarSchedule { id: schedule.id }.to_string();
+        if missing(schedule.effect_id) {
+            add(
+                Severity::Error,
+                item.clone(),
+                format!("runs effect {}, which doesn't exist", schedule.effect_id),
+                cli(format!(
+                    "schedule delete --id {} --schedule-type calendar",
+                    schedule.id
+                )),
+            );
+        }
+        for (field, date) in [("start", &schedule.start_date), ("end", &schedule.end_date)] {
+            if date.validate().is_err() {
+                add(
+                    Severity::Error,
+                    item.clone(),
+                    format!(
+                        "has an invalid {} date {:02}-{:02}",
+                        field, date.month, date.day
+                    ),
+                    cli(format!(
+                        "schedule modify --id {} --schedule-type calendar --{}-date {}",
+                        schedule.id,
+                        field,
+                        nearest_date(date)
+                    )),
+                );
+            }
+        }
+        for (field, time) in [("start", &schedule.start_time), ("end", &schedule.end_time)] {
+            if !valid_time(time) {
+                add(
+                    Severity::Error,
+                    item.clone(),
+                    format!("has an invalid {} time {}", field, format_time(time)),
+                    cli(format!(
+                        "schedule modify --id {} --schedule-type calendar --{} 00:00",
+                        schedule.id, field
+                    )),
+                );
+            }
+        }
+    }
+
+    // Combined effect
+    if let Some(combined) = &details.combined_effect {
+        let dangling: Vec<i32> = combined
+            .effect_ids
+            .iter()
+            .copied()
+            .filter(|id| missing(*id))
+            .collect();
+        if !dangling.is_empty() {
+            let remaining: Vec<String> = combined
+                .effect_ids
+                .iter()
+                .filter(|id| !dangling.contains(id))
+                .map(|id| id.to_string())
+                .collect();
+            let fix = if remaining.is_empty() {
+                cli("effects combined clear".to_string())
+            } else {
+                cli(format!(
+                    "effects combined set --effects {} --interval {}",
+                    remaining.join(","),
+                    combined.interval
+                ))
+            };
+            add(
+                Severity::Error,
+                EffectReference::CombinedEffect.to_string(),
+                format!("includes effects that don't exist: {}", join_ids(&dangling)),
+                fix,
+            );
+        }
+    }
+
+    // Overlays
+    if details
+        .overlay_effects
+        .iter()
+        .any(|o| missing(o.target_effect))
+    {
+        let mut commands = vec![cli("effects overlay clear".to_string())];
+        commands.extend(
+            details
+                .overlay_effects
+                .iter()
+                .filter(|o| !missing(o.target_effect))
+                .filter_map(|o| {
+                    let flag = match o.overlay_type {
+                        0 => "--lightning",
+                        1 => "--snow",
+                        _ => return None,
+                    };
+                    Some(cli(format!(
+                        "effects overlay add {} --target {}",
+                        flag, o.target_effect
+                    )))
+                }),
+        );
+        let fix = commands.join(" && ");
+        for overlay in details
+            .overlay_effects
+            .iter()
+            .filter(|o| missing(o.target_effect))
+        {
+            add(
+                Severity::Error,
+                EffectReference::Overlay {
+                    overlay_type: overlay.overlay_type,
+                }
+                .to_string(),
+                format!(
+                    "targets effect {}, which doesn't exist",
+                    overlay.target_effect
+                ),
+                fix.clone(),
+            );
+        }
+    }
+
+    // Effects
+    let port_leds: i32 = details
+        .ports
+        .iter()
+        .map(|p| (p.end - p.start + 1).max(0))
+        .sum();
+    for effect in &details.effects {
+        let item = format!("effect {:?} (id {})", effect.name, effect.id);
+        let update = |args: String| {
+            cli(format!(
+                "effects update --id {}{} {}",
+                effect.id,
+                mode_arg(effect),
+                args
+            ))
+        };
+        for (field, value) in [("speed", effect.speed), ("brightness", effect.brightness)] {
+            if !(0..=255).contains(&value) {
+                add(
+                    Severity::Error,
+                    item.clone(),
+                    format!("{} {} is outside 0-255", field, value),
+                    update(format!("--{} {}", field, value.clamp(0, 255))),
+                );
+            }
+        }
+        match effect.category {
+            BUILTIN_CATEGORY | ALT_BUILTIN_CATEGORY => {
+                if let Some(pixel_len) = effect.pixel_len {
+                    if !(1..=MAX_PIXEL_LEN).contains(&pixel_len) {
+                        add(
+                            Severity::Error,
+                            item.clone(),
+                            format!("pixel length {} is outside 1-{}", pixel_len, MAX_PIXEL_LEN),
+                            update(format!("--pixel-len {}", pixel_len.clamp(1, MAX_PIXEL_LEN))),
+                        );
+                    }
+                }
+            }
+            CUSTOM_CATEGORY => {
+                let pixels = effect.pixels.as_deref().unwrap_or_default();
+                let count: i32 = pixels.iter().map(|p| p.count.max(0)).sum();
+                if port_leds > 0 && count > port_leds {
+                    add(
+                        Severity::Warning,
+                        item.clone(),
+                        format!(
+                            "pattern covers {} LEDs, but the ports only have {}",
+                            count, port_leds
+                        ),
+                        update(format!(
+                            "--pixels \"{}\"",
+                            trimmed_pixels(effect, port_leds)
+                        )),
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+
+    findings.sort_by_key(|f| f.severity);
+    findings
+}
+
+fn valid_time(time: &ScheduleTime) -> bool {
+    (0..=23).contains(&time.hours) && (0..=59).contains(&time.minutes)
+}
+
+fn format_time(time: &ScheduleTime) -> String {
+    format!("{:02}:{:02}", time.hours, time.minutes)
+}
+
+/// The closest real date, in `MM-DD` format
+fn nearest_date(date: &ScheduleDate) -> String {
+    let month = date.month.clamp(1, 12);
+    let days = ScheduleDate::days_in_month(month).unwrap_or(31);
+    format!("{:02}-{:02}", month, date.day.clamp(1, days))
+}
+
+fn join_ids(ids: &[i32]) -> String {
+    ids.iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// `effects update` needs the mode to change category-specific fields
+fn mode_arg(effect: &Effect) -> String {
+    match effect.category {
+        BUILTIN_CATEGORY | ALT_BUILTIN_CATEGORY => format!(" --built-in {}", effect.mode),
+        CUSTOM_CATEGORY => format!(" --pattern {}", effect.mode),
+        _ => String::new(),
+    }
+}
+
+/// The effect's pixels in `--pixels` format, cut off after `leds` LEDs
+fn trimmed_pixels(effect: &Effect, leds: i32) -> String {
+    let mut remaining = leds;
+    let mut segments = Vec::new();
+    for pixel in effect.pixels.as_deref().unwrap_or_default() {
+        if remaining <= 0 {
+            break;
+        }
+        let count = pixel.count.clamp(0, remaining);
+        remaining -= count;
+        segments.push(format!(
+            "{},{},{}:{}:{}",
+            (pixel.color >> 16) & 0xff,
+            (pixel.color >> 8) & 0xff,
+            pixel.color & 0xff,
+            count,
+            pixel.disable as i32
+        ));
+    }
+    segments.join(";")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::device;
+
+    /// Findings as `severity item: message => fix` lines
+    fn findings(details: &DeviceDetails) -> Vec<String> {
+        validate("abc123", details)
+            .iter()
+            .map(|f| format!("{} {} => {}", f.severity, f, f.fix))
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_device() {
+        let details = device(serde_json::json!({
+            "ports": [{"id": 0, "start": 1, "end": 100}],
+            "effects": [
+                {"id": 1, "name": "Warm White", "category": 1, "mode": 0, "speed": 100,
+                 "brightness": 200, "pixelLen": 30},
+                {"id": 2, "name": "Candy Cane", "category": 2, "mode": 1, "speed": 100,
+                 "brightness": 200, "pixels": [{"index": 0, "count": 50, "color": 16711680}]}
+            ],
+            "daily": [{"id": 0, "enable": true, "effectId": 1, "repetition": 1}],
+            "calendar": [{"id": 1, "effectId": 2, "startDate": {"month": 12, "day": 1},
+                          "endDate": {"month": 2, "day": 29}}],
+            "combinedEffect": {"effectIds": [1, 2], "interval": 30},
+            "overlayEffects": [{"overlayType": 1, "targetEffect": 2}]
+        }));
+        assert_eq!(validate("abc123", &details), []);
+    }
+
+    #[test]
+    fn test_findings() {
+        let details = device(serde_json::json!({
+            "ports": [{"id": 0, "start": 1, "end": 10}],
+            "effects": [
+                {"id": 1, "name": "Warm White", "category": 1, "mode": 0, "speed": 300,
+                 "brightness": 200, "pixelLen": 120},
+                {"id": 2, "name": "Candy Cane", "category": 2, "mode": 1, "speed": 100,
+                 "brightness": 200, "pixels": [
+                    {"index": 0, "count": 6, "color": 16711680},
+                    {"index": 1, "count": 6, "color": 16777215}
+                ]}
+            ],
+            "daily": [
+                {"id": 0, "enable": true, "effectId": 7, "repetition": 1},
+                {"id": 1, "enable": false, "effectId": 1, "repetition": 1}
+            ],
+            "calendar": [{"id": 4, "effectId": 2, "startDate": {"month": 2, "day": 30},
+                          "endDate": {"month": 3, "day": 1}}],
+            "combinedEffect": {"effectIds": [1, 5], "interval": 30},
+            "overlayEffects": [
+                {"overlayType": 0, "targetEffect": 1},
+                {"overlayType": 1, "targetEffect": 9}
+            ]
+        }));
+
+        assert_eq!(
+            findings(&details),
+            [
+                "error daily schedule 0: runs effect 7, which doesn't exist => \
+                 trimlight-cli schedule delete --id 0 --schedule-type daily --device abc123",
+                "error calendar schedule 4: has an invalid start date 02-30 => \
+                 trimlight-cli schedule modify --id 4 --schedule-type calendar --start-date 02-29 --device abc123",
+                "error combined effect: includes effects that don't exist: 5 => \
+                 trimlight-cli effects combined set --effects 1 --interval 30 --device abc123",
+                "error snow overlay: targets effect 9, which doesn't exist => \
+                 trimlight-cli effects overlay clear --device abc123 && \
+                 trimlight-cli effects overlay add --lightning --target 1 --device abc123",
+                "error effect \"Warm White\" (id 1): speed 300 is outside 0-255 => \
+                 trimlight-cli effects update --id 1 --built-in 0 --speed 255 --device abc123",
+                "error effect \"Warm White\" (id 1): pixel length 120 is outside 1-90 => \
+                 trimlight-cli effects update --id 1 --built-in 0 --pixel-len 90 --device abc123",
+                "warning daily schedule 1: is disabled, so it never runs effect 1 => \
+                 trimlight-cli schedule toggle --id 1 --schedule-type daily --enable --device abc123",
+                "warning effect \"Candy Cane\" (id 2): pattern covers 12 LEDs, but the ports only have 10 => \
+                 trimlight-cli effects update --id 2 --pattern 1 --pixels \"255,0,0:6:0;255,255,255:4:0\" --device abc123",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_times() {
+        let details = device(serde_json::json!({
+            "effects": [{"id": 1, "name": "Warm White", "category": 1, "mode": 0}],
+            "daily": [{"id": 0, "enable": true, "effectId": 1, "repetition": 1,
+                       "startTime": {"hours": 24, "minutes": 0},
+                       "endTime": {"hours": 23, "minutes": 60}}],
+            "calendar": [{"id": 1, "effectId": 1,
+                          "startDate": {"month": 12, "day": 1}, "endDate": {"month": 12, "day": 31},
+                          "startTime": {"hours": 18, "minutes": 0},
+                          "endTime": {"hours": 25, "minutes": 30}}]
+        }));
+        assert_eq!(
+            findings(&details),
+            [
+                "error daily schedule 0: has an invalid start time 24:00 => \
+                 trimlight-cli schedule modify --id 0 --schedule-type daily --start 00:00 --device abc123",
+                "error daily schedule 0: has an invalid end time 23:60 => \
+                 trimlight-cli schedule modify --id 0 --schedule-type daily --end 00:00 --device abc123",
+                "error calendar schedule 1: has an invalid end time 25:30 => \
+                 trimlight-cli schedule modify --id 1 --schedule-type calendar --end 00:00 --device abc123",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_out_of_range_brightness() {
+        let details = device(serde_json::json!({
+            "effects": [
+                {"id": 1, "name": "Warm White", "category": 1, "mode": 0, "speed": 100,
+                 "brightness": 300},
+                {"id": 2, "name": "Candy Cane", "category": 2, "mode": 1, "speed": 100,
+                 "brightness": -5, "pixels": []}
+            ]
+        }));
+        assert_eq!(
+            findings(&details),
+            [
+                "error effect \"Warm White\" (id 1): brightness 300 is outside 0-255 => \
+                 trimlight-cli effects update --id 1 --built-in 0 --brightness 255 --device abc123",
+                "error effect \"Candy Cane\" (id 2): brightness -5 is outside 0-255 => \
+                 trimlight-cli effects update --id 2 --pattern 1 --brightness 0 --device abc123",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_combined_effect_without_existing_effects() {
+        let details = device(serde_json::json!({
+            "effects": [{"id": 1, "name": "Warm White", "category": 1, "mode": 0}],
+            "combinedEffect": {"effectIds": [5, 6], "interval": 30}
+        }));
+        assert_eq!(
+            findings(&details),
+            [
+                "error combined effect: includes effects that don't exist: 5, 6 => \
+              trimlight-cli effects combined clear --device abc123"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_overlay_types() {
+        // Unknown types are reported when their target is gone, but the CLI
+        // can't add them back, so the fix only restores the known ones
+        let details = device(serde_json::json!({
+            "effects": [{"id": 1, "name": "Warm White", "category": 1, "mode": 0}],
+            "overlayEffects": [
+                {"overlayType": 7, "targetEffect": 1},
+                {"overlayType": 8, "targetEffect": 9},
+                {"overlayType": 1, "targetEffect": 1}
+            ]
+        }));
+        assert_eq!(
+            findings(&details),
+            [
+                "error overlay type 8: targets effect 9, which doesn't exist => \
+              trimlight-cli effects overlay clear --device abc123 && \
+              trimlight-cli effects overlay add --snow --target 1 --device abc123"
+            ]
+        );
+    }
+}